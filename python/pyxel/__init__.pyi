@@ -1,5 +1,7 @@
 # flake8: noqa
-from typing import Any, Callable, Generic, List, Optional, Tuple, TypeVar, Union
+from typing import Any, Callable, Dict, Generic, List, Optional, Tuple, TypeVar, Union
+
+_Tile = Union[Tuple[int, int], Tuple[int, int, int]]
 
 # Constants
 VERSION: str
@@ -40,6 +42,46 @@ COLOR_PEACH: int
 
 FONT_WIDTH: int
 FONT_HEIGHT: int
+FILL_RULE_EVEN_ODD: int
+FILL_RULE_NONZERO: int
+LINE_CAP_BUTT: int
+LINE_CAP_ROUND: int
+LINE_CAP_SQUARE: int
+BLEND_NONE: int
+BLEND_ADD: int
+BLEND_SUBTRACT: int
+BLEND_MULTIPLY: int
+BLEND_AVERAGE: int
+ANIM_LOOP: int
+ANIM_PINGPONG: int
+ANIM_ONESHOT: int
+TILE_FLIP_X: int
+TILE_FLIP_Y: int
+# Only applies to square tiles, ignored when tile_width != tile_height
+TILE_ROTATE: int
+TILE_PALETTE_SHIFT: int
+DIAGONAL_NONE: int
+DIAGONAL_NO_CORNERS: int
+DIAGONAL_ALL: int
+TERRAIN_EDGES: int
+TERRAIN_BLOB: int
+TERRAIN_N: int
+TERRAIN_NE: int
+TERRAIN_E: int
+TERRAIN_SE: int
+TERRAIN_S: int
+TERRAIN_SW: int
+TERRAIN_W: int
+TERRAIN_NW: int
+ALIGN_LEFT: int
+ALIGN_CENTER: int
+ALIGN_RIGHT: int
+ALIGN_JUSTIFY: int
+QUANTIZE_MEDIAN_CUT: int
+QUANTIZE_KMEANS: int
+IMPORT_DITHER_NONE: int
+IMPORT_DITHER_FLOYD_STEINBERG: int
+IMPORT_DITHER_ORDERED: int
 
 NUM_CHANNELS: int
 NUM_TONES: int
@@ -417,6 +459,26 @@ class Seq(Generic[T]):
 # Font class
 class Font:
     def __init__(self, filename: str, font_size: Optional[float] = None) -> None: ...
+    @staticmethod
+    def builtin() -> Font: ...
+    @staticmethod
+    def stack(fonts: List[Font]) -> Font: ...
+    @staticmethod
+    def from_image(
+        img: Union[int, Image],
+        x: int,
+        y: int,
+        w: int,
+        h: int,
+        cell_w: int,
+        cell_h: int,
+        chars: str,
+        *,
+        variable_width: Optional[bool] = None,
+        multicolor: Optional[bool] = None,
+        colkey: Optional[int] = None,
+        spacing: Optional[int] = None,
+    ) -> Font: ...
     def text_width(self, s: str) -> int: ...
 
 # Image class
@@ -430,7 +492,30 @@ class Image:
         filename: str,
         *,
         include_colors: Optional[bool] = None,
+        max_colors: Optional[int] = None,
+        quantize: Optional[int] = None,
+        dither: Optional[int] = None,
+        transparent: Optional[int] = None,
+        perceptual: Optional[bool] = None,
     ) -> Image: ...
+    @staticmethod
+    def import_image(
+        filename: str,
+        *,
+        include_colors: Optional[bool] = None,
+        max_colors: Optional[int] = None,
+        quantize: Optional[int] = None,
+        dither: Optional[int] = None,
+        transparent: Optional[int] = None,
+        perceptual: Optional[bool] = None,
+    ) -> Tuple[Image, Dict[str, Any]]: ...
+    @staticmethod
+    def from_aseprite(
+        filename: str,
+        *,
+        layer: Optional[int] = None,
+        include_colors: Optional[bool] = None,
+    ) -> Tuple[Image, Dict[str, Any]]: ...
     def data_ptr(self) -> Any: ...
     def set(self, x: int, y: int, data: List[str]) -> None: ...
     def load(
@@ -441,7 +526,17 @@ class Image:
         *,
         include_colors: Optional[bool] = None,
     ) -> None: ...
+    def load_aseprite(
+        self,
+        x: int,
+        y: int,
+        filename: str,
+        *,
+        layer: Optional[int] = None,
+        include_colors: Optional[bool] = None,
+    ) -> Dict[str, Any]: ...
     def save(self, filename: str, scale: int) -> None: ...
+    def to_rgba(self, scale: Optional[int] = None) -> bytes: ...
     def clip(
         self,
         x: Optional[float] = None,
@@ -449,6 +544,12 @@ class Image:
         w: Optional[float] = None,
         h: Optional[float] = None,
     ) -> None: ...
+    def mask(
+        self,
+        img: Optional[Union[int, Image]] = None,
+        col: Optional[int] = None,
+        invert: Optional[bool] = None,
+    ) -> None: ...
     def camera(
         self,
         x: Optional[float] = None,
@@ -456,6 +557,7 @@ class Image:
     ) -> None: ...
     def pal(self, col1: Optional[int] = None, col2: Optional[int] = None) -> None: ...
     def dither(self, alpha: float) -> None: ...
+    def blend(self, mode: int) -> None: ...
     def cls(self, col: int) -> None: ...
     def pget(self, x: float, y: float) -> int: ...
     def pset(self, x: float, y: float, col: int) -> None: ...
@@ -472,6 +574,27 @@ class Image:
     def trib(
         self, x1: float, y1: float, x2: float, y2: float, x3: float, y3: float, col: int
     ) -> None: ...
+    def poly(
+        self, pts: List[Tuple[float, float]], col: int, rule: Optional[int] = None
+    ) -> None: ...
+    def polyb(self, pts: List[Tuple[float, float]], col: int) -> None: ...
+    def thick_line(
+        self,
+        x1: float,
+        y1: float,
+        x2: float,
+        y2: float,
+        w: float,
+        col: int,
+        cap: Optional[int] = None,
+    ) -> None: ...
+    def arc(
+        self, x: float, y: float, r: float, a1: float, a2: float, col: int
+    ) -> None: ...
+    def pie(
+        self, x: float, y: float, r: float, a1: float, a2: float, col: int
+    ) -> None: ...
+    def bezier(self, pts: List[Tuple[float, float]], col: int) -> None: ...
     def fill(self, x: float, y: float, col: int) -> None: ...
     def blt(
         self,
@@ -491,7 +614,7 @@ class Image:
         self,
         x: float,
         y: float,
-        tm: Union[int, Tilemap],
+        tm: Union[int, Tilemap, ChunkedTilemap],
         u: float,
         v: float,
         w: float,
@@ -501,20 +624,111 @@ class Image:
         rotate: Optional[float] = None,
         scale: Optional[float] = None,
     ) -> None: ...
+    def blt_tri(
+        self,
+        x1: float,
+        y1: float,
+        x2: float,
+        y2: float,
+        x3: float,
+        y3: float,
+        img: Union[int, Image],
+        u1: float,
+        v1: float,
+        u2: float,
+        v2: float,
+        u3: float,
+        v3: float,
+        colkey: Optional[int] = None,
+    ) -> None: ...
+    def blt_perspective(
+        self,
+        x: float,
+        y: float,
+        w: float,
+        h: float,
+        img: Union[int, Image],
+        lines: List[Optional[Tuple[float, float, float, float]]],
+        colkey: Optional[int] = None,
+        wrap: Optional[bool] = None,
+    ) -> None: ...
+    def bltm_perspective(
+        self,
+        x: float,
+        y: float,
+        w: float,
+        h: float,
+        tm: Union[int, Tilemap],
+        lines: List[Optional[Tuple[float, float, float, float]]],
+        colkey: Optional[int] = None,
+        wrap: Optional[bool] = None,
+    ) -> None: ...
     def text(
         self, x: float, y: float, s: str, col: int, font: Optional[Font] = None
     ) -> None: ...
+    def text_box(
+        self,
+        x: float,
+        y: float,
+        s: str,
+        col: int,
+        *,
+        font: Optional[Font] = None,
+        fonts: Optional[List[Font]] = None,
+        w: Optional[int] = None,
+        align: Optional[int] = None,
+        line_spacing: Optional[int] = None,
+        shadow: Optional[Tuple[int, int, int]] = None,
+        outline: Optional[int] = None,
+    ) -> Tuple[int, int, int, int]: ...
 
 # Tilemap class
+class Tileset:
+    def __init__(self) -> None: ...
+    def set_tile(
+        self,
+        tile: _Tile,
+        *,
+        frames: Optional[List[Tuple[int, int, int]]] = None,
+        solid: Optional[bool] = None,
+        one_way: Optional[bool] = None,
+        slope: Optional[Tuple[float, float]] = None,
+        damage: Optional[int] = None,
+        friction: Optional[float] = None,
+    ) -> None: ...
+    def get_tile(self, tile: _Tile) -> Optional[Dict[str, Any]]: ...
+    def remove_tile(self, tile: _Tile) -> None: ...
+
+class Terrain:
+    mode: int
+
+    def __init__(self, mode: Optional[int] = None) -> None: ...
+    def set_rule(self, mask: int, tile: Tuple[int, int]) -> None: ...
+    def get_rule(self, mask: int) -> Optional[Tuple[int, int]]: ...
+    def remove_rule(self, mask: int) -> None: ...
+    def normalize_mask(self, mask: int) -> int: ...
+
 class Tilemap:
     width: int
     height: int
     imgsrc: Union[int, Image]
+    tileset: Optional[Tileset]
+    tile_width: int
+    tile_height: int
 
-    def __init__(self, width: int, height: int, img: Union[int, Image]) -> None: ...
+    def __init__(
+        self,
+        width: int,
+        height: int,
+        img: Union[int, Image],
+        tile_width: Optional[int] = None,
+        tile_height: Optional[int] = None,
+    ) -> None: ...
     @staticmethod
     def from_tmx(filename: str, layer: int) -> Image: ...
+    # 3 bytes per tile (x, y, attr) since tile attributes were added, previously 2 bytes
     def data_ptr(self) -> Any: ...
+    def tile_data_ptr(self) -> Any: ...
     def set(self, x: int, y: int, data: List[str]) -> None: ...
     def load(self, x: int, y: int, filename: str, layer: int) -> None: ...
     def clip(
@@ -529,26 +743,16 @@ class Tilemap:
         x: Optional[float] = None,
         y: Optional[float] = None,
     ) -> None: ...
-    def cls(self, tile: Tuple[int, int]) -> None: ...
-    def pget(self, x: float, y: float) -> Tuple[int, int]: ...
-    def pset(self, x: float, y: float, tile: Tuple[int, int]) -> None: ...
-    def line(
-        self, x1: float, y1: float, x2: float, y2: float, tile: Tuple[int, int]
-    ) -> None: ...
-    def rect(
-        self, x: float, y: float, w: float, h: float, tile: Tuple[int, int]
-    ) -> None: ...
-    def rectb(
-        self, x: float, y: float, w: float, h: float, tile: Tuple[int, int]
-    ) -> None: ...
-    def circ(self, x: float, y: float, r: float, tile: Tuple[int, int]) -> None: ...
-    def circb(self, x: float, y: float, r: float, tile: Tuple[int, int]) -> None: ...
-    def elli(
-        self, x: float, y: float, w: float, h: float, tile: Tuple[int, int]
-    ) -> None: ...
-    def ellib(
-        self, x: float, y: float, w: float, h: float, tile: Tuple[int, int]
-    ) -> None: ...
+    def cls(self, tile: _Tile) -> None: ...
+    def pget(self, x: float, y: float) -> Tuple[int, int, int]: ...
+    def pset(self, x: float, y: float, tile: _Tile) -> None: ...
+    def line(self, x1: float, y1: float, x2: float, y2: float, tile: _Tile) -> None: ...
+    def rect(self, x: float, y: float, w: float, h: float, tile: _Tile) -> None: ...
+    def rectb(self, x: float, y: float, w: float, h: float, tile: _Tile) -> None: ...
+    def circ(self, x: float, y: float, r: float, tile: _Tile) -> None: ...
+    def circb(self, x: float, y: float, r: float, tile: _Tile) -> None: ...
+    def elli(self, x: float, y: float, w: float, h: float, tile: _Tile) -> None: ...
+    def ellib(self, x: float, y: float, w: float, h: float, tile: _Tile) -> None: ...
     def tri(
         self,
         x1: float,
@@ -557,7 +761,7 @@ class Tilemap:
         y2: float,
         x3: float,
         y3: float,
-        tile: Tuple[int, int],
+        tile: _Tile,
     ) -> None: ...
     def trib(
         self,
@@ -567,9 +771,33 @@ class Tilemap:
         y2: float,
         x3: float,
         y3: float,
-        tile: Tuple[int, int],
+        tile: _Tile,
+    ) -> None: ...
+    def poly(
+        self,
+        pts: List[Tuple[float, float]],
+        tile: _Tile,
+        rule: Optional[int] = None,
+    ) -> None: ...
+    def polyb(self, pts: List[Tuple[float, float]], tile: _Tile) -> None: ...
+    def thick_line(
+        self,
+        x1: float,
+        y1: float,
+        x2: float,
+        y2: float,
+        w: float,
+        tile: _Tile,
+        cap: Optional[int] = None,
+    ) -> None: ...
+    def arc(
+        self, x: float, y: float, r: float, a1: float, a2: float, tile: _Tile
     ) -> None: ...
-    def fill(self, x: float, y: float, tile: Tuple[int, int]) -> None: ...
+    def pie(
+        self, x: float, y: float, r: float, a1: float, a2: float, tile: _Tile
+    ) -> None: ...
+    def bezier(self, pts: List[Tuple[float, float]], tile: _Tile) -> None: ...
+    def fill(self, x: float, y: float, tile: _Tile) -> None: ...
     def collide(
         self,
         x: float,
@@ -578,8 +806,58 @@ class Tilemap:
         h: float,
         dx: float,
         dy: float,
-        walls: List[Tuple[int, int]],
+        walls: Optional[List[_Tile]] = None,
     ) -> Tuple[float, float]: ...
+    def sweep(
+        self,
+        x: float,
+        y: float,
+        w: float,
+        h: float,
+        dx: float,
+        dy: float,
+        walls: Optional[List[_Tile]] = None,
+    ) -> Dict[str, Any]: ...
+    def paint_terrain(self, x: int, y: int, terrain: Terrain) -> None: ...
+    def resolve_terrain(
+        self,
+        terrain: Terrain,
+        x: Optional[int] = None,
+        y: Optional[int] = None,
+        w: Optional[int] = None,
+        h: Optional[int] = None,
+    ) -> None: ...
+    def terrain_mask(self, x: int, y: int, terrain: Terrain) -> int: ...
+    def find_path(
+        self,
+        x1: int,
+        y1: int,
+        x2: int,
+        y2: int,
+        walls: Optional[List[_Tile]] = None,
+        diagonal: Optional[int] = None,
+        costs: Optional[Dict[Tuple[int, int], float]] = None,
+    ) -> Optional[List[Tuple[int, int]]]: ...
+    def reachable(
+        self,
+        x: int,
+        y: int,
+        walls: Optional[List[_Tile]] = None,
+        diagonal: Optional[int] = None,
+        costs: Optional[Dict[Tuple[int, int], float]] = None,
+        max_cost: Optional[float] = None,
+    ) -> List[Tuple[int, int]]: ...
+    def line_of_sight(
+        self,
+        x1: int,
+        y1: int,
+        x2: int,
+        y2: int,
+        walls: Optional[List[_Tile]] = None,
+    ) -> bool: ...
+    def field_of_view(
+        self, x: int, y: int, radius: int, walls: Optional[List[_Tile]] = None
+    ) -> List[Tuple[int, int]]: ...
     def blt(
         self,
         x: float,
@@ -589,12 +867,103 @@ class Tilemap:
         v: float,
         w: float,
         h: float,
-        tilekey: Optional[Tuple[int, int]] = None,
+        tilekey: Optional[_Tile] = None,
         *,
         rotate: Optional[float] = None,
         scale: Optional[float] = None,
     ) -> None: ...
 
+class ChunkedTilemap:
+    chunk_size: int
+    imgsrc: Union[int, Image]
+    tileset: Optional[Tileset]
+    tile_width: int
+    tile_height: int
+    chunk_dir: Optional[str]
+
+    def __init__(
+        self,
+        chunk_size: int,
+        img: Union[int, Image],
+        tile_width: Optional[int] = None,
+        tile_height: Optional[int] = None,
+    ) -> None: ...
+    def set_loader(
+        self, loader: Optional[Callable[[int, int], Optional[Tilemap]]]
+    ) -> None: ...
+    def set_saver(
+        self, saver: Optional[Callable[[int, int, Tilemap], None]]
+    ) -> None: ...
+    def chunk(self, chunk_x: int, chunk_y: int) -> Tilemap: ...
+    def loaded_chunks(self) -> List[Tuple[int, int]]: ...
+    def load_area(self, x: int, y: int, w: int, h: int) -> None: ...
+    def unload_chunk(self, chunk_x: int, chunk_y: int) -> None: ...
+    def unload_outside(self, x: int, y: int, w: int, h: int) -> None: ...
+    def invalidate_chunks(
+        self, chunk_x: Optional[int] = None, chunk_y: Optional[int] = None
+    ) -> None: ...
+    def save_chunks(self) -> None: ...
+    def pget(self, x: int, y: int) -> Tuple[int, int, int]: ...
+    def pset(self, x: int, y: int, tile: _Tile) -> None: ...
+    def rect(self, x: int, y: int, w: int, h: int, tile: _Tile) -> None: ...
+    def collide(
+        self,
+        x: float,
+        y: float,
+        w: float,
+        h: float,
+        dx: float,
+        dy: float,
+        walls: Optional[List[_Tile]] = None,
+    ) -> Tuple[float, float]: ...
+    def sweep(
+        self,
+        x: float,
+        y: float,
+        w: float,
+        h: float,
+        dx: float,
+        dy: float,
+        walls: Optional[List[_Tile]] = None,
+    ) -> Dict[str, Any]: ...
+
+# Layer class
+class Layer:
+    image: Image
+    x: int
+    y: int
+    visible: bool
+    front: bool
+    wrap_around: bool
+    transparent: Optional[int]
+    colors: Optional[List[int]]
+
+    def __init__(self, img: Image) -> None: ...
+    def scroll(self, x: int, y: int) -> None: ...
+
+# Animation class
+class Animation:
+    clip: Optional[str]
+    clips: List[str]
+    events: List[str]
+    finished: bool
+
+    def __init__(self) -> None: ...
+    def add_frame(
+        self,
+        clip: str,
+        img: int,
+        u: float,
+        v: float,
+        w: float,
+        h: float,
+        duration: int,
+        events: Optional[List[str]] = None,
+    ) -> None: ...
+    def set_mode(self, clip: str, mode: int) -> None: ...
+    def play(self, clip: str, restart: Optional[bool] = None) -> None: ...
+    def draw(self, x: float, y: float, colkey: Optional[int] = None) -> None: ...
+
 # Channel class
 class Channel:
     gain: float
@@ -685,7 +1054,15 @@ def title(title: str) -> None: ...
 def icon(data: List[str], scale: int, colkey: Optional[int] = None) -> None: ...
 def perf_monitor(enabled: bool) -> None: ...
 def integer_scale(enabled: bool) -> None: ...
+def deterministic(enabled: bool) -> None: ...
+def start_recording(seed: Optional[int] = None) -> None: ...
+def stop_recording() -> None: ...
+def save_replay(filename: str) -> None: ...
+def play_replay(filename: str) -> None: ...
+def is_replaying() -> bool: ...
 def screen_mode(scr: int) -> None: ...
+def add_screen_shader(code: str) -> int: ...
+def screen_effects(scrs: List[int]) -> None: ...
 def fullscreen(enabled: bool) -> None: ...
 
 # Resource
@@ -707,7 +1084,12 @@ def save(
 ) -> None: ...
 def load_pal(filename: str) -> None: ...
 def save_pal(filename: str) -> None: ...
+def load_tmx(filename: str) -> Dict[str, Any]: ...
 def screenshot(scale: Optional[int] = None) -> None: ...
+def screen_rgba(scale: Optional[int] = None) -> bytes: ...
+def compare_screen(
+    filename: str, scale: Optional[int] = None, tolerance: Optional[int] = None
+) -> Tuple[bool, str]: ...
 def screencast(scale: Optional[int] = None) -> None: ...
 def reset_screencast() -> None: ...
 def user_data_dir(vendor_name: str, app_name: str) -> str: ...
@@ -734,6 +1116,8 @@ colors: Seq[int]
 images: Seq[Image]
 tilemaps: Seq[Tilemap]
 screen: Image
+layers: Seq[Layer]
+animations: Seq[Animation]
 cursor: Image
 font: Image
 
@@ -743,12 +1127,18 @@ def clip(
     w: Optional[float] = None,
     h: Optional[float] = None,
 ) -> None: ...
+def mask(
+    img: Optional[Union[int, Image]] = None,
+    col: Optional[int] = None,
+    invert: Optional[bool] = None,
+) -> None: ...
 def camera(
     x: Optional[float] = None,
     y: Optional[float] = None,
 ) -> None: ...
 def pal(col1: Optional[int] = None, col2: Optional[int] = None) -> None: ...
 def dither(alpha: float) -> None: ...
+def blend(mode: int) -> None: ...
 def cls(col: int) -> None: ...
 def pget(x: float, y: float) -> int: ...
 def pset(x: float, y: float, col: int) -> None: ...
@@ -777,6 +1167,22 @@ def trib(
     y3: float,
     col: int,
 ) -> None: ...
+def poly(
+    pts: List[Tuple[float, float]], col: int, rule: Optional[int] = None
+) -> None: ...
+def polyb(pts: List[Tuple[float, float]], col: int) -> None: ...
+def thick_line(
+    x1: float,
+    y1: float,
+    x2: float,
+    y2: float,
+    w: float,
+    col: int,
+    cap: Optional[int] = None,
+) -> None: ...
+def arc(x: float, y: float, r: float, a1: float, a2: float, col: int) -> None: ...
+def pie(x: float, y: float, r: float, a1: float, a2: float, col: int) -> None: ...
+def bezier(pts: List[Tuple[float, float]], col: int) -> None: ...
 def fill(x: float, y: float, col: int) -> None: ...
 def blt(
     x: float,
@@ -791,10 +1197,29 @@ def blt(
     rotate: Optional[float] = None,
     scale: Optional[float] = None,
 ) -> None: ...
+def spr(
+    x: float,
+    y: float,
+    img: Union[int, Image],
+    u: float,
+    v: float,
+    w: float,
+    h: float,
+    colkey: Optional[int] = None,
+    *,
+    z: Optional[int] = None,
+    flip_x: Optional[bool] = None,
+    flip_y: Optional[bool] = None,
+    rotate: Optional[float] = None,
+    scale: Optional[float] = None,
+    pal: Optional[List[int]] = None,
+) -> None: ...
+def sprite_limit(limit: Optional[int] = None) -> None: ...
+def flush_sprites() -> None: ...
 def bltm(
     x: float,
     y: float,
-    tm: Union[int, Tilemap],
+    tm: Union[int, Tilemap, ChunkedTilemap],
     u: float,
     v: float,
     w: float,
@@ -804,7 +1229,90 @@ def bltm(
     rotate: Optional[float] = None,
     scale: Optional[float] = None,
 ) -> None: ...
+def blt_tri(
+    x1: float,
+    y1: float,
+    x2: float,
+    y2: float,
+    x3: float,
+    y3: float,
+    img: Union[int, Image],
+    u1: float,
+    v1: float,
+    u2: float,
+    v2: float,
+    u3: float,
+    v3: float,
+    colkey: Optional[int] = None,
+) -> None: ...
+def blt_perspective(
+    x: float,
+    y: float,
+    w: float,
+    h: float,
+    img: Union[int, Image],
+    lines: List[Optional[Tuple[float, float, float, float]]],
+    colkey: Optional[int] = None,
+    wrap: Optional[bool] = None,
+) -> None: ...
+def bltm_perspective(
+    x: float,
+    y: float,
+    w: float,
+    h: float,
+    tm: Union[int, Tilemap],
+    lines: List[Optional[Tuple[float, float, float, float]]],
+    colkey: Optional[int] = None,
+    wrap: Optional[bool] = None,
+) -> None: ...
+def mode7_lines(
+    w: int,
+    h: int,
+    x: float,
+    y: float,
+    height: float,
+    pitch: float,
+    yaw: float,
+    fov: Optional[float] = None,
+) -> List[Optional[Tuple[float, float, float, float]]]: ...
 def text(x: float, y: float, s: str, col: int, font: Optional[Font] = None) -> None: ...
+def default_font(font: Optional[Font] = None) -> None: ...
+def text_box(
+    x: float,
+    y: float,
+    s: str,
+    col: int,
+    *,
+    font: Optional[Font] = None,
+    fonts: Optional[List[Font]] = None,
+    w: Optional[int] = None,
+    align: Optional[int] = None,
+    line_spacing: Optional[int] = None,
+    shadow: Optional[Tuple[int, int, int]] = None,
+    outline: Optional[int] = None,
+) -> Tuple[int, int, int, int]: ...
+def measure_text(
+    s: str,
+    *,
+    font: Optional[Font] = None,
+    fonts: Optional[List[Font]] = None,
+    w: Optional[int] = None,
+    align: Optional[int] = None,
+    line_spacing: Optional[int] = None,
+    shadow: Optional[Tuple[int, int, int]] = None,
+    outline: Optional[int] = None,
+) -> Tuple[int, int, int, int]: ...
+def screen_transparent(col: Optional[int] = None) -> None: ...
+def raster(
+    y: Optional[int] = None,
+    colors: Optional[List[int]] = None,
+    offset: Optional[int] = None,
+) -> None: ...
+def pal_cycle(
+    start: Optional[int] = None,
+    end: Optional[int] = None,
+    speed: Optional[float] = None,
+) -> None: ...
 
 # Audio
 channels: Seq[Channel]
@@ -61,6 +61,7 @@ class CanvasPanel(Widget):
         self._bank_buffer = None
         self._is_dragged = False
         self._is_assist_mode = False
+        self._is_terrain_mode = False
         self._edit_canvas = (
             pyxel.Tilemap(16, 16, 0) if self._is_tilemap_mode else pyxel.Image(16, 16)
         )
@@ -173,7 +174,7 @@ class CanvasPanel(Widget):
 
         for y in range(16):
             for x in range(16):
-                if self._edit_canvas.pget(x, y) != (255, 255):
+                if self._edit_canvas.pget(x, y)[:2] != (255, 255):
                     continue
                 tile = (
                     self.tile_x_var + (x - self._press_x) % self.tile_w_var,
@@ -181,6 +182,31 @@ class CanvasPanel(Widget):
                 )
                 self._edit_canvas.pset(x, y, tile)
 
+    def _can_paint_terrain(self):
+        return (
+            self._is_tilemap_mode
+            and self.tool_var == TOOL_PENCIL
+            and self.tile_w_var == 4
+            and self.tile_h_var == 4
+        )
+
+    def _paint_terrain(self, x, y):
+        # The selected 4x4 tiles are the edge terrain, indexed by the N/E/S/W bits of the mask
+        terrain = pyxel.Terrain(pyxel.TERRAIN_EDGES)
+        for i in range(16):
+            mask = 0
+            for bit, edge in enumerate(
+                [pyxel.TERRAIN_N, pyxel.TERRAIN_E, pyxel.TERRAIN_S, pyxel.TERRAIN_W]
+            ):
+                if i & (1 << bit):
+                    mask |= edge
+            terrain.set_rule(mask, (self.tile_x_var + i % 4, self.tile_y_var + i // 4))
+
+        x = self.focus_x_var * 8 + min(max(x, 0), 15)
+        y = self.focus_y_var * 8 + min(max(y, 0), 15)
+        self.canvas_var.paint_terrain(x, y, terrain)
+        self._reset_edit_canvas()
+
     def __on_h_scroll_bar_change(self, value):
         self.focus_x_var = value
 
@@ -198,7 +224,7 @@ class CanvasPanel(Widget):
             x = self.focus_x_var * 8 + (x - self.x) // 8
             y = self.focus_y_var * 8 + (y - self.y) // 8
             if self._is_tilemap_mode:
-                (self.tile_x_var, self.tile_y_var) = self.canvas_var.pget(x, y)
+                (self.tile_x_var, self.tile_y_var) = self.canvas_var.pget(x, y)[:2]
             else:
                 self.color_var = self.canvas_var.pget(x, y)
             return
@@ -210,8 +236,12 @@ class CanvasPanel(Widget):
         self._press_y = self._last_y = y
         self._is_dragged = True
         self._is_assist_mode = False
+        self._is_terrain_mode = self._can_paint_terrain() and pyxel.btn(pyxel.KEY_ALT)
 
-        if self.tool_var == TOOL_SELECT:
+        if self._is_terrain_mode:
+            self._add_pre_history(bank_copy=True)
+            self._paint_terrain(x, y)
+        elif self.tool_var == TOOL_SELECT:
             self._reset_edit_canvas()
             self._select_x1 = self._select_x2 = x
             self._select_y1 = self._select_y2 = y
@@ -240,7 +270,10 @@ class CanvasPanel(Widget):
             return
 
         self._is_dragged = False
-        if TOOL_PENCIL <= self.tool_var <= TOOL_CIRC:
+        if self._is_terrain_mode:
+            self._is_terrain_mode = False
+            self._add_post_history(bank_copy=True)
+        elif TOOL_PENCIL <= self.tool_var <= TOOL_CIRC:
             self._add_pre_history()
             self.canvas_var.blt(
                 self.focus_x_var * 8,
@@ -274,6 +307,9 @@ class CanvasPanel(Widget):
                 self._select_x1, self._select_x2 = (x1, x2) if x1 < x2 else (x2, x1)
                 self._select_y1, self._select_y2 = (y1, y2) if y1 < y2 else (y2, y1)
 
+            elif self._is_terrain_mode:
+                self._paint_terrain(x2, y2)
+
             elif self.tool_var == TOOL_PENCIL:
                 if self._is_assist_mode:
                     self._reset_edit_canvas()
@@ -338,6 +374,8 @@ class CanvasPanel(Widget):
             s = "COPY:CTRL+C/X/V FLIP:H/V"
         elif self._is_dragged:
             s = "ASSIST:SHIFT"
+        elif self._can_paint_terrain():
+            s = "TERRAIN:ALT PICK:R-CLICK"
         else:
             s = "PICK:R-CLICK VIEW:R-DRAG"
 
@@ -23,7 +23,7 @@ enemies = []
 
 
 def get_tile(tile_x, tile_y):
-    return pyxel.tilemaps[0].pget(tile_x, tile_y)
+    return pyxel.tilemaps[0].pget(tile_x, tile_y)[:2]
 
 
 def is_wall(x, y):
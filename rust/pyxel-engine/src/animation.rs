@@ -0,0 +1,278 @@
+use indexmap::IndexMap;
+
+use crate::image::Color;
+use crate::pyxel::Pyxel;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnimationMode {
+    Loop,
+    PingPong,
+    OneShot,
+}
+
+impl From<u32> for AnimationMode {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::PingPong,
+            2 => Self::OneShot,
+            _ => Self::Loop,
+        }
+    }
+}
+
+impl From<AnimationMode> for u32 {
+    fn from(mode: AnimationMode) -> Self {
+        match mode {
+            AnimationMode::Loop => 0,
+            AnimationMode::PingPong => 1,
+            AnimationMode::OneShot => 2,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub image: u32,
+    pub image_x: f32,
+    pub image_y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub duration: u32,
+    pub events: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct AnimationClip {
+    pub frames: Vec<AnimationFrame>,
+    pub mode: AnimationMode,
+}
+
+impl AnimationClip {
+    // Frame indices in playback order, where ping-pong does not repeat the end frames
+    fn sequence(&self) -> Vec<usize> {
+        let num_frames = self.frames.len();
+        let mut sequence: Vec<usize> = (0..num_frames).collect();
+        if self.mode == AnimationMode::PingPong && num_frames > 2 {
+            sequence.extend((1..num_frames - 1).rev());
+        }
+        sequence
+    }
+
+    // Returns the sequence slot at the tick and whether the slot starts at that tick
+    fn slot_at(&self, tick: u32) -> Option<(usize, bool)> {
+        let sequence = self.sequence();
+        let durations: Vec<u32> = sequence
+            .iter()
+            .map(|&index| self.frames[index].duration.max(1))
+            .collect();
+        let total: u32 = durations.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut tick = match self.mode {
+            AnimationMode::OneShot if tick >= total => return Some((sequence.len() - 1, false)),
+            AnimationMode::OneShot => tick,
+            _ => tick % total,
+        };
+        for (slot, &duration) in durations.iter().enumerate() {
+            if tick < duration {
+                return Some((slot, tick == 0));
+            }
+            tick -= duration;
+        }
+        None
+    }
+
+    fn total_duration(&self) -> u32 {
+        self.sequence()
+            .iter()
+            .map(|&index| self.frames[index].duration.max(1))
+            .sum()
+    }
+}
+
+#[derive(Clone)]
+pub struct Animation {
+    pub clips: IndexMap<String, AnimationClip>,
+    pub clip: Option<String>,
+    pub events: Vec<String>,
+    start_frame_count: Option<u32>,
+    last_tick: Option<u32>,
+}
+
+pub type SharedAnimation = shared_type!(Animation);
+
+impl Animation {
+    pub fn new() -> SharedAnimation {
+        new_shared_type!(Self {
+            clips: IndexMap::new(),
+            clip: None,
+            events: Vec::new(),
+            start_frame_count: None,
+            last_tick: None,
+        })
+    }
+
+    pub fn add_frame(&mut self, clip: &str, frame: AnimationFrame) {
+        self.clips
+            .entry(clip.to_string())
+            .or_insert_with(|| AnimationClip {
+                frames: Vec::new(),
+                mode: AnimationMode::Loop,
+            })
+            .frames
+            .push(frame);
+        if self.clip.is_none() {
+            self.clip = Some(clip.to_string());
+        }
+    }
+
+    pub fn set_mode(&mut self, clip: &str, mode: AnimationMode) -> Result<(), String> {
+        let clip = self
+            .clips
+            .get_mut(clip)
+            .ok_or_else(|| format!("Animation clip '{clip}' not found"))?;
+        clip.mode = mode;
+        Ok(())
+    }
+
+    pub fn play(&mut self, clip: &str, restart: bool) -> Result<(), String> {
+        if !self.clips.contains_key(clip) {
+            return Err(format!("Animation clip '{clip}' not found"));
+        }
+        if restart || self.clip.as_deref() != Some(clip) {
+            self.clip = Some(clip.to_string());
+            self.start_frame_count = None;
+            self.last_tick = None;
+        }
+        Ok(())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let Some(clip) = self.current_clip() else {
+            return true;
+        };
+        clip.mode == AnimationMode::OneShot
+            && self
+                .last_tick
+                .is_some_and(|tick| tick >= clip.total_duration())
+    }
+
+    // Advances to the engine frame count and collects the events of the frames entered
+    pub fn update(&mut self, frame_count: u32) -> Option<&AnimationFrame> {
+        self.events.clear();
+        let start_frame_count = *self.start_frame_count.get_or_insert(frame_count);
+        let tick = frame_count.wrapping_sub(start_frame_count);
+        let clip = self.clip.as_ref().and_then(|clip| self.clips.get(clip))?;
+
+        // A one-shot clip stops at its end, while a looping clip only replays its last cycle
+        let total_duration = clip.total_duration();
+        let last_event_tick = if clip.mode == AnimationMode::OneShot {
+            tick.min(total_duration)
+        } else {
+            tick
+        };
+        let first_tick = match self.last_tick {
+            Some(last_tick) if last_tick >= tick => tick + 1,
+            Some(last_tick) => (last_tick + 1).max(last_event_tick.saturating_sub(total_duration)),
+            None => last_event_tick.saturating_sub(total_duration),
+        };
+        let sequence = clip.sequence();
+        for event_tick in first_tick..=last_event_tick {
+            if let Some((slot, true)) = clip.slot_at(event_tick) {
+                self.events
+                    .extend(clip.frames[sequence[slot]].events.iter().cloned());
+            }
+        }
+        self.last_tick = Some(tick);
+
+        let (slot, _) = clip.slot_at(tick)?;
+        clip.frames.get(sequence[slot])
+    }
+
+    fn current_clip(&self) -> Option<&AnimationClip> {
+        self.clip.as_ref().and_then(|clip| self.clips.get(clip))
+    }
+}
+
+impl Pyxel {
+    pub fn draw_animation(
+        &self,
+        x: f32,
+        y: f32,
+        animation: &mut Animation,
+        transparent: Option<Color>,
+    ) {
+        let Some(frame) = animation.update(self.frame_count).cloned() else {
+            return;
+        };
+        let Some(image) = self.images.lock().get(frame.image as usize).cloned() else {
+            return;
+        };
+
+        self.screen.lock().blt(
+            x,
+            y,
+            image,
+            frame.image_x,
+            frame.image_y,
+            frame.width,
+            frame.height,
+            transparent,
+            None,
+            None,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(duration: u32, event: Option<&str>) -> AnimationFrame {
+        AnimationFrame {
+            image: 0,
+            image_x: 0.0,
+            image_y: 0.0,
+            width: 8.0,
+            height: 8.0,
+            duration,
+            events: event.into_iter().map(str::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_animation_modes() {
+        let animation = Animation::new();
+        let mut animation = animation.lock();
+        for (duration, event) in [(2, Some("step")), (1, None), (1, Some("end"))] {
+            let mut frame = frame(duration, event);
+            frame.image_x = duration as f32;
+            animation.add_frame("walk", frame);
+        }
+
+        let image_x: Vec<f32> = (10..18)
+            .map(|frame_count| animation.update(frame_count).unwrap().image_x)
+            .collect();
+        assert_eq!(image_x, vec![2.0, 2.0, 1.0, 1.0, 2.0, 2.0, 1.0, 1.0]);
+
+        animation.set_mode("walk", AnimationMode::PingPong).unwrap();
+        animation.play("walk", true).unwrap();
+        let mut events = Vec::new();
+        for frame_count in 0..6 {
+            animation.update(frame_count);
+            events.extend(animation.events.clone());
+        }
+        assert_eq!(events, vec!["step", "end", "step"]);
+
+        animation.set_mode("walk", AnimationMode::OneShot).unwrap();
+        animation.play("walk", true).unwrap();
+        animation.update(0);
+        assert!(!animation.is_finished());
+        animation.update(100);
+        assert!(animation.is_finished());
+        assert_eq!(animation.events, vec!["end"]);
+        assert!(animation.play("run", false).is_err());
+    }
+}
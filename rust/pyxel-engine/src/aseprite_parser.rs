@@ -0,0 +1,780 @@
+use std::fs;
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use crate::image::{Color, Image, Rgb24, SharedImage};
+use crate::pyxel::COLORS;
+use crate::quantize::Quantizer;
+use crate::settings::{IMPORT_ALPHA_THRESHOLD, MAX_COLORS};
+
+const HEADER_MAGIC: u16 = 0xa5e0;
+const FRAME_MAGIC: u16 = 0xf1fa;
+const HEADER_SIZE: usize = 128;
+const MAX_PALETTE_SIZE: usize = 256;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+const CHUNK_SLICE: u16 = 0x2022;
+
+const COLOR_DEPTH_RGBA: u16 = 32;
+const COLOR_DEPTH_GRAYSCALE: u16 = 16;
+const COLOR_DEPTH_INDEXED: u16 = 8;
+
+const LAYER_FLAG_VISIBLE: u16 = 1;
+const LAYER_TYPE_IMAGE: u16 = 0;
+const LAYER_TYPE_GROUP: u16 = 1;
+const LAYER_TYPE_TILEMAP: u16 = 2;
+
+const CEL_TYPE_RAW: u16 = 0;
+const CEL_TYPE_LINKED: u16 = 1;
+const CEL_TYPE_COMPRESSED: u16 = 2;
+
+const SLICE_FLAG_NINE_PATCH: u32 = 1;
+const SLICE_FLAG_PIVOT: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TagDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+impl From<u32> for TagDirection {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::Reverse,
+            2 => Self::PingPong,
+            3 => Self::PingPongReverse,
+            _ => Self::Forward,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteFrame {
+    pub x: i32,
+    pub y: i32,
+    pub duration: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+    pub direction: TagDirection,
+    pub repeat: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteLayer {
+    pub name: String,
+    pub visible: bool,
+    pub is_group: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SliceKey {
+    pub frame: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub center: Option<(i32, i32, u32, u32)>,
+    pub pivot: Option<(i32, i32)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsepriteSlice {
+    pub name: String,
+    pub keys: Vec<SliceKey>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Aseprite {
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<AsepriteFrame>,
+    pub tags: Vec<AsepriteTag>,
+    pub layers: Vec<AsepriteLayer>,
+    pub slices: Vec<AsepriteSlice>,
+    pub colors: Vec<Rgb24>,
+    pub transparent: Color,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Pixel {
+    Index(Color),
+    Rgb(Rgb24),
+}
+
+type FramePixels = Vec<Option<Pixel>>;
+
+#[derive(Clone)]
+struct Cel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    z_index: i32,
+    width: u32,
+    height: u32,
+    pixels: Vec<Option<Pixel>>,
+}
+
+struct LayerInfo {
+    layer: AsepriteLayer,
+    layer_type: u16,
+    child_level: u16,
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| "Unexpected end of Aseprite file".to_string())?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), String> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+}
+
+pub fn parse_aseprite(
+    filename: &str,
+    layer_index: Option<u32>,
+    include_colors: bool,
+) -> Result<(SharedImage, Aseprite), String> {
+    let data = fs::read(filename).map_err(|_| format!("Failed to open file '{filename}'"))?;
+    let (aseprite, frame_pixels) = parse_aseprite_data(&data, layer_index)?;
+
+    let mut colors = COLORS.lock();
+    if include_colors {
+        *colors = aseprite
+            .colors
+            .iter()
+            .take(MAX_COLORS as usize)
+            .copied()
+            .collect();
+    }
+    let mut quantizer = Quantizer::new(&colors, None, false);
+
+    let image = Image::new(
+        aseprite.width * aseprite.frames.len() as u32,
+        aseprite.height,
+    );
+    {
+        let mut image = image.lock();
+        let frame_width = aseprite.width as usize;
+        let frame_height = aseprite.height as usize;
+
+        for (frame_index, pixels) in frame_pixels.iter().enumerate() {
+            for (i, pixel) in pixels.iter().enumerate() {
+                let color = match pixel {
+                    Some(Pixel::Index(color)) => *color,
+                    Some(Pixel::Rgb(rgb)) => quantizer.nearest_color(*rgb),
+                    None => aseprite.transparent,
+                };
+                let x = frame_index * frame_width + i % frame_width;
+                let y = i / frame_width;
+                if y < frame_height {
+                    image.canvas.write_data(x, y, color);
+                }
+            }
+        }
+    }
+
+    Ok((image, aseprite))
+}
+
+fn parse_aseprite_data(
+    data: &[u8],
+    layer_index: Option<u32>,
+) -> Result<(Aseprite, Vec<FramePixels>), String> {
+    let mut reader = ByteReader::new(data);
+    reader.skip(4)?;
+    if reader.u16()? != HEADER_MAGIC {
+        return Err("Invalid Aseprite file".to_string());
+    }
+    let num_frames = reader.u16()? as usize;
+    let width = reader.u16()? as u32;
+    let height = reader.u16()? as u32;
+    let color_depth = reader.u16()?;
+    if ![COLOR_DEPTH_RGBA, COLOR_DEPTH_GRAYSCALE, COLOR_DEPTH_INDEXED].contains(&color_depth) {
+        return Err(format!("Unsupported Aseprite color depth {color_depth}"));
+    }
+    reader.skip(14)?;
+    let transparent = if color_depth == COLOR_DEPTH_INDEXED {
+        reader.u8()?
+    } else {
+        reader.skip(1)?;
+        0
+    };
+    reader.pos = HEADER_SIZE;
+
+    let mut layers: Vec<LayerInfo> = Vec::new();
+    let mut colors: Vec<Rgb24> = Vec::new();
+    let mut tags = Vec::new();
+    let mut slices = Vec::new();
+    let mut frames = Vec::with_capacity(num_frames);
+    let mut frame_cels: Vec<Vec<Cel>> = Vec::with_capacity(num_frames);
+
+    for frame_index in 0..num_frames {
+        let frame_start = reader.pos;
+        let frame_size = reader.u32()? as usize;
+        if reader.u16()? != FRAME_MAGIC {
+            return Err("Invalid Aseprite frame".to_string());
+        }
+        let old_num_chunks = reader.u16()? as u32;
+        let duration = reader.u16()? as u32;
+        reader.skip(2)?;
+        let num_chunks = match reader.u32()? {
+            0 => old_num_chunks,
+            num_chunks => num_chunks,
+        };
+
+        let mut cels = Vec::new();
+        for _ in 0..num_chunks {
+            let chunk_start = reader.pos;
+            let chunk_size = reader.u32()? as usize;
+            let chunk_type = reader.u16()?;
+            let mut chunk = ByteReader::new(reader.bytes(chunk_size.saturating_sub(6))?);
+
+            match chunk_type {
+                CHUNK_LAYER => {
+                    let flags = chunk.u16()?;
+                    let layer_type = chunk.u16()?;
+                    let child_level = chunk.u16()?;
+                    chunk.skip(10)?;
+                    layers.push(LayerInfo {
+                        layer: AsepriteLayer {
+                            name: chunk.string()?,
+                            visible: flags & LAYER_FLAG_VISIBLE != 0,
+                            is_group: layer_type == LAYER_TYPE_GROUP,
+                        },
+                        layer_type,
+                        child_level,
+                    });
+                }
+                CHUNK_CEL => {
+                    let layer = chunk.u16()? as usize;
+                    let x = chunk.i16()? as i32;
+                    let y = chunk.i16()? as i32;
+                    chunk.skip(1)?;
+                    let cel_type = chunk.u16()?;
+                    let z_index = chunk.i16()? as i32;
+                    chunk.skip(5)?;
+
+                    match cel_type {
+                        CEL_TYPE_RAW | CEL_TYPE_COMPRESSED => {
+                            let width = chunk.u16()? as u32;
+                            let height = chunk.u16()? as u32;
+                            let rest = chunk.bytes(chunk.data.len() - chunk.pos)?;
+                            let pixel_data = if cel_type == CEL_TYPE_COMPRESSED {
+                                let mut decompressed = Vec::new();
+                                ZlibDecoder::new(rest)
+                                    .read_to_end(&mut decompressed)
+                                    .map_err(|_| "Failed to decompress Aseprite cel".to_string())?;
+                                decompressed
+                            } else {
+                                rest.to_vec()
+                            };
+                            let pixels = decode_pixels(
+                                &pixel_data,
+                                (width * height) as usize,
+                                color_depth,
+                                transparent,
+                            )?;
+                            cels.push(Cel {
+                                layer,
+                                x,
+                                y,
+                                z_index,
+                                width,
+                                height,
+                                pixels,
+                            });
+                        }
+                        CEL_TYPE_LINKED => {
+                            let linked_frame = chunk.u16()? as usize;
+                            let linked_cel = frame_cels
+                                .get(linked_frame)
+                                .and_then(|cels| cels.iter().find(|cel| cel.layer == layer))
+                                .cloned();
+                            if let Some(mut cel) = linked_cel {
+                                cel.z_index = z_index;
+                                cels.push(cel);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                CHUNK_PALETTE => {
+                    let size = chunk.u32()? as usize;
+                    let first = chunk.u32()? as usize;
+                    let last = chunk.u32()? as usize;
+                    chunk.skip(8)?;
+                    if size > MAX_PALETTE_SIZE || first > last || last >= size {
+                        return Err("Invalid Aseprite palette".to_string());
+                    }
+                    colors.resize(size.max(colors.len()), 0);
+                    for color in &mut colors[first..=last] {
+                        let flags = chunk.u16()?;
+                        let rgb = chunk.bytes(3)?;
+                        chunk.skip(1)?;
+                        if flags & 1 != 0 {
+                            chunk.string()?;
+                        }
+                        *color = rgb_from_bytes(rgb);
+                    }
+                }
+                CHUNK_OLD_PALETTE if colors.is_empty() => {
+                    let num_packets = chunk.u16()?;
+                    let mut index = 0;
+                    for _ in 0..num_packets {
+                        index += chunk.u8()? as usize;
+                        let num_colors = match chunk.u8()? {
+                            0 => 256,
+                            num_colors => num_colors as usize,
+                        };
+                        if index + num_colors > MAX_PALETTE_SIZE {
+                            return Err("Invalid Aseprite palette".to_string());
+                        }
+                        colors.resize((index + num_colors).max(colors.len()), 0);
+                        for _ in 0..num_colors {
+                            colors[index] = rgb_from_bytes(chunk.bytes(3)?);
+                            index += 1;
+                        }
+                    }
+                }
+                CHUNK_TAGS => {
+                    let num_tags = chunk.u16()?;
+                    chunk.skip(8)?;
+                    for _ in 0..num_tags {
+                        let from = chunk.u16()? as u32;
+                        let to = chunk.u16()? as u32;
+                        let direction = TagDirection::from(chunk.u8()? as u32);
+                        let repeat = chunk.u16()? as u32;
+                        chunk.skip(10)?;
+                        tags.push(AsepriteTag {
+                            name: chunk.string()?,
+                            from,
+                            to,
+                            direction,
+                            repeat,
+                        });
+                    }
+                }
+                CHUNK_SLICE => {
+                    let num_keys = chunk.u32()?;
+                    let flags = chunk.u32()?;
+                    chunk.skip(4)?;
+                    let name = chunk.string()?;
+                    let mut keys = Vec::new();
+                    for _ in 0..num_keys {
+                        let frame = chunk.u32()?;
+                        let x = chunk.i32()?;
+                        let y = chunk.i32()?;
+                        let width = chunk.u32()?;
+                        let height = chunk.u32()?;
+                        let center = if flags & SLICE_FLAG_NINE_PATCH != 0 {
+                            Some((chunk.i32()?, chunk.i32()?, chunk.u32()?, chunk.u32()?))
+                        } else {
+                            None
+                        };
+                        let pivot = if flags & SLICE_FLAG_PIVOT != 0 {
+                            Some((chunk.i32()?, chunk.i32()?))
+                        } else {
+                            None
+                        };
+                        keys.push(SliceKey {
+                            frame,
+                            x,
+                            y,
+                            width,
+                            height,
+                            center,
+                            pivot,
+                        });
+                    }
+                    slices.push(AsepriteSlice { name, keys });
+                }
+                _ => {}
+            }
+
+            reader.pos = chunk_start + chunk_size.max(6);
+        }
+
+        frames.push(AsepriteFrame {
+            x: (frame_index as u32 * width) as i32,
+            y: 0,
+            duration,
+        });
+        frame_cels.push(cels);
+        reader.pos = frame_start + frame_size;
+    }
+
+    let drawn_layers = drawn_layers(&layers, layer_index)?;
+    let frame_pixels = frame_cels
+        .into_iter()
+        .map(|mut cels| {
+            cels.retain(|cel| drawn_layers.contains(&cel.layer));
+            cels.sort_by_key(|cel| (cel.layer as i32 + cel.z_index, cel.z_index));
+            flatten_cels(&cels, width, height)
+        })
+        .collect();
+
+    let aseprite = Aseprite {
+        width,
+        height,
+        frames,
+        tags,
+        layers: layers.into_iter().map(|info| info.layer).collect(),
+        slices,
+        colors,
+        transparent,
+    };
+    Ok((aseprite, frame_pixels))
+}
+
+// Visible image layers, taking hidden parent groups into account
+fn drawn_layers(layers: &[LayerInfo], layer_index: Option<u32>) -> Result<Vec<usize>, String> {
+    if let Some(layer_index) = layer_index {
+        let layer_index = layer_index as usize;
+        return match layers.get(layer_index) {
+            Some(info) if info.layer_type == LAYER_TYPE_IMAGE => Ok(vec![layer_index]),
+            Some(info) if info.layer_type == LAYER_TYPE_TILEMAP => Err(format!(
+                "Layer {layer_index} is a tilemap layer, which is not supported"
+            )),
+            Some(_) => Err(format!("Layer {layer_index} is not an image layer")),
+            None => Err(format!("Layer {layer_index} not found in Aseprite file")),
+        };
+    }
+
+    let mut group_visibility: Vec<bool> = Vec::new();
+    let mut drawn_layers = Vec::new();
+    for (index, info) in layers.iter().enumerate() {
+        group_visibility.truncate(info.child_level as usize);
+        let visible = info.layer.visible && group_visibility.iter().all(|&visible| visible);
+        match info.layer_type {
+            LAYER_TYPE_GROUP => group_visibility.push(visible),
+            LAYER_TYPE_TILEMAP if visible => {
+                return Err(format!(
+                    "Tilemap layer '{}' is not supported in Aseprite files",
+                    info.layer.name
+                ));
+            }
+            LAYER_TYPE_TILEMAP => {}
+            _ if visible => drawn_layers.push(index),
+            _ => {}
+        }
+    }
+    Ok(drawn_layers)
+}
+
+fn flatten_cels(cels: &[Cel], width: u32, height: u32) -> FramePixels {
+    let mut pixels = vec![None; (width * height) as usize];
+
+    for cel in cels {
+        for cel_y in 0..cel.height as i32 {
+            for cel_x in 0..cel.width as i32 {
+                let x = cel.x + cel_x;
+                let y = cel.y + cel_y;
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                    continue;
+                }
+                if let Some(pixel) = cel.pixels[(cel_y * cel.width as i32 + cel_x) as usize] {
+                    pixels[(y * width as i32 + x) as usize] = Some(pixel);
+                }
+            }
+        }
+    }
+
+    pixels
+}
+
+fn decode_pixels(
+    data: &[u8],
+    num_pixels: usize,
+    color_depth: u16,
+    transparent: Color,
+) -> Result<FramePixels, String> {
+    let bytes_per_pixel = (color_depth / 8) as usize;
+    if data.len() < num_pixels * bytes_per_pixel {
+        return Err("Unexpected end of Aseprite cel".to_string());
+    }
+
+    let pixels = data
+        .chunks_exact(bytes_per_pixel)
+        .take(num_pixels)
+        .map(|bytes| match color_depth {
+            COLOR_DEPTH_INDEXED => (bytes[0] != transparent).then_some(Pixel::Index(bytes[0])),
+            COLOR_DEPTH_GRAYSCALE => (bytes[1] >= IMPORT_ALPHA_THRESHOLD)
+                .then(|| Pixel::Rgb(rgb_from_bytes(&[bytes[0], bytes[0], bytes[0]]))),
+            _ => (bytes[3] >= IMPORT_ALPHA_THRESHOLD).then(|| Pixel::Rgb(rgb_from_bytes(bytes))),
+        })
+        .collect();
+    Ok(pixels)
+}
+
+fn rgb_from_bytes(bytes: &[u8]) -> Rgb24 {
+    ((bytes[0] as Rgb24) << 16) | ((bytes[1] as Rgb24) << 8) | bytes[2] as Rgb24
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn chunk(chunk_type: u16, body: &[u8]) -> Vec<u8> {
+        let mut chunk = ((body.len() + 6) as u32).to_le_bytes().to_vec();
+        chunk.extend_from_slice(&chunk_type.to_le_bytes());
+        chunk.extend_from_slice(body);
+        chunk
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u16).to_le_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    fn layer(name: &str, flags: u16, layer_type: u16, child_level: u16) -> Vec<u8> {
+        let mut body = Vec::new();
+        for value in [flags, layer_type, child_level, 0, 0, 0] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        body.extend_from_slice(&[255, 0, 0, 0]);
+        body.extend(string(name));
+        chunk(CHUNK_LAYER, &body)
+    }
+
+    fn cel(layer: u16, x: i16, cel_type: u16, body: &[u8]) -> Vec<u8> {
+        let mut data = layer.to_le_bytes().to_vec();
+        data.extend_from_slice(&x.to_le_bytes());
+        data.extend_from_slice(&0i16.to_le_bytes());
+        data.push(255);
+        data.extend_from_slice(&cel_type.to_le_bytes());
+        data.extend_from_slice(&[0; 7]);
+        data.extend_from_slice(body);
+        chunk(CHUNK_CEL, &data)
+    }
+
+    fn frame(duration: u16, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut frame = ((body.len() + 16) as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(&FRAME_MAGIC.to_le_bytes());
+        frame.extend_from_slice(&(chunks.len() as u16).to_le_bytes());
+        frame.extend_from_slice(&duration.to_le_bytes());
+        frame.extend_from_slice(&[0; 2]);
+        frame.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        frame.extend(body);
+        frame
+    }
+
+    fn header(num_frames: u16) -> Vec<u8> {
+        let mut header = vec![0; HEADER_SIZE];
+        header[4..6].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+        header[6..8].copy_from_slice(&num_frames.to_le_bytes());
+        header[8..10].copy_from_slice(&2u16.to_le_bytes());
+        header[10..12].copy_from_slice(&2u16.to_le_bytes());
+        header[12..14].copy_from_slice(&COLOR_DEPTH_INDEXED.to_le_bytes());
+        header[28] = 0;
+        header
+    }
+
+    fn test_file() -> Vec<u8> {
+        let mut palette = Vec::new();
+        for value in [3u32, 0, 2, 0, 0] {
+            palette.extend_from_slice(&value.to_le_bytes());
+        }
+        for rgb in [[0, 0, 0], [255, 0, 0], [0, 0, 255]] {
+            palette.extend_from_slice(&[0, 0]);
+            palette.extend_from_slice(&rgb);
+            palette.push(255);
+        }
+
+        let mut tags = 1u16.to_le_bytes().to_vec();
+        tags.extend_from_slice(&[0; 8]);
+        tags.extend_from_slice(&[0, 0, 1, 0, 2, 3, 0]);
+        tags.extend_from_slice(&[0; 10]);
+        tags.extend(string("walk"));
+
+        let mut slice = Vec::new();
+        for value in [1u32, SLICE_FLAG_PIVOT, 0] {
+            slice.extend_from_slice(&value.to_le_bytes());
+        }
+        slice.extend(string("hitbox"));
+        for value in [0u32, 0, 1, 2, 1, 1, 0] {
+            slice.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut raw_cel = Vec::new();
+        raw_cel.extend_from_slice(&2u16.to_le_bytes());
+        raw_cel.extend_from_slice(&1u16.to_le_bytes());
+        raw_cel.extend_from_slice(&[1, 0]);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[2]).unwrap();
+        let mut compressed_cel = Vec::new();
+        compressed_cel.extend_from_slice(&1u16.to_le_bytes());
+        compressed_cel.extend_from_slice(&1u16.to_le_bytes());
+        compressed_cel.extend(encoder.finish().unwrap());
+
+        let frame1 = frame(
+            100,
+            &[
+                layer("body", LAYER_FLAG_VISIBLE, LAYER_TYPE_IMAGE, 0),
+                layer("hidden", 0, LAYER_TYPE_IMAGE, 0),
+                chunk(CHUNK_PALETTE, &palette),
+                chunk(CHUNK_TAGS, &tags),
+                chunk(CHUNK_SLICE, &slice),
+                cel(0, 0, CEL_TYPE_RAW, &raw_cel),
+                cel(1, 1, CEL_TYPE_COMPRESSED, &compressed_cel),
+            ],
+        );
+        let frame2 = frame(50, &[cel(0, 0, CEL_TYPE_LINKED, &0u16.to_le_bytes())]);
+
+        [header(2), frame1, frame2].concat()
+    }
+
+    #[test]
+    fn test_parse_aseprite_data() {
+        let (aseprite, frame_pixels) = parse_aseprite_data(&test_file(), None).unwrap();
+        assert_eq!((aseprite.width, aseprite.height), (2, 2));
+        assert_eq!(aseprite.colors, vec![0x000000, 0xff0000, 0x0000ff]);
+        assert_eq!(aseprite.frames.len(), 2);
+        assert_eq!(aseprite.frames[1].duration, 50);
+        assert_eq!(aseprite.frames[1].x, 2);
+        assert_eq!(aseprite.tags[0].name, "walk");
+        assert_eq!(aseprite.tags[0].to, 1);
+        assert_eq!(aseprite.tags[0].direction, TagDirection::PingPong);
+        assert_eq!(aseprite.tags[0].repeat, 3);
+        assert_eq!(aseprite.slices[0].name, "hitbox");
+        assert_eq!(aseprite.slices[0].keys[0].width, 2);
+        assert_eq!(aseprite.slices[0].keys[0].pivot, Some((1, 0)));
+        assert!(!aseprite.layers[1].visible);
+
+        let expected = vec![Some(Pixel::Index(1)), None, None, None];
+        assert_eq!(frame_pixels[0], expected);
+        assert_eq!(frame_pixels[1], expected);
+
+        let (_, frame_pixels) = parse_aseprite_data(&test_file(), Some(1)).unwrap();
+        assert_eq!(
+            frame_pixels[0],
+            vec![None, Some(Pixel::Index(2)), None, None]
+        );
+        assert!(parse_aseprite_data(&test_file(), Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_palettes() {
+        let palette_file = |size: u32, first: u32, last: u32| {
+            let mut palette = Vec::new();
+            for value in [size, first, last, 0, 0] {
+                palette.extend_from_slice(&value.to_le_bytes());
+            }
+            palette.extend_from_slice(&[0, 0, 255, 0, 0, 255]);
+            [header(1), frame(100, &[chunk(CHUNK_PALETTE, &palette)])].concat()
+        };
+        assert!(parse_aseprite_data(&palette_file(1, 0, 0), None).is_ok());
+        assert!(parse_aseprite_data(&palette_file(u32::MAX, 0, 0), None).is_err());
+        assert!(parse_aseprite_data(&palette_file(1, 0, 1), None).is_err());
+        assert!(parse_aseprite_data(&palette_file(2, 1, 0), None).is_err());
+
+        let mut old_palette = 2u16.to_le_bytes().to_vec();
+        old_palette.extend_from_slice(&[0, 0]);
+        old_palette.extend_from_slice(&[0; 256 * 3]);
+        old_palette.extend_from_slice(&[1, 1, 255, 0, 0]);
+        let file = [
+            header(1),
+            frame(100, &[chunk(CHUNK_OLD_PALETTE, &old_palette)]),
+        ]
+        .concat();
+        assert!(parse_aseprite_data(&file, None).is_err());
+    }
+
+    #[test]
+    fn test_tilemap_layers() {
+        let file = |tilemap_flags| {
+            let layers = [
+                layer("body", LAYER_FLAG_VISIBLE, LAYER_TYPE_IMAGE, 0),
+                layer("tiles", tilemap_flags, LAYER_TYPE_TILEMAP, 0),
+            ];
+            [header(1), frame(100, &layers)].concat()
+        };
+
+        assert_eq!(
+            parse_aseprite_data(&file(LAYER_FLAG_VISIBLE), None).err(),
+            Some("Tilemap layer 'tiles' is not supported in Aseprite files".to_string())
+        );
+        assert!(parse_aseprite_data(&file(0), None).is_ok());
+        assert!(parse_aseprite_data(&file(0), Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_load_aseprite_colkey() {
+        let path =
+            std::env::temp_dir().join(format!("pyxel_aseprite_{}.aseprite", std::process::id()));
+        fs::write(&path, test_file()).unwrap();
+
+        let image = Image::new(4, 2);
+        image.lock().cls(5);
+        let result = image
+            .lock()
+            .load_aseprite(0, 0, &path.to_string_lossy(), None, None);
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        let mut image = image.lock();
+        assert_eq!(image.pget(0.0, 0.0), 1);
+        assert_eq!(image.pget(1.0, 0.0), 5);
+        assert_eq!(image.pget(2.0, 0.0), 1);
+        assert_eq!(image.pget(3.0, 1.0), 5);
+    }
+}
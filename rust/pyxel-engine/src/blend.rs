@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+
+use parking_lot::Mutex;
+
+use crate::image::{Color, Rgb24};
+use crate::settings::BLEND_TABLE_SIZE;
+
+const BLEND_TABLE_CACHE_SIZE: usize = 8;
+
+type BlendTableKey = (BlendMode, Vec<Rgb24>);
+
+static BLEND_TABLES: LazyLock<Mutex<BlendTableCache>> =
+    LazyLock::new(|| Mutex::new(BlendTableCache::new(BLEND_TABLE_CACHE_SIZE)));
+
+struct BlendTableCache {
+    tables: HashMap<BlendTableKey, (Arc<Vec<Color>>, u64)>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl BlendTableCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            tables: HashMap::new(),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    // Evicts the least recently used table once the cache is full
+    fn table(&mut self, mode: BlendMode, colors: &[Rgb24]) -> Arc<Vec<Color>> {
+        self.clock += 1;
+        let key = (mode, colors.to_vec());
+
+        if !self.tables.contains_key(&key) && self.tables.len() >= self.capacity {
+            let oldest = self
+                .tables
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.tables.remove(&oldest);
+            }
+        }
+
+        let (table, last_used) = self
+            .tables
+            .entry(key)
+            .or_insert_with(|| (Arc::new(build_blend_table(mode, colors)), 0));
+        *last_used = self.clock;
+        table.clone()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    None,
+    Add,
+    Subtract,
+    Multiply,
+    Average,
+}
+
+impl From<u32> for BlendMode {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::Add,
+            2 => Self::Subtract,
+            3 => Self::Multiply,
+            4 => Self::Average,
+            _ => Self::None,
+        }
+    }
+}
+
+impl BlendMode {
+    fn blend_channel(self, src: u8, dst: u8) -> u8 {
+        let (src, dst) = (src as u32, dst as u32);
+        let value = match self {
+            Self::None => src,
+            Self::Add => src + dst,
+            Self::Subtract => dst.saturating_sub(src),
+            Self::Multiply => src * dst / 255,
+            Self::Average => u32::midpoint(src, dst),
+        };
+        value.min(255) as u8
+    }
+
+    fn blend_rgb(self, src: Rgb24, dst: Rgb24) -> Rgb24 {
+        [16, 8, 0].iter().fold(0, |rgb, &shift| {
+            let value = self.blend_channel((src >> shift) as u8, (dst >> shift) as u8);
+            rgb | ((value as Rgb24) << shift)
+        })
+    }
+}
+
+// The table is indexed by src * BLEND_TABLE_SIZE + dst and yields the nearest palette color
+pub fn blend_table(mode: BlendMode, colors: &[Rgb24]) -> Option<Arc<Vec<Color>>> {
+    if mode == BlendMode::None {
+        return None;
+    }
+
+    Some(BLEND_TABLES.lock().table(mode, colors))
+}
+
+fn build_blend_table(mode: BlendMode, colors: &[Rgb24]) -> Vec<Color> {
+    let table_size = BLEND_TABLE_SIZE as usize;
+    let num_colors = colors.len().min(table_size);
+    let mut nearest_colors: HashMap<Rgb24, Color> = HashMap::new();
+    let mut blend_table: Vec<Color> = (0..table_size * table_size)
+        .map(|i| (i / table_size) as Color)
+        .collect();
+
+    for src in 0..num_colors {
+        for dst in 0..num_colors {
+            let rgb = mode.blend_rgb(colors[src], colors[dst]);
+            let color = *nearest_colors
+                .entry(rgb)
+                .or_insert_with(|| nearest_color(rgb, &colors[..num_colors]));
+            blend_table[src * table_size + dst] = color;
+        }
+    }
+
+    blend_table
+}
+
+fn nearest_color(rgb: Rgb24, colors: &[Rgb24]) -> Color {
+    let distance = |color: Rgb24| {
+        [16, 8, 0]
+            .iter()
+            .map(|&shift| {
+                let diff = ((rgb >> shift) & 0xff) as i32 - ((color >> shift) & 0xff) as i32;
+                diff * diff
+            })
+            .sum::<i32>()
+    };
+
+    colors
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, &color)| distance(color))
+        .map_or(0, |(index, _)| index as Color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_table() {
+        let colors = [0x000000, 0x808080, 0xffffff, 0xff0000];
+        let size = BLEND_TABLE_SIZE as usize;
+
+        let table = blend_table(BlendMode::Add, &colors).unwrap();
+        assert_eq!(table[size + 1], 2);
+        assert_eq!(table[3 * size], 3);
+
+        let table = blend_table(BlendMode::Subtract, &colors).unwrap();
+        assert_eq!(table[2 * size + 3], 0);
+        assert_eq!(table[size + 2], 1);
+
+        let table = blend_table(BlendMode::Multiply, &colors).unwrap();
+        assert_eq!(table[2 * size + 3], 3);
+        assert_eq!(table[2 * size + 1], 1);
+
+        let table = blend_table(BlendMode::Average, &colors).unwrap();
+        assert_eq!(table[2 * size], 1);
+
+        // Colors outside the palette are written unchanged
+        assert_eq!(table[10 * size + 1], 10);
+        assert!(blend_table(BlendMode::None, &colors).is_none());
+    }
+
+    #[test]
+    fn test_blend_table_cache() {
+        let mut cache = BlendTableCache::new(2);
+        let table = cache.table(BlendMode::Add, &[0x000000]);
+        cache.table(BlendMode::Add, &[0xffffff]);
+        assert!(Arc::ptr_eq(
+            &table,
+            &cache.table(BlendMode::Add, &[0x000000])
+        ));
+
+        cache.table(BlendMode::Average, &[0x000000]);
+        assert_eq!(cache.tables.len(), 2);
+        assert!(Arc::ptr_eq(
+            &table,
+            &cache.table(BlendMode::Add, &[0x000000])
+        ));
+        assert!(!cache.tables.contains_key(&(BlendMode::Add, vec![0xffffff])));
+    }
+}
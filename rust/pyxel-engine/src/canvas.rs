@@ -1,14 +1,49 @@
 use std::cmp::max;
 use std::f32::consts::PI;
-use std::mem::swap;
+use std::mem::{swap, take};
+use std::sync::Arc;
 
+use crate::perspective::ScanlineTransform;
 use crate::rect_area::RectArea;
+use crate::settings::{BLEND_TABLE_SIZE, MAX_CURVE_SEGMENTS};
 use crate::utils::{f32_to_i32, f32_to_u32};
 
 pub trait ToIndex {
     fn to_index(&self) -> usize;
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
+impl From<u32> for FillRule {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::NonZero,
+            _ => Self::EvenOdd,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+impl From<u32> for LineCap {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::Round,
+            2 => Self::Square,
+            _ => Self::Butt,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Canvas<T: Copy + PartialEq + Default + ToIndex> {
     pub self_rect: RectArea,
@@ -17,6 +52,8 @@ pub struct Canvas<T: Copy + PartialEq + Default + ToIndex> {
     pub camera_y: i32,
     pub alpha: f32,
     pub data: Vec<T>,
+    pub blend_table: Option<Arc<Vec<T>>>,
+    pub mask: Option<Arc<Vec<bool>>>,
 
     should_write: fn(&Canvas<T>, i32, i32) -> bool,
 }
@@ -30,6 +67,8 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
             camera_y: 0,
             alpha: 1.0,
             data: vec![T::default(); (width * height) as usize],
+            blend_table: None,
+            mask: None,
             should_write: Self::should_write_always,
         }
     }
@@ -81,10 +120,34 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         }
     }
 
+    // Pixels are writable where the mask value matches, or is non-default without a mask value
+    pub fn mask(&mut self, mask: &Self, mask_value: Option<T>, invert: bool) {
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+        let mut mask_data = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let is_writable = mask.self_rect.contains(x as i32, y as i32) && {
+                    let value = mask.read_data(x, y);
+                    mask_value.map_or(value != T::default(), |mask_value| value == mask_value)
+                };
+                mask_data.push(is_writable != invert);
+            }
+        }
+
+        self.mask = Some(Arc::new(mask_data));
+    }
+
+    pub fn mask0(&mut self) {
+        self.mask = None;
+    }
+
     pub fn cls(&mut self, value: T) {
         let width = self.width();
         let height = self.height();
         let alpha = self.alpha;
+        let blend_table = take(&mut self.blend_table);
         self.dither(1.0);
         for y in 0..height {
             for x in 0..width {
@@ -92,6 +155,7 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
             }
         }
         self.dither(alpha);
+        self.blend_table = blend_table;
     }
 
     pub fn pget(&mut self, x: f32, y: f32) -> T {
@@ -126,7 +190,9 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
             };
             let length = end_x - start_x + 1;
             let alpha = (end_y - start_y) as f32 / (end_x - start_x) as f32;
-            for xi in 0..length {
+            let first = (self.clip_rect.left() - start_x).max(0);
+            let last = (self.clip_rect.right() - start_x + 1).min(length);
+            for xi in first..last {
                 self.write_data_with_clipping(
                     start_x + xi,
                     start_y + f32_to_i32(alpha * xi as f32),
@@ -141,7 +207,9 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
             };
             let length = end_y - start_y + 1;
             let alpha = (end_x - start_x) as f32 / (end_y - start_y) as f32;
-            for yi in 0..length {
+            let first = (self.clip_rect.top() - start_y).max(0);
+            let last = (self.clip_rect.bottom() - start_y + 1).min(length);
+            for yi in first..last {
                 self.write_data_with_clipping(
                     start_x + f32_to_i32(alpha * yi as f32),
                     start_y + yi,
@@ -358,6 +426,184 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         self.line(x2, y2, x3, y3, value);
     }
 
+    pub fn poly(&mut self, points: &[(f32, f32)], value: T, fill_rule: FillRule) {
+        if points.len() < 3 {
+            self.polyb(points, value);
+            return;
+        }
+
+        let vertices: Vec<(i32, i32)> = points
+            .iter()
+            .map(|&(x, y)| (f32_to_i32(x) - self.camera_x, f32_to_i32(y) - self.camera_y))
+            .collect();
+        let top = vertices.iter().map(|v| v.1).min().unwrap();
+        let bottom = vertices.iter().map(|v| v.1).max().unwrap();
+        let top = top.max(self.clip_rect.top());
+        let bottom = bottom.min(self.clip_rect.bottom());
+
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for y in top..=bottom {
+            crossings.clear();
+            for (i, &(x1, y1)) in vertices.iter().enumerate() {
+                let (x2, y2) = vertices[(i + 1) % vertices.len()];
+                if y1 == y2 || y < y1.min(y2) || y >= y1.max(y2) {
+                    continue;
+                }
+                let x = x1 as f32 + (y - y1) as f32 * (x2 - x1) as f32 / (y2 - y1) as f32;
+                crossings.push((x, if y1 < y2 { 1 } else { -1 }));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for (i, &(x, direction)) in crossings.iter().enumerate() {
+                winding += direction;
+                let is_inside = match fill_rule {
+                    FillRule::EvenOdd => i % 2 == 0,
+                    FillRule::NonZero => winding != 0,
+                };
+                if !is_inside || i + 1 >= crossings.len() {
+                    continue;
+                }
+                let left = f32_to_i32(x).max(self.clip_rect.left());
+                let right = f32_to_i32(crossings[i + 1].0).min(self.clip_rect.right());
+                for xi in left..=right {
+                    self.write_data_with_clipping(xi, y, value);
+                }
+            }
+        }
+
+        self.polyb(points, value);
+    }
+
+    pub fn polyb(&mut self, points: &[(f32, f32)], value: T) {
+        match points.len() {
+            0 => {}
+            1 => self.pset(points[0].0, points[0].1, value),
+            _ => {
+                for (i, &(x1, y1)) in points.iter().enumerate() {
+                    let (x2, y2) = points[(i + 1) % points.len()];
+                    self.line(x1, y1, x2, y2, value);
+                }
+            }
+        }
+    }
+
+    pub fn thick_line(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        width: f32,
+        value: T,
+        line_cap: LineCap,
+    ) {
+        let length = (x2 - x1).hypot(y2 - y1);
+        if width <= 1.0 || length < f32::EPSILON {
+            self.line(x1, y1, x2, y2, value);
+            if line_cap == LineCap::Round && width > 1.0 {
+                self.circ(x1, y1, (width - 1.0) / 2.0, value);
+            }
+            return;
+        }
+
+        let half_width = (width - 1.0) / 2.0;
+        let dx = (x2 - x1) / length * half_width;
+        let dy = (y2 - y1) / length * half_width;
+        let (x1, y1, x2, y2) = if line_cap == LineCap::Square {
+            (x1 - dx, y1 - dy, x2 + dx, y2 + dy)
+        } else {
+            (x1, y1, x2, y2)
+        };
+
+        self.poly(
+            &[
+                (x1 - dy, y1 + dx),
+                (x2 - dy, y2 + dx),
+                (x2 + dy, y2 - dx),
+                (x1 + dy, y1 - dx),
+            ],
+            value,
+            FillRule::NonZero,
+        );
+        if line_cap == LineCap::Round {
+            self.circ(x1, y1, half_width, value);
+            self.circ(x2, y2, half_width, value);
+        }
+    }
+
+    pub fn arc(&mut self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, value: T) {
+        let points = Self::arc_points(x, y, radius, start_angle, end_angle);
+        for segment in points.windows(2) {
+            self.line(
+                segment[0].0,
+                segment[0].1,
+                segment[1].0,
+                segment[1].1,
+                value,
+            );
+        }
+    }
+
+    pub fn pie(&mut self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, value: T) {
+        let mut points = Self::arc_points(x, y, radius, start_angle, end_angle);
+        points.push((x, y));
+        self.poly(&points, value, FillRule::NonZero);
+    }
+
+    pub fn bezier(&mut self, points: &[(f32, f32)], value: T) {
+        if points.len() < 2 {
+            self.polyb(points, value);
+            return;
+        }
+
+        let curve_length: f32 = points
+            .windows(2)
+            .map(|segment| (segment[1].0 - segment[0].0).hypot(segment[1].1 - segment[0].1))
+            .sum();
+        let num_segments = (curve_length.ceil() as u32).clamp(1, MAX_CURVE_SEGMENTS);
+
+        let mut prev_point = points[0];
+        for i in 1..=num_segments {
+            let point = Self::bezier_point(points, i as f32 / num_segments as f32);
+            self.line(prev_point.0, prev_point.1, point.0, point.1, value);
+            prev_point = point;
+        }
+    }
+
+    fn arc_points(
+        x: f32,
+        y: f32,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+    ) -> Vec<(f32, f32)> {
+        let start = start_angle.to_radians();
+        let sweep = (end_angle - start_angle).to_radians();
+        let num_segments = ((radius * sweep.abs()).ceil() as u32).clamp(1, MAX_CURVE_SEGMENTS);
+
+        (0..=num_segments)
+            .map(|i| {
+                let angle = start + sweep * i as f32 / num_segments as f32;
+                (x + radius * angle.cos(), y + radius * angle.sin())
+            })
+            .collect()
+    }
+
+    fn bezier_point(points: &[(f32, f32)], t: f32) -> (f32, f32) {
+        let mut points = points.to_vec();
+        while points.len() > 1 {
+            for i in 0..points.len() - 1 {
+                points[i] = (
+                    points[i].0 + (points[i + 1].0 - points[i].0) * t,
+                    points[i].1 + (points[i + 1].1 - points[i].1) * t,
+                );
+            }
+            points.pop();
+        }
+        points[0]
+    }
+
     pub fn fill(&mut self, x: f32, y: f32, value: T) {
         let x = f32_to_i32(x) - self.camera_x;
         let y = f32_to_i32(y) - self.camera_y;
@@ -370,24 +616,21 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
             return;
         }
 
+        // Blending would make the filled area indistinguishable from the unfilled one
+        let blend_table = take(&mut self.blend_table);
         let mut visit_stack = Vec::new();
         visit_stack.push((x, y));
         while let Some((x, y)) = visit_stack.pop() {
-            if !self.clip_rect.contains(x, y) || self.read_data(x as usize, y as usize) != dst_value
-            {
+            if !self.clip_rect.contains(x, y) || !self.is_fill_target(x, y, dst_value) {
                 continue;
             }
 
             let mut left = x;
             let mut right = x;
-            while left > self.clip_rect.left()
-                && self.read_data((left - 1) as usize, y as usize) == dst_value
-            {
+            while left > self.clip_rect.left() && self.is_fill_target(left - 1, y, dst_value) {
                 left -= 1;
             }
-            while right < self.clip_rect.right()
-                && self.read_data((right + 1) as usize, y as usize) == dst_value
-            {
+            while right < self.clip_rect.right() && self.is_fill_target(right + 1, y, dst_value) {
                 right += 1;
             }
 
@@ -400,8 +643,7 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
                     let mut scan_x = left;
                     let mut in_segment = false;
                     while scan_x <= right {
-                        let is_target =
-                            self.read_data(scan_x as usize, scan_y as usize) == dst_value;
+                        let is_target = self.is_fill_target(scan_x, scan_y, dst_value);
                         if is_target && !in_segment {
                             visit_stack.push((scan_x, scan_y));
                             in_segment = true;
@@ -413,6 +655,7 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
                 }
             }
         }
+        self.blend_table = blend_table;
     }
 
     pub fn blt(
@@ -551,22 +794,123 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         }
     }
 
+    pub fn tri_textured<F: FnMut(i32, i32) -> Option<T>>(
+        &mut self,
+        points: [(f32, f32); 3],
+        uvs: [(f32, f32); 3],
+        mut sample: F,
+    ) {
+        let points = points.map(|(x, y)| {
+            (
+                (f32_to_i32(x) - self.camera_x) as f32,
+                (f32_to_i32(y) - self.camera_y) as f32,
+            )
+        });
+        let [(x1, y1), (x2, y2), (x3, y3)] = points;
+        let area = (x2 - x1) * (y3 - y1) - (x3 - x1) * (y2 - y1);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+
+        let left = f32_to_i32(x1.min(x2).min(x3)).max(self.clip_rect.left());
+        let right = f32_to_i32(x1.max(x2).max(x3)).min(self.clip_rect.right());
+        let top = f32_to_i32(y1.min(y2).min(y3)).max(self.clip_rect.top());
+        let bottom = f32_to_i32(y1.max(y2).max(y3)).min(self.clip_rect.bottom());
+
+        for y in top..=bottom {
+            for x in left..=right {
+                let (px, py) = (x as f32, y as f32);
+                let w1 = ((x2 - px) * (y3 - py) - (x3 - px) * (y2 - py)) / area;
+                let w2 = ((x3 - px) * (y1 - py) - (x1 - px) * (y3 - py)) / area;
+                let w3 = 1.0 - w1 - w2;
+                if w1 < -f32::EPSILON || w2 < -f32::EPSILON || w3 < -f32::EPSILON {
+                    continue;
+                }
+
+                let u = w1 * uvs[0].0 + w2 * uvs[1].0 + w3 * uvs[2].0;
+                let v = w1 * uvs[0].1 + w2 * uvs[1].1 + w3 * uvs[2].1;
+                if let Some(value) = sample(f32_to_i32(u), f32_to_i32(v)) {
+                    self.write_data(x as usize, y as usize, value);
+                }
+            }
+        }
+    }
+
+    pub fn blt_perspective<F: FnMut(i32, i32) -> Option<T>>(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        lines: &[Option<ScanlineTransform>],
+        mut sample: F,
+    ) {
+        let x = f32_to_i32(x) - self.camera_x;
+        let y = f32_to_i32(y) - self.camera_y;
+        let width = f32_to_u32(width);
+        let height = f32_to_u32(height);
+        let rect = RectArea::new(x, y, width, height).intersects(self.clip_rect);
+        if rect.is_empty() {
+            return;
+        }
+
+        for yi in rect.top()..=rect.bottom() {
+            let Some(Some(line)) = lines.get((yi - y) as usize) else {
+                continue;
+            };
+            for xi in rect.left()..=rect.right() {
+                let offset = (xi - x) as f32;
+                let u = (line.u + line.du * offset).floor() as i32;
+                let v = (line.v + line.dv * offset).floor() as i32;
+                if let Some(value) = sample(u, v) {
+                    self.write_data(xi as usize, yi as usize, value);
+                }
+            }
+        }
+    }
+
     pub fn read_data(&self, x: usize, y: usize) -> T {
         let width = self.width() as usize;
         self.data[width * y + x]
     }
 
     pub fn write_data(&mut self, x: usize, y: usize, value: T) {
-        if (self.should_write)(self, x as i32, y as i32) {
+        if (self.should_write)(self, x as i32, y as i32) && self.is_unmasked(x as i32, y as i32) {
             let width = self.width() as usize;
-            self.data[width * y + x] = value;
+            let index = width * y + x;
+            self.data[index] = self.blend_value(value, self.data[index]);
         }
     }
 
     fn write_data_with_clipping(&mut self, x: i32, y: i32, value: T) {
-        if (self.should_write)(self, x, y) && self.clip_rect.contains(x, y) {
+        if (self.should_write)(self, x, y)
+            && self.clip_rect.contains(x, y)
+            && self.is_unmasked(x, y)
+        {
+            let width = self.width() as usize;
+            let index = width * y as usize + x as usize;
+            self.data[index] = self.blend_value(value, self.data[index]);
+        }
+    }
+
+    fn is_unmasked(&self, x: i32, y: i32) -> bool {
+        self.mask.as_ref().is_none_or(|mask| {
             let width = self.width() as usize;
-            self.data[width * y as usize + x as usize] = value;
+            mask[width * y as usize + x as usize]
+        })
+    }
+
+    // Masked pixels bound the fill so that unwritten pixels are not revisited
+    fn is_fill_target(&self, x: i32, y: i32, dst_value: T) -> bool {
+        self.read_data(x as usize, y as usize) == dst_value && self.is_unmasked(x, y)
+    }
+
+    fn blend_value(&self, value: T, dst_value: T) -> T {
+        match &self.blend_table {
+            Some(blend_table) => {
+                blend_table[value.to_index() * BLEND_TABLE_SIZE as usize + dst_value.to_index()]
+            }
+            None => value,
         }
     }
 
@@ -682,7 +1026,8 @@ impl CopyArea {
 
 #[cfg(test)]
 mod tests {
-    use super::Canvas;
+    use super::{Canvas, FillRule, LineCap};
+    use crate::perspective::ScanlineTransform;
 
     #[test]
     fn fill_doesnt_overflow_stack() {
@@ -691,4 +1036,114 @@ mod tests {
         // this assertion won't even be reached if the above line overflows the stack
         assert_eq!(canvas.read_data(128, 128), 8);
     }
+
+    #[test]
+    fn mask_limits_writes() {
+        let mut mask = Canvas::<u8>::new(4, 8);
+        mask.rect(0.0, 0.0, 2.0, 8.0, 1);
+        mask.rect(2.0, 0.0, 2.0, 8.0, 2);
+
+        let mut canvas = Canvas::<u8>::new(8, 8);
+        canvas.mask(&mask, None, false);
+        canvas.rect(0.0, 0.0, 8.0, 8.0, 3);
+        assert_eq!(canvas.read_data(1, 1), 3);
+        assert_eq!(canvas.read_data(3, 1), 3);
+        assert_eq!(canvas.read_data(5, 1), 0);
+
+        canvas.mask(&mask, Some(2), true);
+        canvas.fill(0.0, 0.0, 4);
+        assert_eq!(canvas.read_data(1, 1), 4);
+        assert_eq!(canvas.read_data(3, 1), 3);
+        assert_eq!(canvas.read_data(5, 1), 0);
+
+        canvas.mask0();
+        canvas.fill(5.0, 1.0, 5);
+        assert_eq!(canvas.read_data(7, 7), 5);
+    }
+
+    #[test]
+    fn poly_clamps_huge_spans() {
+        let huge = [(-2e8, -2e8), (2e8, -2e8), (2e8, 2e8), (-2e8, 2e8)];
+        let mut canvas = Canvas::<u8>::new(16, 16);
+        canvas.clip(4.0, 4.0, 8.0, 8.0);
+        canvas.poly(&huge, 1, FillRule::EvenOdd);
+        canvas.line(-2e8, 8.0, 2e8, 8.0, 2);
+        assert_eq!(canvas.read_data(3, 3), 0);
+        assert_eq!(canvas.read_data(4, 4), 1);
+        assert_eq!(canvas.read_data(11, 11), 1);
+        assert_eq!(canvas.read_data(12, 12), 0);
+        assert_eq!(canvas.read_data(11, 8), 2);
+        assert_eq!(canvas.read_data(3, 8), 0);
+    }
+
+    #[test]
+    fn poly_fill_rules() {
+        let square = [(0.0, 0.0), (8.0, 0.0), (8.0, 8.0), (0.0, 8.0)];
+        let twice: Vec<_> = square.iter().chain(square.iter()).copied().collect();
+
+        let mut canvas = Canvas::<u8>::new(16, 16);
+        canvas.poly(&twice, 1, FillRule::NonZero);
+        assert_eq!(canvas.read_data(4, 4), 1);
+        assert_eq!(canvas.read_data(8, 8), 1);
+        assert_eq!(canvas.read_data(9, 4), 0);
+
+        let mut canvas = Canvas::<u8>::new(16, 16);
+        canvas.poly(&twice, 1, FillRule::EvenOdd);
+        assert_eq!(canvas.read_data(4, 4), 0);
+        assert_eq!(canvas.read_data(0, 4), 1);
+        assert_eq!(canvas.read_data(8, 4), 1);
+    }
+
+    #[test]
+    fn thick_line_caps() {
+        let mut canvas = Canvas::<u8>::new(16, 16);
+        canvas.thick_line(2.0, 5.0, 10.0, 5.0, 3.0, 1, LineCap::Butt);
+        assert_eq!(canvas.read_data(2, 4), 1);
+        assert_eq!(canvas.read_data(10, 6), 1);
+        assert_eq!(canvas.read_data(6, 3), 0);
+        assert_eq!(canvas.read_data(1, 5), 0);
+
+        canvas.thick_line(2.0, 5.0, 10.0, 5.0, 3.0, 2, LineCap::Square);
+        assert_eq!(canvas.read_data(1, 5), 2);
+        assert_eq!(canvas.read_data(11, 4), 2);
+    }
+
+    #[test]
+    fn bezier_reaches_end_points() {
+        let mut canvas = Canvas::<u8>::new(16, 16);
+        canvas.camera(-1.0, -1.0);
+        canvas.bezier(&[(0.0, 0.0), (7.0, 14.0), (14.0, 0.0)], 1);
+        assert_eq!(canvas.read_data(1, 1), 1);
+        assert_eq!(canvas.read_data(15, 1), 1);
+        assert_eq!(canvas.read_data(8, 8), 1);
+    }
+
+    #[test]
+    fn tri_textured_maps_uvs() {
+        let mut canvas = Canvas::<u8>::new(8, 8);
+        let uvs = [(10.0, 20.0), (17.0, 20.0), (10.0, 27.0)];
+        canvas.tri_textured([(0.0, 0.0), (7.0, 0.0), (0.0, 7.0)], uvs, |u, v| {
+            Some((u - 10 + (v - 20) * 8) as u8)
+        });
+        assert_eq!(canvas.read_data(0, 0), 0);
+        assert_eq!(canvas.read_data(7, 0), 7);
+        assert_eq!(canvas.read_data(2, 3), 26);
+        assert_eq!(canvas.read_data(7, 7), 0);
+    }
+
+    #[test]
+    fn blt_perspective_uses_scanlines() {
+        let mut canvas = Canvas::<u8>::new(4, 2);
+        let lines = [
+            None,
+            Some(ScanlineTransform {
+                u: 1.5,
+                v: 0.0,
+                du: 2.0,
+                dv: 0.0,
+            }),
+        ];
+        canvas.blt_perspective(0.0, 0.0, 4.0, 2.0, &lines, |u, _| Some(u as u8));
+        assert_eq!(canvas.data, vec![0, 0, 0, 0, 1, 3, 5, 7]);
+    }
 }
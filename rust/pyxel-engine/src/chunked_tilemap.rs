@@ -0,0 +1,520 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::collision::{TileCollision, TileQuery};
+use crate::resource_data::TilemapData;
+use crate::settings::TILE_SIZE;
+use crate::tilemap::{ImageSource, SharedTilemap, Tile, Tilemap};
+use crate::tileset::SharedTileset;
+
+pub type ChunkCoord = (i32, i32);
+pub type ChunkLoader = Box<dyn FnMut(ChunkCoord) -> Result<Option<SharedTilemap>, String> + Send>;
+pub type ChunkSaver = Box<dyn FnMut(ChunkCoord, SharedTilemap) -> Result<(), String> + Send>;
+
+pub struct ChunkedTilemap {
+    pub imgsrc: ImageSource,
+    pub tileset: Option<SharedTileset>,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub chunk_dir: Option<String>,
+    pub loader: Option<ChunkLoader>,
+    pub saver: Option<ChunkSaver>,
+
+    chunk_size: u32,
+    chunks: HashMap<ChunkCoord, SharedTilemap>,
+    missing_chunks: HashSet<ChunkCoord>,
+    modified_chunks: HashSet<ChunkCoord>,
+    blank_chunk: SharedTilemap,
+}
+
+pub type SharedChunkedTilemap = shared_type!(ChunkedTilemap);
+
+impl ChunkedTilemap {
+    pub fn new(chunk_size: u32, imgsrc: ImageSource) -> SharedChunkedTilemap {
+        let chunk_size = chunk_size.max(1);
+        new_shared_type!(Self {
+            imgsrc: imgsrc.clone(),
+            tileset: None,
+            tile_width: TILE_SIZE,
+            tile_height: TILE_SIZE,
+            chunk_dir: None,
+            loader: None,
+            saver: None,
+
+            chunk_size,
+            chunks: HashMap::new(),
+            missing_chunks: HashSet::new(),
+            modified_chunks: HashSet::new(),
+            blank_chunk: Tilemap::new(chunk_size, chunk_size, imgsrc),
+        })
+    }
+
+    pub const fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    pub fn loaded_chunks(&self) -> Vec<ChunkCoord> {
+        let mut coords: Vec<_> = self.chunks.keys().copied().collect();
+        coords.sort_unstable();
+        coords
+    }
+
+    // Splits a tile position into its chunk and the offset within that chunk
+    pub fn locate(&self, x: i32, y: i32) -> (ChunkCoord, (usize, usize)) {
+        let size = self.chunk_size as i32;
+        (
+            (x.div_euclid(size), y.div_euclid(size)),
+            (x.rem_euclid(size) as usize, y.rem_euclid(size) as usize),
+        )
+    }
+
+    // Chunks are looked up in memory, then through the loader, then in the chunk directory.
+    // Coordinates found in neither source are remembered until they are written or invalidated,
+    // while a chunk that fails to load is reported and looked up again next time.
+    pub fn load_chunk(&mut self, coord: ChunkCoord) -> Result<Option<SharedTilemap>, String> {
+        if let Some(chunk) = self.chunks.get(&coord) {
+            return Ok(Some(chunk.clone()));
+        }
+        if self.missing_chunks.contains(&coord) {
+            return Ok(None);
+        }
+
+        let mut source = match self.loader.as_mut() {
+            Some(loader) => loader(coord)?,
+            None => None,
+        };
+        if source.is_none() {
+            if let Some(filename) = self.chunk_filename(coord) {
+                if Path::new(&filename).exists() {
+                    let toml_text = fs::read_to_string(&filename)
+                        .map_err(|_| format!("Failed to open file '{filename}'"))?;
+                    let tilemap_data: TilemapData = toml::from_str(&toml_text)
+                        .map_err(|_| format!("Failed to parse file '{filename}'"))?;
+                    source = Some(tilemap_data.to_tilemap());
+                }
+            }
+        }
+        let Some(source) = source else {
+            self.missing_chunks.insert(coord);
+            return Ok(None);
+        };
+
+        let chunk = self.new_chunk();
+        let (width, height) = {
+            let source = source.lock();
+            (source.width(), source.height())
+        };
+        chunk.lock().blt(
+            0.0,
+            0.0,
+            source,
+            0.0,
+            0.0,
+            width as f32,
+            height as f32,
+            None,
+            None,
+            None,
+        );
+        self.chunks.insert(coord, chunk.clone());
+        Ok(Some(chunk))
+    }
+
+    // The returned chunk may be edited by the caller, so it is treated as modified
+    pub fn chunk(&mut self, coord: ChunkCoord) -> Result<SharedTilemap, String> {
+        let chunk = if let Some(chunk) = self.load_chunk(coord)? {
+            chunk
+        } else {
+            let chunk = self.new_chunk();
+            self.chunks.insert(coord, chunk.clone());
+            self.missing_chunks.remove(&coord);
+            chunk
+        };
+        self.modified_chunks.insert(coord);
+        Ok(chunk)
+    }
+
+    // Lets a chunk that was previously missing be looked up again
+    pub fn invalidate_chunk(&mut self, coord: ChunkCoord) {
+        self.missing_chunks.remove(&coord);
+    }
+
+    pub fn invalidate_chunks(&mut self) {
+        self.missing_chunks.clear();
+    }
+
+    pub fn load_area(&mut self, x: i32, y: i32, width: i32, height: i32) -> Result<(), String> {
+        for coord in self.chunks_in_area(x, y, width, height) {
+            self.load_chunk(coord)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_chunk(&mut self, coord: ChunkCoord) -> Result<(), String> {
+        let Some(chunk) = self.chunks.get(&coord).cloned() else {
+            return Ok(());
+        };
+
+        if self.saver.is_none() && self.chunk_dir.is_none() {
+            return Ok(());
+        }
+        if let Some(saver) = self.saver.as_mut() {
+            saver(coord, chunk.clone())?;
+        }
+        if let Some(filename) = self.chunk_filename(coord) {
+            let toml_text = toml::to_string(&TilemapData::from_tilemap(chunk)).unwrap();
+            fs::write(&filename, toml_text)
+                .map_err(|_| format!("Failed to write file '{filename}'"))?;
+        }
+        self.modified_chunks.remove(&coord);
+        Ok(())
+    }
+
+    pub fn save_chunks(&mut self) -> Result<(), String> {
+        for coord in self.loaded_chunks() {
+            self.save_chunk(coord)?;
+        }
+        Ok(())
+    }
+
+    // A modified chunk is kept loaded when it cannot be saved so that no edits are lost
+    pub fn unload_chunk(&mut self, coord: ChunkCoord) -> Result<(), String> {
+        self.save_chunk(coord)?;
+        if self.modified_chunks.contains(&coord) {
+            let (chunk_x, chunk_y) = coord;
+            return Err(format!(
+                "Chunk ({chunk_x}, {chunk_y}) has unsaved changes and no saver or chunk directory"
+            ));
+        }
+        self.chunks.remove(&coord);
+        Ok(())
+    }
+
+    // Keeps only the chunks overlapping the given tile area, which is typically the camera view
+    pub fn unload_outside(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> Result<(), String> {
+        let keep = self.chunks_in_area(x, y, width, height);
+        for coord in self.loaded_chunks() {
+            if !keep.contains(&coord) {
+                self.unload_chunk(coord)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Chunks that have never been written read as the default tile
+    pub fn pget(&mut self, x: i32, y: i32) -> Result<Tile, String> {
+        let (coord, (offset_x, offset_y)) = self.locate(x, y);
+        Ok(self.load_chunk(coord)?.map_or((0, 0, 0), |chunk| {
+            chunk.lock().canvas.read_data(offset_x, offset_y)
+        }))
+    }
+
+    pub fn pset(&mut self, x: i32, y: i32, tile: Tile) -> Result<(), String> {
+        let (coord, (offset_x, offset_y)) = self.locate(x, y);
+        self.chunk(coord)?
+            .lock()
+            .canvas
+            .write_data(offset_x, offset_y, tile);
+        Ok(())
+    }
+
+    pub fn rect(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        tile: Tile,
+    ) -> Result<(), String> {
+        let size = self.chunk_size as i32;
+        for (chunk_x, chunk_y) in self.chunks_in_area(x, y, width, height) {
+            let chunk = self.chunk((chunk_x, chunk_y))?;
+            chunk.lock().canvas.rect(
+                (x - chunk_x * size) as f32,
+                (y - chunk_y * size) as f32,
+                width as f32,
+                height as f32,
+                tile,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn collide(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        dy: f32,
+        walls: &[Tile],
+    ) -> Result<(f32, f32), String> {
+        let collision = self.sweep(x, y, w, h, dx, dy, walls)?;
+        Ok((collision.dx, collision.dy))
+    }
+
+    // Chunks along the movement are streamed in first so that the query itself stays read-only
+    pub fn sweep(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        dy: f32,
+        walls: &[Tile],
+    ) -> Result<TileCollision, String> {
+        let tile_width = self.tile_width as f32;
+        let tile_height = self.tile_height as f32;
+        let left = ((x + dx.min(0.0)) / tile_width).floor() as i32 - 1;
+        let top = ((y + dy.min(0.0)) / tile_height).floor() as i32 - 1;
+        let right = ((x + w + dx.max(0.0)) / tile_width).ceil() as i32 + 1;
+        let bottom = ((y + h + dy.max(0.0) + dx.abs()) / tile_height).ceil() as i32 + 1;
+        self.load_area(left, top, right - left, bottom - top)?;
+
+        let tileset = self.tileset.as_ref().map(|tileset| tileset.lock());
+        let tile_at = |x, y| Some(self.read_tile(x, y));
+        let query = TileQuery {
+            tile_at: &tile_at,
+            tile_width,
+            tile_height,
+            tileset: tileset.as_deref(),
+            walls,
+        };
+        Ok(query.sweep(x, y, w, h, dx, dy))
+    }
+
+    // Returns the chunk to draw for a chunk coordinate, using a blank chunk for missing ones.
+    // Drawing cannot report errors, so a chunk that fails to load is drawn blank and retried.
+    pub(crate) fn chunk_for_drawing(&mut self, coord: ChunkCoord) -> SharedTilemap {
+        let chunk = self
+            .load_chunk(coord)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| self.blank_chunk.clone());
+        {
+            let mut chunk = chunk.lock();
+            chunk.imgsrc = self.imgsrc.clone();
+            chunk.tileset.clone_from(&self.tileset);
+            chunk.tile_width = self.tile_width;
+            chunk.tile_height = self.tile_height;
+        }
+        chunk
+    }
+
+    pub(crate) fn chunks_in_area(
+        &self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> Vec<ChunkCoord> {
+        if width <= 0 || height <= 0 {
+            return Vec::new();
+        }
+
+        let ((left, top), _) = self.locate(x, y);
+        let ((right, bottom), _) = self.locate(x + width - 1, y + height - 1);
+        (top..=bottom)
+            .flat_map(|chunk_y| (left..=right).map(move |chunk_x| (chunk_x, chunk_y)))
+            .collect()
+    }
+
+    fn read_tile(&self, x: i32, y: i32) -> Tile {
+        let (coord, (offset_x, offset_y)) = self.locate(x, y);
+        self.chunks.get(&coord).map_or((0, 0, 0), |chunk| {
+            chunk.lock().canvas.read_data(offset_x, offset_y)
+        })
+    }
+
+    fn new_chunk(&self) -> SharedTilemap {
+        let chunk = Tilemap::new(self.chunk_size, self.chunk_size, self.imgsrc.clone());
+        {
+            let mut tilemap = chunk.lock();
+            tilemap.tileset.clone_from(&self.tileset);
+            tilemap.tile_width = self.tile_width;
+            tilemap.tile_height = self.tile_height;
+        }
+        chunk
+    }
+
+    fn chunk_filename(&self, (chunk_x, chunk_y): ChunkCoord) -> Option<String> {
+        self.chunk_dir.as_ref().map(|dir| {
+            Path::new(dir)
+                .join(format!("chunk_{chunk_x}_{chunk_y}.toml"))
+                .to_string_lossy()
+                .to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::image::Image;
+    use crate::tileset::Tileset;
+
+    #[test]
+    fn test_chunked_tiles() {
+        let tilemap = ChunkedTilemap::new(4, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        assert_eq!(tilemap.locate(-1, 5), ((-1, 1), (3, 1)));
+        assert_eq!(tilemap.pget(-100, 100).unwrap(), (0, 0, 0));
+        assert!(tilemap.loaded_chunks().is_empty());
+
+        tilemap.pset(-1, -1, (1, 2, 0)).unwrap();
+        tilemap.rect(2, 2, 4, 1, (3, 0, 0)).unwrap();
+        assert_eq!(tilemap.pget(-1, -1).unwrap(), (1, 2, 0));
+        assert_eq!(tilemap.pget(5, 2).unwrap(), (3, 0, 0));
+        assert_eq!(tilemap.pget(6, 2).unwrap(), (0, 0, 0));
+        assert_eq!(tilemap.loaded_chunks(), vec![(-1, -1), (0, 0), (1, 0)]);
+
+        let tileset = Tileset::new();
+        tileset.lock().tile_mut((3, 0, 0)).solid = true;
+        tilemap.tileset = Some(tileset);
+        let collision = tilemap.sweep(40.0, 0.0, 8.0, 8.0, 0.0, 20.0, &[]).unwrap();
+        assert_eq!(collision.dy, 8.0);
+        assert!(collision.grounded);
+    }
+
+    #[test]
+    fn test_bltm_chunked() {
+        let tiles = Image::new(16, 8);
+        tiles.lock().rect(8.0, 0.0, 8.0, 8.0, 5);
+        let tilemap = ChunkedTilemap::new(2, ImageSource::Image(tiles));
+        tilemap.lock().pset(-1, 0, (1, 0, 0)).unwrap();
+        tilemap.lock().pset(2, 0, (1, 0, 0)).unwrap();
+
+        let image = Image::new(40, 8);
+        let mut image = image.lock();
+        image.bltm_chunked(
+            0.0,
+            0.0,
+            tilemap.clone(),
+            -16.0,
+            0.0,
+            40.0,
+            8.0,
+            None,
+            None,
+            None,
+        );
+        let lit: Vec<_> = (0..40)
+            .filter(|&x| image.pget(x as f32, 0.0) == 5)
+            .collect();
+        assert_eq!(lit, (8..16).chain(32..40).collect::<Vec<_>>());
+
+        image.cls(0);
+        image.bltm_chunked(0.0, 0.0, tilemap, -16.0, 0.0, -40.0, 8.0, None, None, None);
+        let lit: Vec<_> = (0..40)
+            .filter(|&x| image.pget(x as f32, 0.0) == 5)
+            .collect();
+        assert_eq!(lit, (0..8).chain(24..32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_chunk_streaming() {
+        let dir = std::env::temp_dir().join(format!("pyxel_chunks_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let tilemap = ChunkedTilemap::new(8, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.chunk_dir = Some(dir.to_string_lossy().to_string());
+        tilemap.pset(-20, 3, (4, 5, 0)).unwrap();
+        tilemap.pset(3, 3, (1, 1, 0)).unwrap();
+        tilemap.unload_outside(0, 0, 8, 8).unwrap();
+        assert_eq!(tilemap.loaded_chunks(), vec![(0, 0)]);
+        assert!(dir.join("chunk_-3_0.toml").exists());
+
+        assert_eq!(tilemap.pget(-20, 3).unwrap(), (4, 5, 0));
+        tilemap.chunk_dir = None;
+        tilemap.loader = Some(Box::new(|(chunk_x, _)| {
+            Ok((chunk_x == 5).then(|| {
+                let chunk = Tilemap::new(2, 2, ImageSource::Index(0));
+                chunk.lock().cls((7, 7, 0));
+                chunk
+            }))
+        }));
+        assert_eq!(tilemap.pget(41, 1).unwrap(), (7, 7, 0));
+        assert_eq!(tilemap.pget(42, 1).unwrap(), (0, 0, 0));
+        assert_eq!(tilemap.pget(60, 1).unwrap(), (0, 0, 0));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_chunks() {
+        let num_loads = Arc::new(AtomicU32::new(0));
+        let tilemap = ChunkedTilemap::new(4, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.loader = Some(Box::new({
+            let num_loads = num_loads.clone();
+            move |_| {
+                num_loads.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+        }));
+
+        assert_eq!(tilemap.pget(0, 0).unwrap(), (0, 0, 0));
+        assert_eq!(tilemap.pget(1, 0).unwrap(), (0, 0, 0));
+        tilemap.sweep(0.0, 0.0, 8.0, 8.0, 0.0, 1.0, &[]).unwrap();
+        let _ = tilemap.chunk_for_drawing((0, 0));
+        assert_eq!(num_loads.load(Ordering::Relaxed), 4);
+
+        tilemap.pset(2, 0, (1, 0, 0)).unwrap();
+        assert_eq!(tilemap.pget(2, 0).unwrap(), (1, 0, 0));
+        assert!(tilemap.unload_chunk((0, 0)).is_err());
+        tilemap.saver = Some(Box::new(|_, _| Ok(())));
+        tilemap.unload_chunk((0, 0)).unwrap();
+        assert_eq!(tilemap.pget(2, 0).unwrap(), (0, 0, 0));
+        assert_eq!(num_loads.load(Ordering::Relaxed), 5);
+
+        tilemap.invalidate_chunk((-1, -1));
+        tilemap.pget(-1, -1).unwrap();
+        tilemap.pget(-1, -1).unwrap();
+        assert_eq!(num_loads.load(Ordering::Relaxed), 6);
+
+        tilemap.invalidate_chunks();
+        tilemap.pget(0, 0).unwrap();
+        tilemap.pget(-1, 0).unwrap();
+        assert_eq!(num_loads.load(Ordering::Relaxed), 8);
+    }
+
+    #[test]
+    fn test_chunk_errors() {
+        let tilemap = ChunkedTilemap::new(4, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.loader = Some(Box::new(|(chunk_x, _)| {
+            if chunk_x == 1 {
+                Err("broken chunk".to_string())
+            } else {
+                Ok(None)
+            }
+        }));
+
+        assert_eq!(tilemap.pget(4, 0), Err("broken chunk".to_string()));
+        assert!(tilemap.sweep(24.0, 0.0, 8.0, 8.0, 16.0, 0.0, &[]).is_err());
+        assert!(tilemap
+            .collide(24.0, 0.0, 8.0, 8.0, 16.0, 0.0, &[])
+            .is_err());
+        tilemap.loader = None;
+        assert_eq!(tilemap.pget(4, 0), Ok((0, 0, 0)));
+
+        tilemap.pset(0, 0, (1, 0, 0)).unwrap();
+        tilemap.saver = Some(Box::new(|_, _| Err("disk full".to_string())));
+        assert_eq!(tilemap.unload_chunk((0, 0)), Err("disk full".to_string()));
+        assert!(tilemap.unload_outside(8, 8, 4, 4).is_err());
+        assert_eq!(tilemap.pget(0, 0), Ok((1, 0, 0)));
+    }
+}
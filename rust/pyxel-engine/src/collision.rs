@@ -0,0 +1,441 @@
+use crate::tilemap::{Tile, Tilemap};
+use crate::tileset::{TileInfo, Tileset};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileContact {
+    pub x: i32,
+    pub y: i32,
+    pub tile: Tile,
+    pub normal: (f32, f32),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TileCollision {
+    pub dx: f32,
+    pub dy: f32,
+    pub contacts: Vec<TileContact>,
+    pub grounded: bool,
+    pub ceiling: bool,
+    pub wall_left: bool,
+    pub wall_right: bool,
+    pub on_slope: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TileShape {
+    Empty,
+    Solid,
+    OneWay,
+    Slope(f32, f32),
+}
+
+pub(crate) struct TileQuery<'a> {
+    pub tile_at: &'a dyn Fn(i32, i32) -> Option<Tile>,
+    pub tile_width: f32,
+    pub tile_height: f32,
+    pub tileset: Option<&'a Tileset>,
+    pub walls: &'a [Tile],
+}
+
+impl TileQuery<'_> {
+    fn shape(&self, tx: i32, ty: i32) -> (TileShape, Tile) {
+        let Some(tile) = (self.tile_at)(tx, ty) else {
+            return (TileShape::Empty, (0, 0, 0));
+        };
+        let info = self.tileset.and_then(|tileset| tileset.tile(tile));
+        if let Some(TileInfo {
+            slope: Some((left, right)),
+            ..
+        }) = info
+        {
+            return (TileShape::Slope(*left, *right), tile);
+        }
+        if self
+            .walls
+            .iter()
+            .any(|wall| (wall.0, wall.1) == (tile.0, tile.1))
+        {
+            return (TileShape::Solid, tile);
+        }
+
+        let shape = match info {
+            Some(info) if info.solid => TileShape::Solid,
+            Some(info) if info.one_way => TileShape::OneWay,
+            _ => TileShape::Empty,
+        };
+        (shape, tile)
+    }
+
+    // One-way tiles only stop movement coming down onto them from above
+    fn hit_tiles(
+        &self,
+        tiles: impl Iterator<Item = (i32, i32)>,
+        falling: bool,
+        normal: (f32, f32),
+        collision: &mut TileCollision,
+    ) -> bool {
+        let mut is_hit = false;
+        for (tx, ty) in tiles {
+            let (shape, tile) = self.shape(tx, ty);
+            if shape == TileShape::Solid || falling && shape == TileShape::OneWay {
+                collision.contacts.push(TileContact {
+                    x: tx,
+                    y: ty,
+                    tile,
+                    normal,
+                });
+                is_hit = true;
+            }
+        }
+        is_hit
+    }
+
+    // Returns the slope surface height under the given x along with its contact
+    fn slope_surface(&self, probe_x: f32, ty: i32) -> Option<(f32, TileContact)> {
+        let tile_width = self.tile_width;
+        let tile_height = self.tile_height;
+        let tx = (probe_x / tile_width).floor() as i32;
+        let (TileShape::Slope(left, right), tile) = self.shape(tx, ty) else {
+            return None;
+        };
+
+        let fraction = (probe_x / tile_width - tx as f32).clamp(0.0, 1.0);
+        let surface =
+            (ty + 1) as f32 * tile_height - tile_height * (left + (right - left) * fraction);
+        let rise = (right - left) * tile_height / tile_width;
+        let length = rise.hypot(1.0);
+        let contact = TileContact {
+            x: tx,
+            y: ty,
+            tile,
+            normal: (-rise / length, -1.0 / length),
+        };
+        Some((surface, contact))
+    }
+
+    // A box standing on a slope sinks into the next column by up to half its width times the
+    // slope, so tiles within that depth of its bottom are stepped onto instead of blocking it
+    fn slope_step(&self, x: f32, y: f32, w: f32, h: f32) -> f32 {
+        let probe_x = x + w / 2.0;
+        let bottom = y + h;
+        let ty0 = (y / self.tile_height).floor() as i32;
+        let ty1 = ((bottom + 1.0) / self.tile_height).floor() as i32;
+
+        (ty0..=ty1)
+            .filter_map(|ty| self.slope_surface(probe_x, ty))
+            .find(|(surface, _)| *surface > y && *surface <= bottom + 1.0)
+            .map_or(0.0, |(_, contact)| {
+                let rise = -contact.normal.0 / contact.normal.1;
+                w / 2.0 * rise.abs() + 0.001
+            })
+    }
+
+    // Every column between the current and new edges is tested, so fast or large boxes cannot
+    // skip over a wall
+    fn sweep_x(
+        &self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        step: f32,
+        collision: &mut TileCollision,
+    ) -> f32 {
+        if dx == 0.0 {
+            return dx;
+        }
+
+        let tile_width = self.tile_width;
+        let tile_height = self.tile_height;
+        let ty0 = (y / tile_height).floor() as i32;
+        let ty1 = if step > 0.0 {
+            ((y + h - step) / tile_height).ceil() as i32 - 1
+        } else {
+            ((y + h - 1.0) / tile_height).floor() as i32
+        };
+
+        if dx > 0.0 {
+            let start_tx = ((x + w - 1.0) / tile_width).floor() as i32 + 1;
+            let end_tx = ((x + dx + w - 1.0) / tile_width).floor() as i32;
+            for tx in start_tx..=end_tx {
+                if self.hit_tiles(
+                    (ty0..=ty1).map(|ty| (tx, ty)),
+                    false,
+                    (-1.0, 0.0),
+                    collision,
+                ) {
+                    collision.wall_right = true;
+                    return tx as f32 * tile_width - w - x;
+                }
+            }
+        } else {
+            let start_tx = (x / tile_width).floor() as i32 - 1;
+            let end_tx = ((x + dx) / tile_width).floor() as i32;
+            for tx in (end_tx..=start_tx).rev() {
+                if self.hit_tiles((ty0..=ty1).map(|ty| (tx, ty)), false, (1.0, 0.0), collision) {
+                    collision.wall_left = true;
+                    return (tx + 1) as f32 * tile_width - x;
+                }
+            }
+        }
+
+        dx
+    }
+
+    fn sweep_y(
+        &self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dy: f32,
+        collision: &mut TileCollision,
+    ) -> f32 {
+        if dy == 0.0 {
+            return dy;
+        }
+
+        let tile_width = self.tile_width;
+        let tile_height = self.tile_height;
+        let tx0 = (x / tile_width).floor() as i32;
+        let tx1 = ((x + w - 1.0) / tile_width).floor() as i32;
+
+        if dy > 0.0 {
+            // Slope surfaces are tested along the way so that fast falls cannot pass through them
+            let bottom = y + h;
+            let start_ty = ((bottom - 1.0) / tile_height).floor() as i32 + 1;
+            let end_ty = ((bottom + dy - 1.0) / tile_height).floor() as i32;
+            let slope_end_ty = ((bottom + dy) / tile_height).floor() as i32;
+            for ty in start_ty - 1..=end_ty.max(slope_end_ty) {
+                if (start_ty..=end_ty).contains(&ty)
+                    && self.hit_tiles((tx0..=tx1).map(|tx| (tx, ty)), true, (0.0, -1.0), collision)
+                {
+                    collision.grounded = true;
+                    return ty as f32 * tile_height - h - y;
+                }
+                if let Some((surface, contact)) = self
+                    .slope_surface(x + w / 2.0, ty)
+                    .filter(|(surface, _)| *surface >= bottom && *surface <= bottom + dy)
+                {
+                    collision.contacts.push(contact);
+                    collision.grounded = true;
+                    collision.on_slope = true;
+                    return surface - bottom;
+                }
+            }
+        } else {
+            let start_ty = (y / tile_height).floor() as i32 - 1;
+            let end_ty = ((y + dy) / tile_height).floor() as i32;
+            for ty in (end_ty..=start_ty).rev() {
+                if self.hit_tiles((tx0..=tx1).map(|tx| (tx, ty)), false, (0.0, 1.0), collision) {
+                    collision.ceiling = true;
+                    return (ty + 1) as f32 * tile_height - y;
+                }
+            }
+        }
+
+        dy
+    }
+
+    // Slopes hold the box by its bottom center and pull it down while walking downhill, and a
+    // box that stepped off a slope onto a solid tile is lifted onto its top
+    fn resolve_slope(
+        &self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        dy: f32,
+        step: f32,
+        collision: &mut TileCollision,
+    ) -> Option<f32> {
+        if dy < 0.0 {
+            return None;
+        }
+
+        let tile_height = self.tile_height;
+        let probe_x = x + w / 2.0;
+        let bottom = y + h;
+        let snap = dx.abs();
+        let ty0 = (y / tile_height).floor() as i32;
+        let ty1 = ((bottom + snap) / tile_height).floor() as i32;
+
+        for ty in ty0..=ty1 {
+            if let Some((surface, contact)) = self.slope_surface(probe_x, ty) {
+                if surface <= y || surface > bottom + snap {
+                    continue;
+                }
+                if !collision.contacts.contains(&contact) {
+                    collision.contacts.push(contact);
+                }
+                collision.grounded = true;
+                collision.on_slope = true;
+                return Some(surface - h);
+            }
+
+            let top = ty as f32 * tile_height;
+            let tx = (probe_x / self.tile_width).floor() as i32;
+            let (shape, tile) = self.shape(tx, ty);
+            if step > 0.0 && shape == TileShape::Solid && top > y && top < bottom {
+                collision.contacts.push(TileContact {
+                    x: tx,
+                    y: ty,
+                    tile,
+                    normal: (0.0, -1.0),
+                });
+                collision.grounded = true;
+                return Some(top - h);
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn sweep(&self, x: f32, y: f32, w: f32, h: f32, dx: f32, dy: f32) -> TileCollision {
+        let mut collision = TileCollision::default();
+        let step = self.slope_step(x, y, w, h);
+
+        if dx.abs() >= dy.abs() {
+            collision.dx = self.sweep_x(x, y, w, h, dx, step, &mut collision);
+            collision.dy = self.sweep_y(x + collision.dx, y, w, h, dy, &mut collision);
+        } else {
+            collision.dy = self.sweep_y(x, y, w, h, dy, &mut collision);
+            collision.dx = self.sweep_x(x, y + collision.dy, w, h, dx, step, &mut collision);
+        }
+
+        if let Some(new_y) = self.resolve_slope(
+            x + collision.dx,
+            y + collision.dy,
+            w,
+            h,
+            dx,
+            dy,
+            step,
+            &mut collision,
+        ) {
+            collision.dy = new_y - y;
+        }
+
+        collision
+    }
+}
+
+impl Tilemap {
+    pub fn sweep(
+        &self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        dy: f32,
+        walls: &[Tile],
+    ) -> TileCollision {
+        let tileset = self.tileset.as_ref().map(|tileset| tileset.lock());
+        let query = TileQuery {
+            tile_at: &|x, y| self.tile_at((x, y)),
+            tile_width: self.tile_width as f32,
+            tile_height: self.tile_height as f32,
+            tileset: tileset.as_deref(),
+            walls,
+        };
+        query.sweep(x, y, w, h, dx, dy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use super::*;
+    use crate::tilemap::{ImageSource, SharedTilemap};
+
+    #[test]
+    fn test_sweep() {
+        let tilemap = Tilemap::new(10, 4, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.rect(0.0, 3.0, 10.0, 1.0, (1, 0, 0));
+        tilemap.rect(9.0, 0.0, 1.0, 3.0, (1, 0, 0));
+        tilemap.pset(1.0, 2.0, (2, 0, 0));
+        let tileset = Tileset::new();
+        tileset.lock().tile_mut((1, 0, 0)).solid = true;
+        tileset.lock().tile_mut((2, 0, 0)).slope = Some((0.0, 1.0));
+        tilemap.tileset = Some(tileset);
+
+        let collision = tilemap.sweep(0.0, 16.0, 8.0, 8.0, 4.0, 1.0, &[]);
+        assert_eq!((collision.dx, collision.dy), (4.0, 0.0));
+        assert!(collision.grounded && collision.on_slope);
+
+        let collision = tilemap.sweep(4.0, 16.0, 8.0, 8.0, 4.0, 1.0, &[]);
+        assert_eq!((collision.dx, collision.dy), (4.0, -4.0));
+        let normal = collision.contacts.last().unwrap().normal;
+        assert!((normal.0 + FRAC_1_SQRT_2).abs() < 0.001);
+        assert!((normal.1 + FRAC_1_SQRT_2).abs() < 0.001);
+
+        let collision = tilemap.sweep(24.0, 0.0, 20.0, 20.0, 50.0, 0.0, &[]);
+        assert_eq!(collision.dx, 72.0 - 20.0 - 24.0);
+        assert!(collision.wall_right && !collision.grounded);
+        assert_eq!(collision.contacts.len(), 3);
+
+        let collision = tilemap.sweep(40.0, 0.0, 8.0, 8.0, 0.0, 100.0, &[]);
+        assert_eq!(collision.dy, 16.0);
+        assert!(collision.grounded);
+
+        tilemap.tile_width = 16;
+        tilemap.tile_height = 4;
+        let collision = tilemap.sweep(0.0, 0.0, 8.0, 4.0, 200.0, 0.0, &[]);
+        assert_eq!(collision.dx, 144.0 - 8.0);
+        let collision = tilemap.sweep(80.0, 0.0, 8.0, 4.0, 0.0, 20.0, &[]);
+        assert_eq!(collision.dy, 8.0);
+    }
+
+    fn slope_tilemap(width: u32, height: u32) -> SharedTilemap {
+        let tilemap = Tilemap::new(width, height, ImageSource::Index(0));
+        let tileset = Tileset::new();
+        tileset.lock().tile_mut((1, 0, 0)).solid = true;
+        tileset.lock().tile_mut((2, 0, 0)).slope = Some((0.0, 1.0));
+        tilemap.lock().tileset = Some(tileset);
+        tilemap
+    }
+
+    #[test]
+    fn test_sweep_ramp() {
+        let tilemap = slope_tilemap(12, 6);
+        let mut tilemap = tilemap.lock();
+        tilemap.rect(0.0, 5.0, 12.0, 1.0, (1, 0, 0));
+        tilemap.rect(3.0, 4.0, 2.0, 1.0, (1, 0, 0));
+        tilemap.pset(4.0, 3.0, (1, 0, 0));
+        tilemap.rect(5.0, 2.0, 7.0, 3.0, (1, 0, 0));
+        for i in 0..3 {
+            tilemap.pset((2 + i) as f32, (4 - i) as f32, (2, 0, 0));
+        }
+
+        let (mut x, mut y) = (0.0, 32.0);
+        for _ in 0..40 {
+            let collision = tilemap.sweep(x, y, 8.0, 8.0, 2.0, 1.0, &[]);
+            assert!(!collision.wall_right, "blocked at ({x}, {y})");
+            assert!(collision.grounded);
+            x += collision.dx;
+            y += collision.dy;
+        }
+        assert_eq!((x, y), (80.0, 8.0));
+    }
+
+    #[test]
+    fn test_sweep_fast_fall_onto_slope() {
+        let tilemap = slope_tilemap(5, 5);
+        let mut tilemap = tilemap.lock();
+        tilemap.pset(2.0, 2.0, (2, 0, 0));
+
+        let collision = tilemap.sweep(16.0, -50.0, 8.0, 8.0, 0.0, 100.0, &[]);
+        assert_eq!(collision.dy, 62.0);
+        assert!(collision.grounded && collision.on_slope);
+        assert_eq!(collision.contacts.len(), 1);
+
+        let collision = tilemap.sweep(16.0, -50.0, 8.0, 8.0, 1.0, 100.0, &[]);
+        assert!(collision.grounded && collision.on_slope);
+        assert_eq!(collision.dy, 61.0);
+    }
+}
@@ -5,10 +5,13 @@ use std::io::{BufRead, BufReader, Read};
 use fontdue::{Font as FontdueFont, FontSettings, LineMetrics, Metrics};
 
 use crate::canvas::Canvas;
-use crate::image::Color;
+use crate::image::{Color, Image};
+use crate::settings::{FONT_DATA, FONT_HEIGHT, FONT_WIDTH, MAX_FONT_CODE, MIN_FONT_CODE};
+use crate::text_shaping::{is_combining_char, visual_order};
 
 const DEFAULT_FONT_SIZE: f32 = 10.0;
 const FONT_ALPHA_THRESHOLD: u8 = 128;
+const GLYPH_CACHE_SIZE: usize = 1024;
 
 #[derive(Copy, Clone)]
 pub struct BdfBoundingBox {
@@ -24,6 +27,47 @@ pub struct BdfGlyph {
     bitmap: Vec<u32>,
 }
 
+pub struct BitmapGlyph {
+    width: i32,
+    pixels: Vec<Option<Color>>,
+}
+
+pub struct GlyphCache<T> {
+    glyphs: HashMap<char, (T, u64)>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl<T> GlyphCache<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            glyphs: HashMap::new(),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    // Evicts the least recently used glyph once the cache is full
+    fn glyph(&mut self, c: char, rasterize: impl FnOnce() -> T) -> &T {
+        self.clock += 1;
+
+        if !self.glyphs.contains_key(&c) && self.glyphs.len() >= self.capacity {
+            let oldest = self
+                .glyphs
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(&c, _)| c);
+            if let Some(oldest) = oldest {
+                self.glyphs.remove(&oldest);
+            }
+        }
+
+        let (glyph, last_used) = self.glyphs.entry(c).or_insert_with(|| (rasterize(), 0));
+        *last_used = self.clock;
+        glyph
+    }
+}
+
 pub enum Font {
     Bdf {
         bounding_box: BdfBoundingBox,
@@ -31,9 +75,19 @@ pub enum Font {
     },
     Fontdue {
         font: FontdueFont,
-        cache: HashMap<char, (Metrics, Vec<u8>)>,
+        cache: GlyphCache<(Metrics, Vec<u8>)>,
         size: f32,
     },
+    Bitmap {
+        height: i32,
+        spacing: i32,
+        multicolor: bool,
+        glyphs: HashMap<char, BitmapGlyph>,
+    },
+    Builtin,
+    Stack {
+        fonts: Vec<SharedFont>,
+    },
 }
 
 pub type SharedFont = shared_type!(Font);
@@ -133,13 +187,113 @@ impl Font {
             let size = font_size.unwrap_or(DEFAULT_FONT_SIZE);
             Ok(new_shared_type!(Font::Fontdue {
                 font,
-                cache: HashMap::new(),
+                cache: GlyphCache::new(GLYPH_CACHE_SIZE),
                 size,
             }))
         }
     }
 
-    fn should_skip_char(c: char) -> bool {
+    pub fn builtin() -> SharedFont {
+        new_shared_type!(Font::Builtin)
+    }
+
+    // Each character is drawn with the first font that has a glyph for it
+    pub fn stack(fonts: &[SharedFont]) -> SharedFont {
+        let mut stack_fonts = Vec::new();
+        for font in fonts {
+            if let Font::Stack { fonts } = &*font.lock() {
+                stack_fonts.extend(fonts.iter().cloned());
+                continue;
+            }
+            stack_fonts.push(font.clone());
+        }
+        new_shared_type!(Font::Stack { fonts: stack_fonts })
+    }
+
+    // Cells are read row by row from the region, and variable width glyphs are trimmed to their opaque columns
+    pub fn from_image(
+        image: &Image,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        cell_width: u32,
+        cell_height: u32,
+        chars: &str,
+        variable_width: bool,
+        multicolor: bool,
+        transparent: Color,
+        spacing: Option<i32>,
+    ) -> Result<SharedFont, String> {
+        if cell_width == 0 || cell_height == 0 {
+            return Err("Font cell size must be positive".to_string());
+        }
+        if x.checked_add(width)
+            .is_none_or(|right| right > image.width())
+            || y.checked_add(height)
+                .is_none_or(|bottom| bottom > image.height())
+        {
+            return Err("Font region is outside the image".to_string());
+        }
+
+        let num_columns = width / cell_width;
+        let num_cells = num_columns * (height / cell_height);
+        if chars.chars().count() > num_cells as usize {
+            return Err(format!(
+                "Font region has {num_cells} cells for {} characters",
+                chars.chars().count()
+            ));
+        }
+
+        let mut glyphs = HashMap::new();
+        for (i, c) in chars.chars().enumerate() {
+            let cell_x = x + (i as u32 % num_columns) * cell_width;
+            let cell_y = y + (i as u32 / num_columns) * cell_height;
+            let is_opaque_column = |xi: u32| {
+                (0..cell_height).any(|yi| {
+                    image
+                        .canvas
+                        .read_data((cell_x + xi) as usize, (cell_y + yi) as usize)
+                        != transparent
+                })
+            };
+
+            // Empty cells such as spaces keep the full cell width
+            let (left, right) = if variable_width {
+                let left = (0..cell_width).find(|&xi| is_opaque_column(xi));
+                let right = (0..cell_width).rev().find(|&xi| is_opaque_column(xi));
+                left.zip(right).map_or((0, cell_width), |(l, r)| (l, r + 1))
+            } else {
+                (0, cell_width)
+            };
+
+            let pixels = (0..cell_height)
+                .flat_map(|yi| (left..right).map(move |xi| (xi, yi)))
+                .map(|(xi, yi)| {
+                    let color = image
+                        .canvas
+                        .read_data((cell_x + xi) as usize, (cell_y + yi) as usize);
+                    (color != transparent).then_some(color)
+                })
+                .collect();
+            glyphs.insert(
+                c,
+                BitmapGlyph {
+                    width: (right - left) as i32,
+                    pixels,
+                },
+            );
+        }
+
+        Ok(new_shared_type!(Font::Bitmap {
+            height: cell_height as i32,
+            spacing: spacing.unwrap_or(i32::from(variable_width)),
+            multicolor,
+            glyphs,
+        }))
+    }
+
+    pub(crate) fn should_skip_char(c: char) -> bool {
         let cp = c as u32;
 
         // Control characters
@@ -178,62 +332,143 @@ impl Font {
                 continue;
             }
 
-            let advance = match self {
-                Font::Bdf { glyphs, .. } => glyphs.get(&(c as i32)).map_or(0, |g| g.dwidth),
-                Font::Fontdue { font, cache, size } => {
-                    let metrics = if let Some((m, _)) = cache.get(&c) {
-                        *m
-                    } else {
-                        let (m, bitmap) = font.rasterize(c, *size);
-                        cache.insert(c, (m, bitmap));
-                        m
-                    };
-                    metrics.advance_width.ceil() as i32
+            current_width += self.advance(c);
+        }
+
+        max_width.max(current_width)
+    }
+
+    pub(crate) fn advance(&mut self, c: char) -> i32 {
+        if is_combining_char(c) {
+            return 0;
+        }
+
+        match self {
+            Font::Bdf { glyphs, .. } => glyphs.get(&(c as i32)).map_or(0, |g| g.dwidth),
+            Font::Fontdue { font, cache, size } => {
+                let (metrics, _) = cache.glyph(c, || font.rasterize(c, *size));
+                metrics.advance_width.ceil() as i32
+            }
+            Font::Bitmap {
+                spacing, glyphs, ..
+            } => glyphs.get(&c).map_or(0, |g| g.width + *spacing),
+            Font::Builtin => {
+                if (MIN_FONT_CODE..=MAX_FONT_CODE).contains(&c) {
+                    FONT_WIDTH as i32
+                } else {
+                    0
                 }
-            };
+            }
+            Font::Stack { fonts } => {
+                Self::fallback(fonts, c).map_or(0, |font| fonts[font].lock().advance(c))
+            }
+        }
+    }
 
-            current_width += advance;
+    pub(crate) fn has_glyph(&self, c: char) -> bool {
+        match self {
+            Font::Bdf { glyphs, .. } => glyphs.contains_key(&(c as i32)),
+            Font::Fontdue { font, .. } => font.lookup_glyph_index(c) != 0,
+            Font::Bitmap { glyphs, .. } => glyphs.contains_key(&c),
+            Font::Builtin => (MIN_FONT_CODE..=MAX_FONT_CODE).contains(&c),
+            Font::Stack { fonts } => Self::fallback(fonts, c).is_some(),
         }
+    }
 
-        max_width.max(current_width)
+    fn fallback(fonts: &[SharedFont], c: char) -> Option<usize> {
+        fonts.iter().position(|font| font.lock().has_glyph(c))
+    }
+
+    // BDF fonts carry no kerning tables, so only TTF/OTF pairs are adjusted
+    pub(crate) fn kerning(&self, left: char, right: char) -> i32 {
+        match self {
+            Font::Bdf { .. } | Font::Bitmap { .. } | Font::Builtin => 0,
+            Font::Fontdue { font, size, .. } => font
+                .horizontal_kern(left, right, *size)
+                .map_or(0, |kern| kern.round() as i32),
+            Font::Stack { fonts } => {
+                match (Self::fallback(fonts, left), Self::fallback(fonts, right)) {
+                    (Some(left_font), Some(right_font)) if left_font == right_font => {
+                        fonts[left_font].lock().kerning(left, right)
+                    }
+                    _ => 0,
+                }
+            }
+        }
+    }
+
+    pub(crate) fn line_height(&self) -> i32 {
+        match self {
+            Font::Bdf { bounding_box, .. } => bounding_box.height,
+            Font::Bitmap { height, .. } => *height,
+            Font::Fontdue { font, size, .. } => font
+                .horizontal_line_metrics(*size)
+                .map_or(*size, |line_metrics| line_metrics.new_line_size)
+                .ceil() as i32,
+            Font::Builtin => FONT_HEIGHT as i32,
+            Font::Stack { fonts } => fonts
+                .iter()
+                .map(|font| font.lock().line_height())
+                .max()
+                .unwrap_or(0),
+        }
     }
 
     pub(crate) fn draw(
         &mut self,
         canvas: &mut Canvas<Color>,
+        palette: &[Color],
         x: i32,
         y: i32,
         text: &str,
         color: Color,
     ) {
+        let line_height = self.line_height();
+
+        for (i, line) in text.split('\n').enumerate() {
+            let y = y + i as i32 * line_height;
+            let chars: Vec<char> = line
+                .chars()
+                .filter(|&c| !Self::should_skip_char(c))
+                .collect();
+            let mut x = x;
+            let mut base_x = x;
+
+            for c in visual_order(&chars, |c| c) {
+                if !is_combining_char(c) {
+                    base_x = x;
+                }
+                self.draw_char(canvas, palette, x, base_x, y, c, color);
+                x += self.advance(c);
+            }
+        }
+    }
+
+    // Combining marks are drawn over their base character except in TTF/OTF fonts,
+    // whose mark glyphs are already offset back from the pen position
+    fn draw_char(
+        &mut self,
+        canvas: &mut Canvas<Color>,
+        palette: &[Color],
+        x: i32,
+        base_x: i32,
+        y: i32,
+        c: char,
+        color: Color,
+    ) {
+        let line_height = self.line_height();
+        let glyph_x = if is_combining_char(c) { base_x } else { x };
+
         match self {
             Font::Bdf {
                 bounding_box,
                 glyphs,
             } => {
-                let start_x = x;
-                let mut x = x;
-                let mut y = y;
-                for c in text.chars() {
-                    if c == '\n' {
-                        x = start_x;
-                        y += bounding_box.height;
-                        continue;
-                    }
-                    if Self::should_skip_char(c) {
-                        continue;
-                    }
-
-                    if let Some(glyph) = glyphs.get(&(c as i32)) {
-                        Self::draw_bdf_glyph(canvas, x, y, bounding_box, glyph, color);
-                        x += glyph.dwidth;
-                    }
+                if let Some(glyph) = glyphs.get(&(c as i32)) {
+                    Self::draw_bdf_glyph(canvas, glyph_x, y, bounding_box, glyph, color);
                 }
             }
             Font::Fontdue { font, cache, size } => {
-                let start_x = x;
-                let mut x = x;
-                let mut y = y;
                 let line_metrics = font.horizontal_line_metrics(*size).unwrap_or(LineMetrics {
                     ascent: *size,
                     descent: 0.0,
@@ -241,29 +476,43 @@ impl Font {
                     new_line_size: *size,
                 });
                 let ascent = line_metrics.ascent.round() as i32;
-                let line_height = line_metrics.new_line_size.ceil() as i32;
-
-                for c in text.chars() {
-                    if c == '\n' {
-                        x = start_x;
-                        y += line_height;
-                        continue;
-                    }
-                    if Self::should_skip_char(c) {
-                        continue;
-                    }
+                let (metrics, bitmap) = cache.glyph(c, || font.rasterize(c, *size));
+                Self::draw_fontdue_glyph(canvas, x, y, ascent, metrics, bitmap, color);
+            }
+            Font::Bitmap {
+                multicolor, glyphs, ..
+            } => {
+                if let Some(glyph) = glyphs.get(&c) {
+                    let palette = multicolor.then_some(palette);
+                    Self::draw_bitmap_glyph(canvas, glyph_x, y, glyph, palette, color);
+                }
+            }
+            Font::Builtin => Self::draw_builtin_glyph(canvas, glyph_x, y, c, color),
+            Font::Stack { fonts } => {
+                if let Some(font) = Self::fallback(fonts, c) {
+                    let mut font = fonts[font].lock();
+                    let y = y + line_height - font.line_height();
+                    font.draw_char(canvas, palette, x, base_x, y, c, color);
+                }
+            }
+        }
+    }
 
-                    let (metrics, bitmap) = if let Some(entry) = cache.get(&c) {
-                        entry
-                    } else {
-                        let (metrics, bitmap) = font.rasterize(c, *size);
-                        cache.insert(c, (metrics, bitmap));
-                        cache.get(&c).unwrap()
-                    };
+    // Reads the original glyph data so that drawing never locks the font image
+    fn draw_builtin_glyph(canvas: &mut Canvas<Color>, x: i32, y: i32, c: char, color: Color) {
+        if !(MIN_FONT_CODE..=MAX_FONT_CODE).contains(&c) {
+            return;
+        }
 
-                    Self::draw_fontdue_glyph(canvas, x, y, ascent, metrics, bitmap, color);
-                    x += metrics.advance_width.ceil() as i32;
+        let mut data = FONT_DATA[c as usize - MIN_FONT_CODE as usize];
+        for yi in 0..FONT_HEIGHT as i32 {
+            for xi in 0..FONT_WIDTH as i32 {
+                let value_x = x + xi;
+                let value_y = y + yi;
+                if data & 0x800000 != 0 && canvas.clip_rect.contains(value_x, value_y) {
+                    canvas.write_data(value_x as usize, value_y as usize, color);
                 }
+                data <<= 1;
             }
         }
     }
@@ -290,6 +539,35 @@ impl Font {
         }
     }
 
+    // Multicolor glyphs map their own colors through the palette instead of using the text color,
+    // and colors outside the palette are drawn unmapped
+    fn draw_bitmap_glyph(
+        canvas: &mut Canvas<Color>,
+        x: i32,
+        y: i32,
+        glyph: &BitmapGlyph,
+        palette: Option<&[Color]>,
+        color: Color,
+    ) {
+        if glyph.width == 0 {
+            return;
+        }
+
+        for (i, pixel) in glyph.pixels.iter().enumerate() {
+            let Some(pixel) = *pixel else {
+                continue;
+            };
+            let value_x = x + i as i32 % glyph.width;
+            let value_y = y + i as i32 / glyph.width;
+            if canvas.clip_rect.contains(value_x, value_y) {
+                let color = palette.map_or(color, |palette| {
+                    palette.get(pixel as usize).copied().unwrap_or(pixel)
+                });
+                canvas.write_data(value_x as usize, value_y as usize, color);
+            }
+        }
+    }
+
     fn draw_fontdue_glyph(
         canvas: &mut Canvas<Color>,
         x: i32,
@@ -313,3 +591,97 @@ impl Font {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_image() {
+        let image = Image::new(16, 8);
+        let mut image = image.lock();
+        image.rect(1.0, 0.0, 2.0, 4.0, 7);
+        image.pset(2.0, 3.0, 8);
+        image.rect(4.0, 0.0, 4.0, 4.0, 7);
+
+        let font = Font::from_image(&image, 0, 0, 12, 4, 4, 4, "ab ", true, true, 0, None).unwrap();
+        let mut font = font.lock();
+        assert_eq!(font.advance('a'), 3);
+        assert_eq!(font.advance('b'), 5);
+        assert_eq!(font.advance(' '), 5);
+        assert_eq!(font.advance('c'), 0);
+        assert_eq!(font.line_height(), 4);
+        assert!(Font::from_image(&image, 0, 0, 12, 4, 4, 4, "abcd", true, true, 0, None).is_err());
+        assert!(
+            Font::from_image(&image, u32::MAX, 0, 12, 4, 4, 4, "a", true, true, 0, None).is_err()
+        );
+
+        let mut palette: Vec<Color> = (0..16).collect();
+        palette[8] = 9;
+        let mut canvas = Canvas::new(8, 4);
+        font.draw(&mut canvas, &palette, 0, 0, "ab", 3);
+        assert_eq!(canvas.read_data(0, 0), 7);
+        assert_eq!(canvas.read_data(1, 3), 9);
+        assert_eq!(canvas.read_data(2, 0), 0);
+        assert_eq!(canvas.read_data(3, 0), 7);
+        let mut canvas = Canvas::new(8, 4);
+        font.draw(&mut canvas, &palette[..8], 0, 0, "a", 3);
+        assert_eq!(canvas.read_data(1, 3), 8);
+
+        let font = Font::from_image(&image, 0, 0, 8, 4, 4, 4, "ab", false, false, 0, None).unwrap();
+        let mut font = font.lock();
+        assert_eq!(font.advance('a'), 4);
+        let mut canvas = Canvas::new(8, 4);
+        font.draw(&mut canvas, &palette, 0, 0, "a", 3);
+        assert_eq!(canvas.read_data(1, 0), 3);
+        assert_eq!(canvas.read_data(2, 3), 3);
+    }
+
+    #[test]
+    fn test_glyph_cache() {
+        let mut cache = GlyphCache::new(2);
+        assert_eq!(*cache.glyph('a', || 1), 1);
+        assert_eq!(*cache.glyph('b', || 2), 2);
+        assert_eq!(*cache.glyph('a', || 3), 1);
+        assert_eq!(*cache.glyph('c', || 4), 4);
+        assert_eq!(cache.glyphs.len(), 2);
+        assert_eq!(*cache.glyph('a', || 5), 1);
+        assert_eq!(*cache.glyph('b', || 6), 6);
+    }
+
+    #[test]
+    fn test_font_stack() {
+        let image = Image::new(8, 4);
+        let mut image = image.lock();
+        image.rect(0.0, 1.0, 3.0, 3.0, 7);
+        image.pset(5.0, 0.0, 7);
+
+        let bitmap_font =
+            Font::from_image(&image, 0, 0, 8, 4, 4, 4, "a\u{301}", true, false, 0, None).unwrap();
+        let stack = Font::stack(&[bitmap_font.clone(), Font::builtin()]);
+        let stack = Font::stack(&[stack, bitmap_font]);
+        let mut stack = stack.lock();
+        if let Font::Stack { fonts } = &*stack {
+            assert_eq!(fonts.len(), 3);
+        }
+
+        assert_eq!(stack.advance('a'), 4);
+        assert_eq!(stack.advance('b'), 4);
+        assert_eq!(stack.advance('\u{301}'), 0);
+        assert_eq!(stack.advance('\u{3042}'), 0);
+        assert!(!stack.has_glyph('\u{3042}'));
+        assert_eq!(stack.line_height(), 6);
+        assert_eq!(stack.text_width("ab\u{301}"), 8);
+
+        let palette: Vec<Color> = (0..16).collect();
+        let mut canvas = Canvas::new(12, 6);
+        stack.draw(&mut canvas, &palette, 0, 0, "a\u{301}b", 3);
+        assert_eq!(canvas.read_data(0, 3), 3);
+        assert_eq!(canvas.read_data(2, 5), 3);
+        assert_eq!(canvas.read_data(0, 2), 3);
+        assert_eq!(canvas.read_data(1, 2), 0);
+        assert_eq!(canvas.read_data(4, 0), 3);
+        assert_eq!(canvas.read_data(5, 1), 3);
+        assert_eq!(canvas.read_data(6, 0), 0);
+    }
+}
@@ -1,13 +1,19 @@
 use std::collections::HashMap;
+use std::iter::once;
 use std::mem::size_of;
 
 use glow::{HasContext, PixelUnpackData};
 use pyxel_platform::GLProfile;
 
+use crate::blend::BlendMode;
+use crate::canvas::{FillRule, LineCap};
 use crate::font::SharedFont;
-use crate::image::Color;
-use crate::pyxel::Pyxel;
-use crate::settings::{BACKGROUND_COLOR, MAX_COLORS, NUM_SCREEN_TYPES};
+use crate::image::{Color, Rgb24, SharedImage};
+use crate::layer::SharedLayer;
+use crate::perspective::ScanlineTransform;
+use crate::pyxel::{Pyxel, DEFAULT_FONT};
+use crate::settings::{BACKGROUND_COLOR, MAX_COLORS, MAX_LAYERS, NUM_SCREEN_TYPES};
+use crate::text_layout::{TextBounds, TextStyle};
 
 #[cfg(target_os = "macos")]
 const GL_VERSION: &str = include_str!("shaders/gles_version.glsl");
@@ -17,6 +23,7 @@ const GL_VERSION: &str = include_str!("shaders/gl_version.glsl");
 const GLES_VERSION: &str = include_str!("shaders/gles_version.glsl");
 const COMMON_VERT: &str = include_str!("shaders/common.vert");
 const COMMON_FRAG: &str = include_str!("shaders/common.frag");
+const RASTER_TEXTURE_UNIT: u32 = glow::TEXTURE3 + MAX_LAYERS;
 const SCREEN_FRAGS: [&str; NUM_SCREEN_TYPES as usize] = [
     include_str!("shaders/crisp.frag"),
     include_str!("shaders/smooth.frag"),
@@ -29,14 +36,28 @@ pub struct ScreenShader {
     vertex_array: glow::VertexArray,
 }
 
+pub struct RenderTarget {
+    framebuffer: glow::NativeFramebuffer,
+    texture: glow::NativeTexture,
+    width: u32,
+    height: u32,
+}
+
 pub struct Graphics {
     screen_shaders: Vec<ScreenShader>,
     screen_texture: glow::NativeTexture,
     colors_texture: glow::NativeTexture,
+    layer_textures: Vec<glow::NativeTexture>,
+    raster_texture: glow::NativeTexture,
+    render_targets: Vec<RenderTarget>,
 }
 
 impl Graphics {
-    pub fn new() -> Self {
+    pub fn new() -> Option<Self> {
+        if pyxel_platform::gl_profile() == GLProfile::None {
+            return None;
+        }
+
         unsafe {
             let gl = pyxel_platform::gl_context();
 
@@ -45,122 +66,257 @@ impl Graphics {
             }
             gl.disable(glow::BLEND);
 
-            let screen_shaders = Self::create_screen_shaders(gl);
-            let screen_texture = Self::create_screen_texture(gl);
+            let screen_shaders = SCREEN_FRAGS
+                .iter()
+                .map(|screen_frag| {
+                    Self::create_screen_shader(gl, screen_frag)
+                        .unwrap_or_else(|err| panic!("{err}"))
+                })
+                .collect();
+            let screen_texture = Self::create_index_texture(gl, glow::TEXTURE0);
             let colors_texture = Self::create_colors_texture(gl);
+            let layer_textures = (0..MAX_LAYERS)
+                .map(|i| Self::create_index_texture(gl, glow::TEXTURE3 + i))
+                .collect();
+            let raster_texture = Self::create_index_texture(gl, RASTER_TEXTURE_UNIT);
 
-            Self {
+            Some(Self {
                 screen_shaders,
                 screen_texture,
                 colors_texture,
-            }
+                layer_textures,
+                raster_texture,
+                render_targets: Vec::new(),
+            })
+        }
+    }
+
+    pub fn add_screen_shader(&mut self, screen_frag: &str) -> Result<(), String> {
+        unsafe {
+            let gl = pyxel_platform::gl_context();
+            let screen_shader = Self::create_screen_shader(gl, screen_frag)?;
+            self.screen_shaders.push(screen_shader);
+        }
+        Ok(())
+    }
+
+    unsafe fn compile_shader(
+        gl: &mut glow::Context,
+        shader_type: u32,
+        source: &str,
+    ) -> Result<glow::Shader, String> {
+        let shader = gl.create_shader(shader_type)?;
+        gl.shader_source(shader, source);
+        gl.compile_shader(shader);
+
+        if gl.get_shader_compile_status(shader) {
+            Ok(shader)
+        } else {
+            let shader_name = if shader_type == glow::VERTEX_SHADER {
+                "vertex shader"
+            } else {
+                "fragment shader"
+            };
+            let info_log = gl.get_shader_info_log(shader);
+            gl.delete_shader(shader);
+            Err(format!("\n[{shader_name}]\n{info_log}"))
         }
     }
 
-    unsafe fn create_screen_shaders(gl: &mut glow::Context) -> Vec<ScreenShader> {
+    unsafe fn create_screen_shader(
+        gl: &mut glow::Context,
+        screen_frag: &str,
+    ) -> Result<ScreenShader, String> {
         let glsl_version = if pyxel_platform::gl_profile() == GLProfile::Gles {
             GLES_VERSION
         } else {
             GL_VERSION
         };
 
-        let mut screen_shaders = Vec::new();
-        for &screen_frag in &SCREEN_FRAGS {
-            // Vertex shader
-            let vertex_shader = gl.create_shader(glow::VERTEX_SHADER).unwrap();
-            gl.shader_source(vertex_shader, &format!("{glsl_version}{COMMON_VERT}"));
-            gl.compile_shader(vertex_shader);
-            assert!(
-                gl.get_shader_compile_status(vertex_shader),
-                "\n[vertex shader]\n{}",
-                gl.get_shader_info_log(vertex_shader)
-            );
+        // Vertex shader
+        let vertex_shader = Self::compile_shader(
+            gl,
+            glow::VERTEX_SHADER,
+            &format!("{glsl_version}{COMMON_VERT}"),
+        )?;
+
+        // Fragment shader
+        let fragment_shader = match Self::compile_shader(
+            gl,
+            glow::FRAGMENT_SHADER,
+            &format!("{glsl_version}{COMMON_FRAG}{screen_frag}"),
+        ) {
+            Ok(fragment_shader) => fragment_shader,
+            Err(err) => {
+                gl.delete_shader(vertex_shader);
+                return Err(err);
+            }
+        };
 
-            // Fragment shader
-            let fragment_shader = gl.create_shader(glow::FRAGMENT_SHADER).unwrap();
-            gl.shader_source(
-                fragment_shader,
-                &format!("{glsl_version}{COMMON_FRAG}{screen_frag}"),
-            );
-            gl.compile_shader(fragment_shader);
-            assert!(
-                gl.get_shader_compile_status(fragment_shader),
-                "\n[fragment shader]\n{}",
-                gl.get_shader_info_log(fragment_shader)
-            );
+        // Shader program
+        let shader_program = gl.create_program()?;
+        gl.attach_shader(shader_program, vertex_shader);
+        gl.attach_shader(shader_program, fragment_shader);
+        gl.link_program(shader_program);
+        gl.detach_shader(shader_program, vertex_shader);
+        gl.delete_shader(vertex_shader);
+        gl.detach_shader(shader_program, fragment_shader);
+        gl.delete_shader(fragment_shader);
+
+        if !gl.get_program_link_status(shader_program) {
+            let info_log = gl.get_program_info_log(shader_program);
+            gl.delete_program(shader_program);
+            return Err(info_log);
+        }
 
-            // Shader program
-            let shader_program = gl.create_program().unwrap();
-            gl.attach_shader(shader_program, vertex_shader);
-            gl.attach_shader(shader_program, fragment_shader);
-            gl.link_program(shader_program);
-            assert!(
-                gl.get_program_link_status(shader_program),
-                "{}",
-                gl.get_program_info_log(shader_program)
-            );
-            gl.detach_shader(shader_program, vertex_shader);
-            gl.delete_shader(vertex_shader);
-            gl.detach_shader(shader_program, fragment_shader);
-            gl.delete_shader(fragment_shader);
-
-            // Uniform locations
-            let mut uniform_locations: HashMap<String, glow::UniformLocation> = HashMap::new();
-            let uniform_names = [
-                "u_screenPos",
-                "u_screenSize",
-                "u_screenScale",
-                "u_numColors",
-                "u_backgroundColor",
-                "u_screenTexture",
-                "u_colorsTexture",
-            ];
-
-            for &uniform_name in &uniform_names {
-                if let Some(location) = gl.get_uniform_location(shader_program, uniform_name) {
-                    uniform_locations.insert(uniform_name.to_string(), location);
-                }
+        // Uniform locations
+        let mut uniform_locations: HashMap<String, glow::UniformLocation> = HashMap::new();
+        let uniform_names = [
+            "u_screenPos",
+            "u_screenSize",
+            "u_screenScale",
+            "u_numColors",
+            "u_numColorRows",
+            "u_backgroundColor",
+            "u_screenTexture",
+            "u_colorsTexture",
+            "u_sourceTexture",
+            "u_sourceSize",
+            "u_screenTransparent",
+            "u_rasterTexture",
+            "u_rasterEnabled",
+        ]
+        .map(str::to_string)
+        .into_iter()
+        .chain((0..MAX_LAYERS).flat_map(|i| {
+            [
+                format!("u_layerTexture{i}"),
+                format!("u_layerRect{i}"),
+                format!("u_layerFlags{i}"),
+            ]
+        }));
+
+        for uniform_name in uniform_names {
+            if let Some(location) = gl.get_uniform_location(shader_program, &uniform_name) {
+                uniform_locations.insert(uniform_name, location);
             }
+        }
 
-            // Vertex array
-            let vertices: [f32; 8] = [-1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0, -1.0];
-            let vertex_array = gl.create_vertex_array().unwrap();
-            let vertex_buffer = gl.create_buffer().unwrap();
-
-            gl.bind_vertex_array(Some(vertex_array));
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
-            gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                vertices.align_to::<u8>().1,
-                glow::STATIC_DRAW,
-            );
+        // Vertex array
+        let vertices: [f32; 8] = [-1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0, -1.0];
+        let vertex_array = gl.create_vertex_array()?;
+        let vertex_buffer = gl.create_buffer()?;
+
+        gl.bind_vertex_array(Some(vertex_array));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
+        gl.buffer_data_u8_slice(
+            glow::ARRAY_BUFFER,
+            vertices.align_to::<u8>().1,
+            glow::STATIC_DRAW,
+        );
 
-            let position = gl.get_attrib_location(shader_program, "position").unwrap();
-            gl.vertex_attrib_pointer_f32(
-                position,
-                2,
-                glow::FLOAT,
-                false,
-                2 * size_of::<f32>() as i32,
-                0,
-            );
-            gl.enable_vertex_attrib_array(position);
-
-            // Add screen shader
-            screen_shaders.push(ScreenShader {
-                shader_program,
-                uniform_locations,
-                vertex_array,
-            });
+        let position = gl
+            .get_attrib_location(shader_program, "position")
+            .ok_or("Attribute 'position' is not found")?;
+        gl.vertex_attrib_pointer_f32(
+            position,
+            2,
+            glow::FLOAT,
+            false,
+            2 * size_of::<f32>() as i32,
+            0,
+        );
+        gl.enable_vertex_attrib_array(position);
+
+        Ok(ScreenShader {
+            shader_program,
+            uniform_locations,
+            vertex_array,
+        })
+    }
+
+    unsafe fn update_render_targets(&mut self, gl: &mut glow::Context, width: u32, height: u32) {
+        for render_target in &mut self.render_targets {
+            if render_target.width != width || render_target.height != height {
+                gl.delete_framebuffer(render_target.framebuffer);
+                gl.delete_texture(render_target.texture);
+                *render_target = Self::create_render_target(gl, width, height);
+            }
         }
 
-        screen_shaders
+        while self.render_targets.len() < 2 {
+            self.render_targets
+                .push(Self::create_render_target(gl, width, height));
+        }
     }
 
-    unsafe fn create_screen_texture(gl: &mut glow::Context) -> glow::NativeTexture {
-        let screen_texture = gl.create_texture().unwrap();
-        gl.active_texture(glow::TEXTURE0);
-        gl.bind_texture(glow::TEXTURE_2D, Some(screen_texture));
+    unsafe fn create_render_target(
+        gl: &mut glow::Context,
+        width: u32,
+        height: u32,
+    ) -> RenderTarget {
+        let texture = gl.create_texture().unwrap();
+        gl.active_texture(glow::TEXTURE2);
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::NEAREST as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            glow::NEAREST as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_WRAP_S,
+            glow::CLAMP_TO_EDGE as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_WRAP_T,
+            glow::CLAMP_TO_EDGE as i32,
+        );
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA as i32,
+            width as i32,
+            height as i32,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            PixelUnpackData::Slice(None),
+        );
+
+        let framebuffer = gl.create_framebuffer().unwrap();
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(texture),
+            0,
+        );
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+        RenderTarget {
+            framebuffer,
+            texture,
+            width,
+            height,
+        }
+    }
+
+    unsafe fn create_index_texture(
+        gl: &mut glow::Context,
+        texture_unit: u32,
+    ) -> glow::NativeTexture {
+        let index_texture = gl.create_texture().unwrap();
+        gl.active_texture(texture_unit);
+        gl.bind_texture(glow::TEXTURE_2D, Some(index_texture));
 
         gl.tex_parameter_i32(
             glow::TEXTURE_2D,
@@ -183,7 +339,7 @@ impl Graphics {
             glow::CLAMP_TO_EDGE as i32,
         );
 
-        screen_texture
+        index_texture
     }
 
     unsafe fn create_colors_texture(gl: &mut glow::Context) -> glow::NativeTexture {
@@ -225,6 +381,14 @@ impl Pyxel {
         self.screen.lock().clip0();
     }
 
+    pub fn mask(&self, image: SharedImage, mask_color: Option<Color>, invert: bool) {
+        self.screen.lock().mask(image, mask_color, invert);
+    }
+
+    pub fn mask0(&self) {
+        self.screen.lock().mask0();
+    }
+
     pub fn camera(&self, x: f32, y: f32) {
         self.screen.lock().camera(x, y);
     }
@@ -245,6 +409,10 @@ impl Pyxel {
         self.screen.lock().dither(alpha);
     }
 
+    pub fn blend(&self, mode: BlendMode) {
+        self.screen.lock().blend(mode);
+    }
+
     pub fn cls(&self, color: Color) {
         self.screen.lock().cls(color);
     }
@@ -293,6 +461,45 @@ impl Pyxel {
         self.screen.lock().trib(x1, y1, x2, y2, x3, y3, color);
     }
 
+    pub fn poly(&self, points: &[(f32, f32)], color: Color, fill_rule: FillRule) {
+        self.screen.lock().poly(points, color, fill_rule);
+    }
+
+    pub fn polyb(&self, points: &[(f32, f32)], color: Color) {
+        self.screen.lock().polyb(points, color);
+    }
+
+    pub fn thick_line(
+        &self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        width: f32,
+        color: Color,
+        line_cap: LineCap,
+    ) {
+        self.screen
+            .lock()
+            .thick_line(x1, y1, x2, y2, width, color, line_cap);
+    }
+
+    pub fn arc(&self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, color: Color) {
+        self.screen
+            .lock()
+            .arc(x, y, radius, start_angle, end_angle, color);
+    }
+
+    pub fn pie(&self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, color: Color) {
+        self.screen
+            .lock()
+            .pie(x, y, radius, start_angle, end_angle, color);
+    }
+
+    pub fn bezier(&self, points: &[(f32, f32)], color: Color) {
+        self.screen.lock().bezier(points, color);
+    }
+
     pub fn fill(&self, x: f32, y: f32, color: Color) {
         self.screen.lock().fill(x, y, color);
     }
@@ -351,18 +558,155 @@ impl Pyxel {
         );
     }
 
+    pub fn blt_tri(
+        &self,
+        points: [(f32, f32); 3],
+        image_index: u32,
+        uvs: [(f32, f32); 3],
+        color_key: Option<Color>,
+    ) {
+        self.screen.lock().blt_tri(
+            points,
+            self.images.lock()[image_index as usize].clone(),
+            uvs,
+            color_key,
+        );
+    }
+
+    pub fn blt_perspective(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        image_index: u32,
+        lines: &[Option<ScanlineTransform>],
+        color_key: Option<Color>,
+        wrap: bool,
+    ) {
+        self.screen.lock().blt_perspective(
+            x,
+            y,
+            width,
+            height,
+            self.images.lock()[image_index as usize].clone(),
+            lines,
+            color_key,
+            wrap,
+        );
+    }
+
+    pub fn bltm_perspective(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        tilemap_index: u32,
+        lines: &[Option<ScanlineTransform>],
+        color_key: Option<Color>,
+        wrap: bool,
+    ) {
+        self.screen.lock().bltm_perspective(
+            x,
+            y,
+            width,
+            height,
+            self.tilemaps.lock()[tilemap_index as usize].clone(),
+            lines,
+            color_key,
+            wrap,
+        );
+    }
+
     pub fn text(&self, x: f32, y: f32, string: &str, color: Color, font: Option<SharedFont>) {
         self.screen.lock().text(x, y, string, color, font);
     }
 
+    // Replaces the built-in font used when no font is given, or restores it with None
+    pub fn default_font(&self, font: Option<SharedFont>) {
+        *DEFAULT_FONT.lock() = font;
+    }
+
+    pub fn text_box(
+        &self,
+        x: f32,
+        y: f32,
+        string: &str,
+        color: Color,
+        font: Option<SharedFont>,
+        fonts: &[SharedFont],
+        style: &TextStyle,
+    ) -> TextBounds {
+        self.screen
+            .lock()
+            .text_box(x, y, string, color, font, fonts, style)
+    }
+
+    pub fn add_screen_shader(&mut self, code: &str) -> Result<u32, String> {
+        if let Some(graphics) = &mut self.graphics {
+            graphics.add_screen_shader(code)?;
+        }
+
+        self.system.num_screen_modes += 1;
+        Ok(self.system.num_screen_modes - 1)
+    }
+
+    pub fn screen_effects(&mut self, screen_modes: &[u32]) -> Result<(), String> {
+        if let Some(&screen_mode) = screen_modes
+            .iter()
+            .find(|&&screen_mode| screen_mode >= self.system.num_screen_modes)
+        {
+            return Err(format!("Invalid screen mode {screen_mode}"));
+        }
+
+        self.system.screen_effects = screen_modes.to_vec();
+        Ok(())
+    }
+
     pub(crate) fn render_screen(&mut self) {
+        if self.graphics.is_none() {
+            return;
+        }
+
         unsafe {
             let gl = pyxel_platform::gl_context();
-            self.set_viewport(gl);
-            self.use_screen_shader(gl);
-            self.bind_screen_texture(gl);
-            self.bind_colors_texture(gl);
-            gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+            let screen_effects = &self.system.screen_effects;
+
+            if !screen_effects.is_empty() {
+                let (window_width, window_height) = pyxel_platform::window_size();
+                if let Some(graphics) = &mut self.graphics {
+                    graphics.update_render_targets(gl, window_width, window_height);
+                }
+            }
+
+            let graphics = self.graphics.as_ref().unwrap();
+            let screen_modes = once(self.system.screen_mode).chain(screen_effects.iter().copied());
+            let num_passes = screen_effects.len() + 1;
+            let layers = self.visible_layers();
+            self.bind_screen_texture(gl, graphics);
+            self.bind_layer_textures(gl, graphics, &layers);
+            self.bind_colors_texture(gl, graphics, &layers);
+            self.bind_raster_texture(gl, graphics, &layers);
+
+            for (pass, screen_mode) in screen_modes.enumerate() {
+                let target = if pass + 1 < num_passes {
+                    Some(graphics.render_targets[pass % 2].framebuffer)
+                } else {
+                    None
+                };
+                let source = if pass > 0 {
+                    Some(graphics.render_targets[(pass - 1) % 2].texture)
+                } else {
+                    None
+                };
+
+                gl.bind_framebuffer(glow::FRAMEBUFFER, target);
+                self.set_viewport(gl);
+                self.use_screen_shader(gl, &graphics.screen_shaders[screen_mode as usize], &layers);
+                self.bind_source_texture(gl, source);
+                gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+            }
         }
     }
 
@@ -371,8 +715,12 @@ impl Pyxel {
         gl.viewport(0, 0, window_width as i32, window_height as i32);
     }
 
-    unsafe fn use_screen_shader(&self, gl: &mut glow::Context) {
-        let shader = &self.graphics.screen_shaders[self.system.screen_mode as usize];
+    unsafe fn use_screen_shader(
+        &self,
+        gl: &mut glow::Context,
+        shader: &ScreenShader,
+        layers: &[SharedLayer],
+    ) {
         gl.use_program(Some(shader.shader_program));
         let uniform_locations = &shader.uniform_locations;
 
@@ -401,7 +749,12 @@ impl Pyxel {
         }
 
         if let Some(location) = uniform_locations.get("u_numColors") {
-            gl.uniform_1_i32(Some(location), self.colors.lock().len() as i32);
+            gl.uniform_1_i32(Some(location), self.num_texture_colors(layers) as i32);
+        }
+
+        if let Some(location) = uniform_locations.get("u_numColorRows") {
+            let num_rows = layers.len() + self.raster.line_palettes().count() + 1;
+            gl.uniform_1_i32(Some(location), num_rows as i32);
         }
 
         if let Some(location) = uniform_locations.get("u_backgroundColor") {
@@ -421,12 +774,85 @@ impl Pyxel {
             gl.uniform_1_i32(Some(location), 1);
         }
 
+        if let Some(location) = uniform_locations.get("u_sourceTexture") {
+            gl.uniform_1_i32(Some(location), 2);
+        }
+
+        if let Some(location) = uniform_locations.get("u_sourceSize") {
+            let (window_width, window_height) = pyxel_platform::window_size();
+            gl.uniform_2_f32(Some(location), window_width as f32, window_height as f32);
+        }
+
+        if let Some(location) = uniform_locations.get("u_screenTransparent") {
+            let transparent = self
+                .system
+                .screen_transparent
+                .map_or(-1.0, |color| color as f32);
+            gl.uniform_1_f32(Some(location), transparent);
+        }
+
+        if let Some(location) = uniform_locations.get("u_rasterTexture") {
+            gl.uniform_1_i32(
+                Some(location),
+                (RASTER_TEXTURE_UNIT - glow::TEXTURE0) as i32,
+            );
+        }
+
+        if let Some(location) = uniform_locations.get("u_rasterEnabled") {
+            let enabled = f32::from(u8::from(self.raster.is_active()));
+            gl.uniform_1_f32(Some(location), enabled);
+        }
+
+        for i in 0..MAX_LAYERS as usize {
+            let layer = layers.get(i).map(|layer| layer.lock());
+
+            if let Some(location) = uniform_locations.get(&format!("u_layerTexture{i}")) {
+                gl.uniform_1_i32(Some(location), 3 + i as i32);
+            }
+
+            if let Some(location) = uniform_locations.get(&format!("u_layerRect{i}")) {
+                let (x, y, width, height) = layer.as_ref().map_or((0, 0, 1, 1), |layer| {
+                    let image = layer.image.lock();
+                    (layer.x, layer.y, image.width(), image.height())
+                });
+                gl.uniform_4_f32(
+                    Some(location),
+                    x as f32,
+                    y as f32,
+                    width as f32,
+                    height as f32,
+                );
+            }
+
+            if let Some(location) = uniform_locations.get(&format!("u_layerFlags{i}")) {
+                let (transparent, front, wrap, visible) =
+                    layer.as_ref().map_or((-1.0, 0.0, 0.0, 0.0), |layer| {
+                        (
+                            layer.transparent.map_or(-1.0, |color| color as f32),
+                            f32::from(u8::from(layer.front)),
+                            f32::from(u8::from(layer.wrap)),
+                            1.0,
+                        )
+                    });
+                gl.uniform_4_f32(Some(location), transparent, front, wrap, visible);
+            }
+        }
+
         gl.bind_vertex_array(Some(shader.vertex_array));
     }
 
-    unsafe fn bind_screen_texture(&self, gl: &mut glow::Context) {
+    unsafe fn bind_source_texture(
+        &self,
+        gl: &mut glow::Context,
+        source: Option<glow::NativeTexture>,
+    ) {
+        gl.active_texture(glow::TEXTURE2);
+        gl.bind_texture(glow::TEXTURE_2D, source);
+    }
+
+    unsafe fn bind_screen_texture(&self, gl: &mut glow::Context, graphics: &Graphics) {
         gl.active_texture(glow::TEXTURE0);
-        gl.bind_texture(glow::TEXTURE_2D, Some(self.graphics.screen_texture));
+        gl.bind_texture(glow::TEXTURE_2D, Some(graphics.screen_texture));
         gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
 
         let (internal_format, format) = if pyxel_platform::gl_profile() == GLProfile::Gles {
@@ -448,32 +874,117 @@ impl Pyxel {
         );
     }
 
+    unsafe fn bind_layer_textures(
+        &self,
+        gl: &mut glow::Context,
+        graphics: &Graphics,
+        layers: &[SharedLayer],
+    ) {
+        let (internal_format, format) = if pyxel_platform::gl_profile() == GLProfile::Gles {
+            (glow::LUMINANCE as i32, glow::LUMINANCE)
+        } else {
+            (glow::R8 as i32, glow::RED)
+        };
+
+        for (i, layer) in layers.iter().enumerate() {
+            gl.active_texture(glow::TEXTURE3 + i as u32);
+            gl.bind_texture(glow::TEXTURE_2D, Some(graphics.layer_textures[i]));
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+
+            let layer = layer.lock();
+            let image = layer.image.lock();
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                internal_format,
+                image.width() as i32,
+                image.height() as i32,
+                0,
+                format,
+                glow::UNSIGNED_BYTE,
+                PixelUnpackData::Slice(Some(&image.canvas.data)),
+            );
+        }
+    }
+
+    unsafe fn bind_raster_texture(
+        &self,
+        gl: &mut glow::Context,
+        graphics: &Graphics,
+        layers: &[SharedLayer],
+    ) {
+        gl.active_texture(RASTER_TEXTURE_UNIT);
+        gl.bind_texture(glow::TEXTURE_2D, Some(graphics.raster_texture));
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA as i32,
+            1,
+            self.height as i32,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            PixelUnpackData::Slice(Some(&self.raster.line_params(layers.len() as u32 + 1))),
+        );
+    }
+
+    fn num_texture_colors(&self, layers: &[SharedLayer]) -> usize {
+        layers
+            .iter()
+            .filter_map(|layer| layer.lock().colors.as_ref().map(Vec::len))
+            .chain(self.raster.line_palettes().map(Vec::len))
+            .fold(self.colors.lock().len(), usize::max)
+    }
+
     #[allow(clippy::uninlined_format_args)]
-    unsafe fn bind_colors_texture(&self, gl: &mut glow::Context) {
+    unsafe fn bind_colors_texture(
+        &self,
+        gl: &mut glow::Context,
+        graphics: &Graphics,
+        layers: &[SharedLayer],
+    ) {
         gl.active_texture(glow::TEXTURE1);
-        gl.bind_texture(glow::TEXTURE_2D, Some(self.graphics.colors_texture));
-        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+        gl.bind_texture(glow::TEXTURE_2D, Some(graphics.colors_texture));
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
 
         let colors = self.colors.lock();
-        assert!(
-            !colors.is_empty() && colors.len() <= MAX_COLORS as usize,
-            "Number of colors must be between 1 to {}",
-            MAX_COLORS
-        );
+        let layers: Vec<_> = layers.iter().map(|layer| layer.lock()).collect();
+        let palettes: Vec<Vec<Rgb24>> = once(&*colors)
+            .chain(
+                layers
+                    .iter()
+                    .map(|layer| layer.colors.as_ref().unwrap_or(&colors)),
+            )
+            .chain(self.raster.line_palettes())
+            .map(|palette| self.cycle_colors(palette))
+            .collect();
+        let num_colors = palettes.iter().map(Vec::len).max().unwrap();
+        let num_rows = palettes.len();
+
+        let mut pixels: Vec<u8> = Vec::with_capacity(num_colors * num_rows * 3);
+        for palette in palettes {
+            assert!(
+                !palette.is_empty() && palette.len() <= MAX_COLORS as usize,
+                "Number of colors must be between 1 to {}",
+                MAX_COLORS
+            );
 
-        let mut pixels: Vec<u8> = Vec::with_capacity(colors.len() * 3);
-        for color in &*colors {
-            pixels.push((color >> 16) as u8);
-            pixels.push((color >> 8) as u8);
-            pixels.push(*color as u8);
+            for i in 0..num_colors {
+                let color: Rgb24 = palette.get(i).copied().unwrap_or(0);
+                pixels.push((color >> 16) as u8);
+                pixels.push((color >> 8) as u8);
+                pixels.push(color as u8);
+            }
         }
 
         gl.tex_image_2d(
             glow::TEXTURE_2D,
             0,
             glow::RGB as i32,
-            colors.len() as i32,
-            1,
+            num_colors as i32,
+            num_rows as i32,
             0,
             glow::RGB,
             glow::UNSIGNED_BYTE,
@@ -1,17 +1,24 @@
 use std::array;
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::atomic::Ordering;
 
 use image::imageops;
 
-use crate::canvas::{Canvas, CopyArea, ToIndex};
+use crate::aseprite_parser::{parse_aseprite, Aseprite};
+use crate::blend::{blend_table, BlendMode};
+use crate::canvas::{Canvas, CopyArea, FillRule, LineCap, ToIndex};
+use crate::chunked_tilemap::SharedChunkedTilemap;
 use crate::font::SharedFont;
-use crate::pyxel::{COLORS, FONT_IMAGE, IMAGES};
+use crate::perspective::ScanlineTransform;
+use crate::pyxel::{COLORS, DEFAULT_FONT, FONT_IMAGE, FRAME_COUNT, IMAGES};
+use crate::quantize::{generate_palette, ImportOptions, ImportReport, Quantizer};
 use crate::rect_area::RectArea;
 use crate::settings::{
-    FONT_HEIGHT, FONT_WIDTH, MAX_COLORS, MAX_FONT_CODE, MIN_FONT_CODE, NUM_FONT_ROWS, TILE_SIZE,
+    FONT_HEIGHT, FONT_WIDTH, IMPORT_ALPHA_THRESHOLD, MAX_COLORS, MAX_FONT_CODE, MIN_FONT_CODE,
+    NUM_FONT_ROWS,
 };
-use crate::tilemap::{ImageSource, SharedTilemap};
+use crate::tilemap::{tile_color, ImageSource, SharedTilemap};
 use crate::utils;
 
 pub type Rgb24 = u32;
@@ -27,6 +34,8 @@ impl ToIndex for Color {
 pub struct Image {
     pub(crate) canvas: Canvas<Color>,
     pub(crate) palette: [Color; MAX_COLORS as usize],
+    blend_mode: BlendMode,
+    blend_colors: Vec<Rgb24>,
 }
 
 pub type SharedImage = shared_type!(Image);
@@ -36,68 +45,100 @@ impl Image {
         new_shared_type!(Self {
             canvas: Canvas::new(width, height),
             palette: array::from_fn(|i| i as Color),
+            blend_mode: BlendMode::None,
+            blend_colors: Vec::new(),
         })
     }
 
     pub fn from_image(filename: &str, include_colors: Option<bool>) -> Result<SharedImage, String> {
-        let include_colors = include_colors.unwrap_or(false);
-        let mut colors = COLORS.lock();
-        if include_colors {
-            colors.clear();
-        }
+        let options = ImportOptions {
+            include_colors: include_colors.unwrap_or(false),
+            ..ImportOptions::default()
+        };
+        Self::import(filename, &options).map(|(image, _)| image)
+    }
+
+    pub fn import(
+        filename: &str,
+        options: &ImportOptions,
+    ) -> Result<(SharedImage, ImportReport), String> {
         let file_image = image::open(Path::new(&filename))
             .map_err(|_e| format!("Failed to open file '{filename}'"))?
-            .to_rgb8();
+            .to_rgba8();
         let (width, height) = file_image.dimensions();
-        let image = Self::new(width, height);
 
+        let mut histogram = Vec::new();
+        let mut histogram_indices = HashMap::<Rgb24, usize>::new();
+        let pixels: Vec<Option<Rgb24>> = file_image
+            .pixels()
+            .map(|p| {
+                if options.transparent.is_some() && p[3] < IMPORT_ALPHA_THRESHOLD {
+                    return None;
+                }
+                let rgb = ((p[0] as Rgb24) << 16) | ((p[1] as Rgb24) << 8) | p[2] as Rgb24;
+                let index = *histogram_indices.entry(rgb).or_insert_with(|| {
+                    histogram.push((rgb, 0));
+                    histogram.len() - 1
+                });
+                histogram[index].1 += 1;
+                Some(rgb)
+            })
+            .collect();
+
+        let mut colors = COLORS.lock();
+        let mut transparent = options.transparent;
+        if options.include_colors {
+            // The transparent color counts toward max_colors
+            if transparent.is_some() && options.max_colors < 2 {
+                return Err("max_colors must be at least 2 with a transparent color".to_string());
+            }
+            let max_colors =
+                options.max_colors.clamp(1, MAX_COLORS) - u32::from(transparent.is_some());
+            *colors = generate_palette(&histogram, max_colors, options.method);
+
+            // Reserve the transparent color so that no opaque pixel maps to it
+            if let Some(color) = transparent {
+                let color = (color as usize).min(colors.len());
+                colors.insert(color, 0);
+                transparent = Some(color as Color);
+            }
+        }
+
+        let mut quantizer = Quantizer::new(&colors, transparent, options.perceptual);
+        let mapped_colors = quantizer.map_pixels(&pixels, width, options.dither);
+        let merged_colors = histogram
+            .iter()
+            .filter_map(|&(rgb, _)| {
+                let color = quantizer.nearest_color(rgb);
+                (quantizer.color_rgb(color) != rgb).then_some((rgb, color))
+            })
+            .collect();
+        let report = ImportReport {
+            num_source_colors: histogram.len() as u32,
+            num_colors: colors.len() as u32,
+            merged_colors,
+            num_transparent_pixels: pixels.iter().filter(|p| p.is_none()).count() as u32,
+            transparent,
+        };
+
+        let image = Self::new(width, height);
         {
             let mut image = image.lock();
-            let mut color_table = HashMap::<(u8, u8, u8), Color>::new();
-
-            for y in 0..height {
-                for x in 0..width {
-                    let p = file_image.get_pixel(x, y);
-                    let src_rgb = (p[0], p[1], p[2]);
-
-                    if let Some(color) = color_table.get(&src_rgb) {
-                        image.canvas.write_data(x as usize, y as usize, *color);
-                    } else {
-                        let mut closest_color: Color = 0;
-
-                        if include_colors {
-                            colors.push(
-                                ((src_rgb.0 as Rgb24) << 16)
-                                    | ((src_rgb.1 as Rgb24) << 8)
-                                    | src_rgb.2 as Rgb24,
-                            );
-                            closest_color = colors.len() as Color - 1;
-                        } else {
-                            let mut closest_dist: f32 = f32::MAX;
-                            for (i, pal_color) in colors.iter().enumerate() {
-                                let pal_rgb = (
-                                    (pal_color >> 16) as u8,
-                                    (pal_color >> 8) as u8,
-                                    *pal_color as u8,
-                                );
-                                let dist = Self::color_dist(src_rgb, pal_rgb);
-                                if dist < closest_dist {
-                                    closest_color = i as Color;
-                                    closest_dist = dist;
-                                }
-                            }
-                        }
-
-                        color_table.insert(src_rgb, closest_color);
-                        image
-                            .canvas
-                            .write_data(x as usize, y as usize, closest_color);
-                    }
-                }
+            for (i, color) in mapped_colors.into_iter().enumerate() {
+                let color = color.or(transparent).unwrap_or(0);
+                image.canvas.data[i] = color;
             }
         }
 
-        Ok(image)
+        Ok((image, report))
+    }
+
+    pub fn from_aseprite(
+        filename: &str,
+        layer_index: Option<u32>,
+        include_colors: Option<bool>,
+    ) -> Result<(SharedImage, Aseprite), String> {
+        parse_aseprite(filename, layer_index, include_colors.unwrap_or(false))
     }
 
     pub const fn width(&self) -> u32 {
@@ -171,6 +212,37 @@ impl Image {
         Ok(())
     }
 
+    pub fn load_aseprite(
+        &mut self,
+        x: i32,
+        y: i32,
+        filename: &str,
+        layer_index: Option<u32>,
+        include_colors: Option<bool>,
+    ) -> Result<Aseprite, String> {
+        let (image, mut aseprite) = Self::from_aseprite(filename, layer_index, include_colors)?;
+        let width = image.lock().width();
+        let height = image.lock().height();
+
+        self.blt(
+            x as f32,
+            y as f32,
+            image,
+            0.0,
+            0.0,
+            width as f32,
+            height as f32,
+            Some(aseprite.transparent),
+            None,
+            None,
+        );
+        for frame in &mut aseprite.frames {
+            frame.x += x;
+            frame.y += y;
+        }
+        Ok(aseprite)
+    }
+
     pub fn save(&self, filename: &str, scale: u32) -> Result<(), String> {
         let colors = COLORS.lock();
         let width = self.width();
@@ -200,6 +272,30 @@ impl Image {
         Ok(())
     }
 
+    pub fn to_rgba8(&self, colors: &[Rgb24], scale: u32) -> Vec<u8> {
+        let scale = scale.max(1) as usize;
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+        let mut rgba = Vec::with_capacity(width * height * scale * scale * 4);
+
+        for y in 0..height {
+            let row_start = rgba.len();
+            for x in 0..width {
+                let color = self.canvas.read_data(x, y) as usize;
+                let rgb = colors.get(color).copied().unwrap_or(0);
+                let pixel = [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xff];
+                for _ in 0..scale {
+                    rgba.extend_from_slice(&pixel);
+                }
+            }
+            for _ in 1..scale {
+                rgba.extend_from_within(row_start..row_start + width * scale * 4);
+            }
+        }
+
+        rgba
+    }
+
     pub fn clip(&mut self, x: f32, y: f32, width: f32, height: f32) {
         self.canvas.clip(x, y, width, height);
     }
@@ -208,6 +304,19 @@ impl Image {
         self.canvas.clip0();
     }
 
+    pub fn mask(&mut self, image: SharedImage, mask_color: Option<Color>, invert: bool) {
+        if let Some(image) = image.try_lock() {
+            self.canvas.mask(&image.canvas, mask_color, invert);
+        } else {
+            let canvas = self.canvas.clone();
+            self.canvas.mask(&canvas, mask_color, invert);
+        }
+    }
+
+    pub fn mask0(&mut self) {
+        self.canvas.mask0();
+    }
+
     pub fn camera(&mut self, x: f32, y: f32) {
         self.canvas.camera(x, y);
     }
@@ -230,6 +339,25 @@ impl Image {
         self.canvas.dither(alpha);
     }
 
+    pub fn blend(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+        self.blend_colors.clear();
+        self.canvas.blend_table = None;
+        self.refresh_blend_table();
+    }
+
+    // The blend table is rebuilt whenever the palette it was built from has changed
+    fn refresh_blend_table(&mut self) {
+        if self.blend_mode == BlendMode::None {
+            return;
+        }
+        let colors = COLORS.lock();
+        if *colors != self.blend_colors {
+            self.blend_colors.clone_from(&colors);
+            self.canvas.blend_table = blend_table(self.blend_mode, &colors);
+        }
+    }
+
     pub fn cls(&mut self, color: Color) {
         self.canvas.cls(self.palette[color as usize]);
     }
@@ -239,54 +367,143 @@ impl Image {
     }
 
     pub fn pset(&mut self, x: f32, y: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas.pset(x, y, self.palette[color as usize]);
     }
 
     pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .line(x1, y1, x2, y2, self.palette[color as usize]);
     }
 
     pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .rect(x, y, width, height, self.palette[color as usize]);
     }
 
     pub fn rectb(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .rectb(x, y, width, height, self.palette[color as usize]);
     }
 
     pub fn circ(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas.circ(x, y, radius, self.palette[color as usize]);
     }
 
     pub fn circb(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .circb(x, y, radius, self.palette[color as usize]);
     }
 
     pub fn elli(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .elli(x, y, width, height, self.palette[color as usize]);
     }
 
     pub fn ellib(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .ellib(x, y, width, height, self.palette[color as usize]);
     }
 
     pub fn tri(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .tri(x1, y1, x2, y2, x3, y3, self.palette[color as usize]);
     }
 
     pub fn trib(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas
             .trib(x1, y1, x2, y2, x3, y3, self.palette[color as usize]);
     }
 
+    pub fn poly(&mut self, points: &[(f32, f32)], color: Color, fill_rule: FillRule) {
+        self.refresh_blend_table();
+        self.canvas
+            .poly(points, self.palette[color as usize], fill_rule);
+    }
+
+    pub fn polyb(&mut self, points: &[(f32, f32)], color: Color) {
+        self.refresh_blend_table();
+        self.canvas.polyb(points, self.palette[color as usize]);
+    }
+
+    pub fn thick_line(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        width: f32,
+        color: Color,
+        line_cap: LineCap,
+    ) {
+        self.refresh_blend_table();
+        self.canvas.thick_line(
+            x1,
+            y1,
+            x2,
+            y2,
+            width,
+            self.palette[color as usize],
+            line_cap,
+        );
+    }
+
+    pub fn arc(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        color: Color,
+    ) {
+        self.refresh_blend_table();
+        self.canvas.arc(
+            x,
+            y,
+            radius,
+            start_angle,
+            end_angle,
+            self.palette[color as usize],
+        );
+    }
+
+    pub fn pie(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        color: Color,
+    ) {
+        self.refresh_blend_table();
+        self.canvas.pie(
+            x,
+            y,
+            radius,
+            start_angle,
+            end_angle,
+            self.palette[color as usize],
+        );
+    }
+
+    pub fn bezier(&mut self, points: &[(f32, f32)], color: Color) {
+        self.refresh_blend_table();
+        self.canvas.bezier(points, self.palette[color as usize]);
+    }
+
     pub fn fill(&mut self, x: f32, y: f32, color: Color) {
+        self.refresh_blend_table();
         self.canvas.fill(x, y, self.palette[color as usize]);
     }
 
@@ -303,6 +520,7 @@ impl Image {
         rotate: Option<f32>,
         scale: Option<f32>,
     ) {
+        self.refresh_blend_table();
         let rotate = rotate.unwrap_or(0.0);
         let scale = scale.unwrap_or(1.0);
         if rotate != 0.0 || scale != 1.0 {
@@ -439,6 +657,7 @@ impl Image {
         rotate: Option<f32>,
         scale: Option<f32>,
     ) {
+        self.refresh_blend_table();
         let rotate = rotate.unwrap_or(0.0);
         let scale = scale.unwrap_or(1.0);
         if rotate != 0.0 || scale != 1.0 {
@@ -465,11 +684,13 @@ impl Image {
         let height = utils::f32_to_i32(height);
 
         let tilemap = tilemap.lock();
+        let tile_width = tilemap.tile_width as i32;
+        let tile_height = tilemap.tile_height as i32;
         let tilemap_rect = RectArea::new(
-            tilemap.canvas.self_rect.left() * TILE_SIZE as i32,
-            tilemap.canvas.self_rect.top() * TILE_SIZE as i32,
-            tilemap.canvas.self_rect.width() * TILE_SIZE,
-            tilemap.canvas.self_rect.height() * TILE_SIZE,
+            tilemap.canvas.self_rect.left() * tile_width,
+            tilemap.canvas.self_rect.top() * tile_height,
+            tilemap.canvas.self_rect.width() * tilemap.tile_width,
+            tilemap.canvas.self_rect.height() * tilemap.tile_height,
         );
 
         let CopyArea {
@@ -502,21 +723,30 @@ impl Image {
             ImageSource::Index(index) => images[*index as usize].lock(),
             ImageSource::Image(image) => image.lock(),
         };
+        let tileset = tilemap
+            .tileset
+            .as_ref()
+            .map(|tileset| tileset.lock())
+            .filter(|tileset| tileset.has_animations());
+        let frame_count = FRAME_COUNT.load(Ordering::Relaxed);
 
         for yi in 0..height {
             for xi in 0..width {
                 let tilemap_x = src_x + sign_x * xi + offset_x;
                 let tilemap_y = src_y + sign_y * yi + offset_y;
 
-                let tile_x = tilemap_x / TILE_SIZE as i32;
-                let tile_y = tilemap_y / TILE_SIZE as i32;
-                let tile = tilemap.canvas.read_data(tile_x as usize, tile_y as usize);
+                let tile_x = tilemap_x / tile_width;
+                let tile_y = tilemap_y / tile_height;
+                let mut tile = tilemap.canvas.read_data(tile_x as usize, tile_y as usize);
+                if let Some(tileset) = &tileset {
+                    tile = tileset.animate(tile, frame_count);
+                }
 
-                let value_x = tile.0 as i32 * TILE_SIZE as i32 + tilemap_x % TILE_SIZE as i32;
+                let (value_x, value_y) =
+                    tilemap.tile_pixel(tile, tilemap_x % tile_width, tilemap_y % tile_height);
                 if value_x < 0 || value_x >= image.width() as i32 {
                     continue;
                 }
-                let value_y = tile.1 as i32 * TILE_SIZE as i32 + tilemap_y % TILE_SIZE as i32;
                 if value_y < 0 || value_y >= image.height() as i32 {
                     continue;
                 }
@@ -527,7 +757,7 @@ impl Image {
                         continue;
                     }
                 }
-                let value = self.palette[value.to_index()];
+                let value = self.palette[tile_color(tile, value).to_index()];
                 self.canvas
                     .write_data((dst_x + xi) as usize, (dst_y + yi) as usize, value);
             }
@@ -551,6 +781,8 @@ impl Image {
         let copy_height = utils::f32_to_u32(height.abs());
         let tilemap_width = tilemap.lock().width() as f32;
         let tilemap_height = tilemap.lock().height() as f32;
+        let tile_width = tilemap.lock().tile_width as f32;
+        let tile_height = tilemap.lock().tile_height as f32;
         let image = Self::new(copy_width, copy_height);
 
         {
@@ -570,8 +802,8 @@ impl Image {
             image.clip(
                 -tilemap_x,
                 -tilemap_y,
-                tilemap_width * TILE_SIZE as f32,
-                tilemap_height * TILE_SIZE as f32,
+                tilemap_width * tile_width,
+                tilemap_height * tile_height,
             );
             self.canvas.blt_transform(
                 x,
@@ -590,12 +822,264 @@ impl Image {
         }
     }
 
+    // Each chunk is drawn as its own tilemap, mirrored into place when the copy is flipped
+    pub fn bltm_chunked(
+        &mut self,
+        x: f32,
+        y: f32,
+        tilemap: SharedChunkedTilemap,
+        tilemap_x: f32,
+        tilemap_y: f32,
+        width: f32,
+        height: f32,
+        transparent: Option<Color>,
+        rotate: Option<f32>,
+        scale: Option<f32>,
+    ) {
+        self.refresh_blend_table();
+        let rotate = rotate.unwrap_or(0.0);
+        let scale = scale.unwrap_or(1.0);
+        if rotate != 0.0 || scale != 1.0 {
+            let image = Self::new(
+                utils::f32_to_u32(width.abs()),
+                utils::f32_to_u32(height.abs()),
+            );
+            let mut image = image.lock();
+            image.bltm_chunked(
+                0.0,
+                0.0,
+                tilemap,
+                tilemap_x,
+                tilemap_y,
+                width.abs(),
+                height.abs(),
+                None,
+                None,
+                None,
+            );
+            self.canvas.blt_transform(
+                x,
+                y,
+                &image.canvas,
+                0.0,
+                0.0,
+                width,
+                height,
+                transparent,
+                Some(&self.palette),
+                rotate,
+                scale,
+                true,
+            );
+            return;
+        }
+
+        let tilemap_x = utils::f32_to_i32(tilemap_x);
+        let tilemap_y = utils::f32_to_i32(tilemap_y);
+        let copy_width = utils::f32_to_i32(width.abs());
+        let copy_height = utils::f32_to_i32(height.abs());
+        if copy_width == 0 || copy_height == 0 {
+            return;
+        }
+
+        let mut tilemap = tilemap.lock();
+        let tile_width = tilemap.tile_width as i32;
+        let tile_height = tilemap.tile_height as i32;
+        let chunk_width = tilemap.chunk_size() as i32 * tile_width;
+        let chunk_height = tilemap.chunk_size() as i32 * tile_height;
+        let tile_left = tilemap_x.div_euclid(tile_width);
+        let tile_top = tilemap_y.div_euclid(tile_height);
+        let tile_right = (tilemap_x + copy_width - 1).div_euclid(tile_width);
+        let tile_bottom = (tilemap_y + copy_height - 1).div_euclid(tile_height);
+
+        for coord in tilemap.chunks_in_area(
+            tile_left,
+            tile_top,
+            tile_right - tile_left + 1,
+            tile_bottom - tile_top + 1,
+        ) {
+            let chunk_left = coord.0 * chunk_width;
+            let chunk_top = coord.1 * chunk_height;
+            let left = tilemap_x.max(chunk_left);
+            let top = tilemap_y.max(chunk_top);
+            let right = (tilemap_x + copy_width).min(chunk_left + chunk_width);
+            let bottom = (tilemap_y + copy_height).min(chunk_top + chunk_height);
+            if left >= right || top >= bottom {
+                continue;
+            }
+
+            let offset_x = if width < 0.0 {
+                tilemap_x + copy_width - right
+            } else {
+                left - tilemap_x
+            };
+            let offset_y = if height < 0.0 {
+                tilemap_y + copy_height - bottom
+            } else {
+                top - tilemap_y
+            };
+            let chunk = tilemap.chunk_for_drawing(coord);
+            self.bltm(
+                x + offset_x as f32,
+                y + offset_y as f32,
+                chunk,
+                (left - chunk_left) as f32,
+                (top - chunk_top) as f32,
+                (right - left) as f32 * width.signum(),
+                (bottom - top) as f32 * height.signum(),
+                transparent,
+                None,
+                None,
+            );
+        }
+    }
+
+    pub fn blt_tri(
+        &mut self,
+        points: [(f32, f32); 3],
+        image: SharedImage,
+        uvs: [(f32, f32); 3],
+        transparent: Option<Color>,
+    ) {
+        self.refresh_blend_table();
+        let copied_canvas;
+        let locked_image = image.try_lock();
+        let canvas = if let Some(image) = &locked_image {
+            &image.canvas
+        } else {
+            copied_canvas = self.canvas.clone();
+            &copied_canvas
+        };
+
+        let palette = self.palette;
+        self.canvas.tri_textured(points, uvs, |u, v| {
+            Self::sample_canvas(canvas, u, v, false, transparent, &palette)
+        });
+    }
+
+    pub fn blt_perspective(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        image: SharedImage,
+        lines: &[Option<ScanlineTransform>],
+        transparent: Option<Color>,
+        wrap: bool,
+    ) {
+        self.refresh_blend_table();
+        let copied_canvas;
+        let locked_image = image.try_lock();
+        let canvas = if let Some(image) = &locked_image {
+            &image.canvas
+        } else {
+            copied_canvas = self.canvas.clone();
+            &copied_canvas
+        };
+
+        let palette = self.palette;
+        self.canvas
+            .blt_perspective(x, y, width, height, lines, |u, v| {
+                Self::sample_canvas(canvas, u, v, wrap, transparent, &palette)
+            });
+    }
+
+    pub fn bltm_perspective(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        tilemap: SharedTilemap,
+        lines: &[Option<ScanlineTransform>],
+        transparent: Option<Color>,
+        wrap: bool,
+    ) {
+        self.refresh_blend_table();
+        let tilemap = tilemap.lock();
+        let images = IMAGES.lock();
+        let image = match &tilemap.imgsrc {
+            ImageSource::Index(index) => images[*index as usize].lock(),
+            ImageSource::Image(image) => image.lock(),
+        };
+
+        let tileset = tilemap
+            .tileset
+            .as_ref()
+            .map(|tileset| tileset.lock())
+            .filter(|tileset| tileset.has_animations());
+        let frame_count = FRAME_COUNT.load(Ordering::Relaxed);
+
+        let tile_width = tilemap.tile_width as i32;
+        let tile_height = tilemap.tile_height as i32;
+        let tilemap_width = tilemap.width() as i32 * tile_width;
+        let tilemap_height = tilemap.height() as i32 * tile_height;
+        let palette = self.palette;
+
+        self.canvas
+            .blt_perspective(x, y, width, height, lines, |u, v| {
+                let (u, v) = if wrap {
+                    (u.rem_euclid(tilemap_width), v.rem_euclid(tilemap_height))
+                } else if u < 0 || u >= tilemap_width || v < 0 || v >= tilemap_height {
+                    return None;
+                } else {
+                    (u, v)
+                };
+
+                let mut tile = tilemap
+                    .canvas
+                    .read_data((u / tile_width) as usize, (v / tile_height) as usize);
+                if let Some(tileset) = &tileset {
+                    tile = tileset.animate(tile, frame_count);
+                }
+                let (value_x, value_y) = tilemap.tile_pixel(tile, u % tile_width, v % tile_height);
+                if !image.canvas.self_rect.contains(value_x, value_y) {
+                    return None;
+                }
+                let value = image.canvas.read_data(value_x as usize, value_y as usize);
+                if Some(value) == transparent {
+                    return None;
+                }
+                Some(palette[tile_color(tile, value).to_index()])
+            });
+    }
+
+    fn sample_canvas(
+        canvas: &Canvas<Color>,
+        x: i32,
+        y: i32,
+        wrap: bool,
+        transparent: Option<Color>,
+        palette: &[Color],
+    ) -> Option<Color> {
+        let (x, y) = if wrap {
+            (
+                x.rem_euclid(canvas.width() as i32),
+                y.rem_euclid(canvas.height() as i32),
+            )
+        } else if canvas.self_rect.contains(x, y) {
+            (x, y)
+        } else {
+            return None;
+        };
+
+        let value = canvas.read_data(x as usize, y as usize);
+        if Some(value) == transparent {
+            None
+        } else {
+            Some(palette[value.to_index()])
+        }
+    }
+
     pub fn text(&mut self, x: f32, y: f32, string: &str, color: Color, font: Option<SharedFont>) {
-        if let Some(font) = font {
+        self.refresh_blend_table();
+        if let Some(font) = font.or_else(|| DEFAULT_FONT.lock().clone()) {
             let x = utils::f32_to_i32(x) - self.canvas.camera_x;
             let y = utils::f32_to_i32(y) - self.canvas.camera_y;
             let color = self.palette[color as usize];
-            font.lock().draw(&mut self.canvas, x, y, string, color);
+            font.lock()
+                .draw(&mut self.canvas, &self.palette, x, y, string, color);
             return;
         }
 
@@ -636,14 +1120,80 @@ impl Image {
         }
         self.pal(1, palette1);
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_max_colors() {
+        let path = std::env::temp_dir().join(format!("pyxel_import_{}.png", std::process::id()));
+        image::RgbaImage::from_fn(4, 1, |x, _| match x {
+            0 => image::Rgba([0, 0, 0, 0]),
+            1 => image::Rgba([255, 0, 0, 255]),
+            2 => image::Rgba([250, 0, 0, 255]),
+            _ => image::Rgba([0, 0, 255, 255]),
+        })
+        .save(&path)
+        .unwrap();
+        let filename = path.to_string_lossy();
+        let options = ImportOptions {
+            include_colors: true,
+            transparent: Some(0),
+            ..ImportOptions::default()
+        };
 
-    fn color_dist(rgb1: (u8, u8, u8), rgb2: (u8, u8, u8)) -> f32 {
-        let (r1, g1, b1) = rgb1;
-        let (r2, g2, b2) = rgb2;
-        let dx = (r1 as f32 - r2 as f32) * 0.30;
-        let dy = (g1 as f32 - g2 as f32) * 0.59;
-        let dz = (b1 as f32 - b2 as f32) * 0.11;
+        let result = Image::import(
+            &filename,
+            &ImportOptions {
+                max_colors: 1,
+                ..options.clone()
+            },
+        );
+        assert!(result.is_err());
+
+        let _lock = crate::pyxel::STATICS_TEST_LOCK.lock();
+        let saved_colors = COLORS.lock().clone();
+        let result = Image::import(
+            &filename,
+            &ImportOptions {
+                max_colors: 3,
+                ..options
+            },
+        );
+        *COLORS.lock() = saved_colors;
+        std::fs::remove_file(&path).unwrap();
+
+        let (image, report) = result.unwrap();
+        assert_eq!(report.num_colors, 3);
+        assert_eq!(report.transparent, Some(0));
+        let mut image = image.lock();
+        assert_eq!(image.pget(0.0, 0.0), 0);
+        assert_eq!(image.pget(1.0, 0.0), image.pget(2.0, 0.0));
+        assert_ne!(image.pget(1.0, 0.0), image.pget(3.0, 0.0));
+    }
 
-        dx * dx + dy * dy + dz * dz
+    #[test]
+    fn test_blend_palette_change() {
+        let _lock = crate::pyxel::STATICS_TEST_LOCK.lock();
+        let saved_colors = COLORS.lock().clone();
+        let image = Image::new(1, 1);
+        let mut image = image.lock();
+
+        *COLORS.lock() = vec![0x000000, 0x808080, 0xffffff];
+        image.blend(BlendMode::Add);
+        image.cls(1);
+        image.pset(0.0, 0.0, 1);
+        let before = image.pget(0.0, 0.0);
+
+        *COLORS.lock() = vec![0x000000, 0x202020, 0xffffff];
+        image.cls(1);
+        image.pset(0.0, 0.0, 1);
+        let after = image.pget(0.0, 0.0);
+        *COLORS.lock() = saved_colors;
+
+        assert_eq!(before, 2);
+        assert_eq!(after, 1);
     }
 }
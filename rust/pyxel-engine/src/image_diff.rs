@@ -0,0 +1,184 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::pyxel::Pyxel;
+
+pub struct ImageDiff {
+    pub width: u32,
+    pub height: u32,
+    pub tolerance: u8,
+    pub num_mismatched_pixels: u32,
+    pub max_channel_diff: u8,
+    pub first_mismatch: Option<(u32, u32)>,
+}
+
+impl ImageDiff {
+    pub fn compare(
+        rgba: &[u8],
+        reference_rgba: &[u8],
+        width: u32,
+        height: u32,
+        tolerance: u8,
+    ) -> Result<Self, String> {
+        let data_len = (width * height * 4) as usize;
+        if rgba.len() != data_len || reference_rgba.len() != data_len {
+            return Err(format!(
+                "Image data does not match the size {width}x{height}"
+            ));
+        }
+
+        let mut image_diff = Self {
+            width,
+            height,
+            tolerance,
+            num_mismatched_pixels: 0,
+            max_channel_diff: 0,
+            first_mismatch: None,
+        };
+
+        for (i, (pixel, reference_pixel)) in rgba
+            .chunks_exact(4)
+            .zip(reference_rgba.chunks_exact(4))
+            .enumerate()
+        {
+            let channel_diff = pixel
+                .iter()
+                .zip(reference_pixel)
+                .map(|(value, reference_value)| value.abs_diff(*reference_value))
+                .max()
+                .unwrap();
+            image_diff.max_channel_diff = image_diff.max_channel_diff.max(channel_diff);
+
+            if channel_diff > tolerance {
+                image_diff.num_mismatched_pixels += 1;
+                if image_diff.first_mismatch.is_none() {
+                    image_diff.first_mismatch = Some((i as u32 % width, i as u32 / width));
+                }
+            }
+        }
+
+        Ok(image_diff)
+    }
+
+    pub fn compare_png(
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        filename: &str,
+        tolerance: u8,
+    ) -> Result<Self, String> {
+        let reference_image = image::open(Path::new(filename))
+            .map_err(|_e| format!("Failed to open file '{filename}'"))?
+            .to_rgba8();
+        let (reference_width, reference_height) = reference_image.dimensions();
+        if reference_width != width || reference_height != height {
+            return Err(format!(
+                "Reference image '{filename}' is {reference_width}x{reference_height}, expected {width}x{height}"
+            ));
+        }
+
+        Self::compare(rgba, reference_image.as_raw(), width, height, tolerance)
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.num_mismatched_pixels == 0
+    }
+
+    pub fn mismatch_ratio(&self) -> f32 {
+        self.num_mismatched_pixels as f32 / (self.width * self.height).max(1) as f32
+    }
+}
+
+impl fmt::Display for ImageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_match() {
+            return write!(
+                f,
+                "{}x{} image matches (tolerance {}, max channel diff {})",
+                self.width, self.height, self.tolerance, self.max_channel_diff
+            );
+        }
+
+        write!(
+            f,
+            "{}x{} image has {} mismatched pixels ({:.2}%, tolerance {}, max channel diff {})",
+            self.width,
+            self.height,
+            self.num_mismatched_pixels,
+            self.mismatch_ratio() * 100.0,
+            self.tolerance,
+            self.max_channel_diff
+        )?;
+        if let Some((x, y)) = self.first_mismatch {
+            write!(f, ", first at ({x}, {y})")?;
+        }
+        Ok(())
+    }
+}
+
+impl Pyxel {
+    pub fn screen_rgba(&self, scale: Option<u32>) -> Vec<u8> {
+        if self.visible_layers().is_empty() && !self.raster.is_active() {
+            self.screen
+                .lock()
+                .to_rgba8(&self.colors.lock(), scale.unwrap_or(1))
+        } else {
+            self.composite_rgba(scale.unwrap_or(1))
+        }
+    }
+
+    pub fn compare_screen(
+        &self,
+        filename: &str,
+        scale: Option<u32>,
+        tolerance: Option<u8>,
+    ) -> Result<ImageDiff, String> {
+        let scale = scale.unwrap_or(1).max(1);
+        ImageDiff::compare_png(
+            &self.screen_rgba(Some(scale)),
+            self.width * scale,
+            self.height * scale,
+            filename,
+            tolerance.unwrap_or(0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImageDiff;
+    use crate::image::Image;
+
+    #[test]
+    fn to_rgba8_applies_colors_and_scale() {
+        let image = Image::new(2, 1);
+        image.lock().pset(1.0, 0.0, 1);
+
+        let rgba = image.lock().to_rgba8(&[0x000000, 0x102030], 2);
+        assert_eq!(rgba.len(), 4 * 2 * 4);
+        assert_eq!(&rgba[0..8], &[0, 0, 0, 255, 0, 0, 0, 255]);
+        assert_eq!(&rgba[8..16], &[16, 32, 48, 255, 16, 32, 48, 255]);
+        assert_eq!(&rgba[0..16], &rgba[16..32]);
+    }
+
+    #[test]
+    fn compare_reports_mismatches_over_tolerance() {
+        let rgba = [
+            0, 0, 0, 255, 10, 20, 30, 255, 100, 100, 100, 255, 0, 0, 0, 255,
+        ];
+        let reference_rgba = [
+            0, 0, 0, 255, 12, 20, 30, 255, 100, 140, 100, 255, 0, 0, 0, 255,
+        ];
+
+        let image_diff = ImageDiff::compare(&rgba, &reference_rgba, 2, 2, 2).unwrap();
+        assert!(!image_diff.is_match());
+        assert_eq!(image_diff.num_mismatched_pixels, 1);
+        assert_eq!(image_diff.max_channel_diff, 40);
+        assert_eq!(image_diff.first_mismatch, Some((0, 1)));
+
+        let image_diff = ImageDiff::compare(&rgba, &reference_rgba, 2, 2, 40).unwrap();
+        assert!(image_diff.is_match());
+
+        assert!(ImageDiff::compare(&rgba, &reference_rgba, 3, 2, 0).is_err());
+    }
+}
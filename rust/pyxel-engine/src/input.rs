@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 use crate::key::{
     Key, KeyValue, GAMEPAD_KEY_INDEX_INTERVAL, GAMEPAD_KEY_START_INDEX, MOUSE_KEY_START_INDEX,
     MOUSE_POS_X, MOUSE_POS_Y, MOUSE_WHEEL_X, MOUSE_WHEEL_Y,
@@ -15,6 +17,16 @@ enum KeyState {
     ReleasedAndPressed,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputEvent {
+    KeyPressed { key: Key },
+    KeyReleased { key: Key },
+    KeyValueChanged { key: Key, value: KeyValue },
+    TextInput { text: String },
+    FileDropped { filename: String },
+}
+
 pub struct Input {
     mouse_visible: bool,
     key_states: HashMap<Key, (u32, KeyState)>,
@@ -171,16 +183,24 @@ impl Pyxel {
             .insert(key, (self.frame_count, key_state));
     }
 
-    pub(crate) fn change_key_value(&mut self, key: Key, value: KeyValue) {
-        let mut value = value;
+    pub(crate) fn translate_key_value(&self, key: Key, value: KeyValue) -> KeyValue {
+        match key {
+            MOUSE_POS_X => {
+                ((value - self.system.screen_x) as f32 / self.system.screen_scale) as i32
+            }
+            MOUSE_POS_Y => {
+                ((value - self.system.screen_y) as f32 / self.system.screen_scale) as i32
+            }
+            _ => value,
+        }
+    }
 
+    pub(crate) fn change_key_value(&mut self, key: Key, value: KeyValue) {
         match key {
             MOUSE_POS_X => {
-                value = ((value - self.system.screen_x) as f32 / self.system.screen_scale) as i32;
                 self.mouse_x = value;
             }
             MOUSE_POS_Y => {
-                value = ((value - self.system.screen_y) as f32 / self.system.screen_scale) as i32;
                 self.mouse_y = value;
             }
             MOUSE_WHEEL_Y => {
@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use crate::image::{Color, Image, Rgb24, SharedImage};
+use crate::pyxel::Pyxel;
+use crate::settings::MAX_LAYERS;
+
+#[derive(Clone)]
+pub struct Layer {
+    pub image: SharedImage,
+    pub x: i32,
+    pub y: i32,
+    pub visible: bool,
+    pub front: bool,
+    pub wrap: bool,
+    pub transparent: Option<Color>,
+    pub colors: Option<Vec<Rgb24>>,
+}
+
+pub type SharedLayer = shared_type!(Layer);
+
+impl Layer {
+    pub fn new(image: SharedImage) -> SharedLayer {
+        new_shared_type!(Self {
+            image,
+            x: 0,
+            y: 0,
+            visible: true,
+            front: true,
+            wrap: true,
+            transparent: Some(0),
+            colors: None,
+        })
+    }
+
+    pub fn scroll(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    // Takes the already locked image so that compositing locks each image once per frame
+    pub(crate) fn color_at(&self, image: &Image, screen_x: i32, screen_y: i32) -> Option<Color> {
+        let width = image.width() as i32;
+        let height = image.height() as i32;
+        let mut x = screen_x + self.x;
+        let mut y = screen_y + self.y;
+
+        if self.wrap {
+            x = x.rem_euclid(width);
+            y = y.rem_euclid(height);
+        } else if x < 0 || x >= width || y < 0 || y >= height {
+            return None;
+        }
+
+        let color = image.canvas.read_data(x as usize, y as usize);
+        if Some(color) == self.transparent {
+            None
+        } else {
+            Some(color)
+        }
+    }
+}
+
+impl Pyxel {
+    pub fn screen_transparent(&mut self, transparent: Option<Color>) {
+        self.system.screen_transparent = transparent;
+    }
+
+    // Layers beyond the first MAX_LAYERS are not drawn
+    pub(crate) fn visible_layers(&self) -> Vec<SharedLayer> {
+        self.layers
+            .lock()
+            .iter()
+            .take(MAX_LAYERS as usize)
+            .filter(|layer| layer.lock().visible)
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn composite_rgba(&self, scale: u32) -> Vec<u8> {
+        // Layers are copied so that one listed twice is not locked twice
+        let layers: Vec<Layer> = self
+            .visible_layers()
+            .iter()
+            .map(|layer| layer.lock().clone())
+            .collect();
+        let screen = self.screen.lock();
+
+        // Each image is locked once, and a layer showing the screen reads the locked screen
+        let mut images: Vec<&SharedImage> = Vec::new();
+        let image_indices: Vec<Option<usize>> = layers
+            .iter()
+            .map(|layer| {
+                if Arc::ptr_eq(&layer.image, &self.screen) {
+                    return None;
+                }
+                let index = images
+                    .iter()
+                    .position(|image| Arc::ptr_eq(image, &layer.image))
+                    .unwrap_or_else(|| {
+                        images.push(&layer.image);
+                        images.len() - 1
+                    });
+                Some(index)
+            })
+            .collect();
+        let images: Vec<_> = images.into_iter().map(|image| image.lock()).collect();
+        let layer_images: Vec<&Image> = image_indices
+            .iter()
+            .map(|index| index.map_or(&*screen, |index| &*images[index]))
+            .collect();
+
+        let colors = self.cycle_colors(&self.colors.lock());
+        let layer_colors: Vec<_> = layers
+            .iter()
+            .map(|layer| {
+                layer
+                    .colors
+                    .as_ref()
+                    .map(|colors| self.cycle_colors(colors))
+            })
+            .collect();
+        let scale = scale.max(1) as usize;
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let mut rgba = Vec::with_capacity(width * height * scale * scale * 4);
+
+        for y in 0..height {
+            let row_start = rgba.len();
+            let raster_line = self.raster.line(y as u32);
+            let line_colors = raster_line
+                .colors
+                .as_ref()
+                .map(|colors| self.cycle_colors(colors));
+            let line_colors = line_colors.as_ref().unwrap_or(&colors);
+
+            for x in 0..width {
+                let screen_x = (x as i32 + raster_line.offset).rem_euclid(width as i32) as usize;
+                let screen_color = screen.canvas.read_data(screen_x, y);
+                let mut rgb = line_colors.get(screen_color as usize).copied().unwrap_or(0);
+
+                let is_screen_transparent = Some(screen_color) == self.system.screen_transparent;
+                for front in [false, true] {
+                    if !front && !is_screen_transparent {
+                        continue;
+                    }
+                    for ((layer, image), layer_colors) in
+                        layers.iter().zip(&layer_images).zip(&layer_colors)
+                    {
+                        if layer.front != front {
+                            continue;
+                        }
+                        if let Some(color) = layer.color_at(image, x as i32, y as i32) {
+                            let colors = layer_colors.as_ref().unwrap_or(&colors);
+                            rgb = colors.get(color as usize).copied().unwrap_or(0);
+                        }
+                    }
+                }
+
+                let pixel = [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xff];
+                for _ in 0..scale {
+                    rgba.extend_from_slice(&pixel);
+                }
+            }
+            for _ in 1..scale {
+                rgba.extend_from_within(row_start..row_start + width * scale * 4);
+            }
+        }
+
+        rgba
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_color_at() {
+        let image = Image::new(4, 4);
+        image.lock().pset(1.0, 2.0, 7);
+        let layer = Layer::new(image.clone());
+        let mut layer = layer.lock();
+        let image = image.lock();
+
+        assert_eq!(layer.color_at(&image, 1, 2), Some(7));
+        assert_eq!(layer.color_at(&image, 0, 0), None);
+
+        layer.scroll(-3, 2);
+        assert_eq!(layer.color_at(&image, 4, 0), Some(7));
+        assert_eq!(layer.color_at(&image, 1, 0), None);
+
+        layer.wrap = false;
+        assert_eq!(layer.color_at(&image, 4, 0), Some(7));
+        assert_eq!(layer.color_at(&image, 0, 0), None);
+        assert_eq!(layer.color_at(&image, 8, 0), None);
+
+        layer.transparent = None;
+        assert_eq!(layer.color_at(&image, 3, 0), Some(0));
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn test_composite_rgba() {
+        crate::pyxel::with_test_pyxel(8, 2, |pyxel| {
+            pyxel.screen.lock().cls(1);
+            for i in 0..=MAX_LAYERS {
+                let image = Image::new(8, 2);
+                image.lock().pset(i as f32, 0.0, 2 + i as Color);
+                pyxel.layers.lock().push(Layer::new(image));
+            }
+            let screen_layer = Layer::new(pyxel.screen.clone());
+            screen_layer.lock().scroll(0, 1);
+            pyxel.layers.lock()[0] = screen_layer;
+            pyxel.screen.lock().pset(7.0, 1.0, 9);
+
+            let rgba = pyxel.composite_rgba(1);
+            let colors = pyxel.colors.lock().clone();
+            let rgb_at = |x: usize, y: usize| {
+                let i = (y * 8 + x) * 4;
+                ((rgba[i] as u32) << 16) | ((rgba[i + 1] as u32) << 8) | rgba[i + 2] as u32
+            };
+
+            assert_eq!(rgb_at(7, 0), colors[9]);
+            for i in 1..MAX_LAYERS as usize {
+                assert_eq!(rgb_at(i, 0), colors[2 + i]);
+            }
+            assert_eq!(rgb_at(MAX_LAYERS as usize, 0), colors[1]);
+        });
+    }
+}
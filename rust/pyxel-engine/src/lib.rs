@@ -22,48 +22,96 @@
 
 #[macro_use]
 mod utils;
+mod animation;
+mod aseprite_parser;
 mod audio;
 mod bgm_generator;
+mod blend;
 mod canvas;
 mod channel;
+mod chunked_tilemap;
+mod collision;
 mod font;
 mod graphics;
 mod image;
+mod image_diff;
 mod input;
+mod layer;
 mod math;
 mod mml_command;
 mod mml_parser;
 mod music;
 mod old_mml_parser;
 mod old_resource_data;
+mod pathfinding;
 mod pcm_decoder;
+mod perspective;
 mod profiler;
 mod pyxel;
+mod quantize;
+mod raster;
 mod rect_area;
+mod replay;
 mod resource;
 mod resource_data;
 mod screencast;
 mod settings;
 mod sound;
+mod sprite;
 mod system;
+mod terrain;
+mod text_layout;
+mod text_shaping;
 mod tilemap;
+mod tileset;
 mod tmx_parser;
 mod tone;
 mod voice;
 mod window_watcher;
 
 use pyxel_platform::key;
+#[cfg(feature = "headless")]
+pub use pyxel_platform::{
+    advance_clock, push_events, set_audio_capture, take_captured_audio, Event,
+};
 
+pub use crate::animation::{
+    Animation, AnimationClip, AnimationFrame, AnimationMode, SharedAnimation,
+};
+pub use crate::aseprite_parser::{
+    Aseprite, AsepriteFrame, AsepriteLayer, AsepriteSlice, AsepriteTag, SliceKey, TagDirection,
+};
+pub use crate::blend::BlendMode;
+pub use crate::canvas::{FillRule, LineCap};
 pub use crate::channel::{Channel, ChannelDetune, ChannelGain, SharedChannel};
+pub use crate::chunked_tilemap::{
+    ChunkCoord, ChunkLoader, ChunkSaver, ChunkedTilemap, SharedChunkedTilemap,
+};
+pub use crate::collision::{TileCollision, TileContact};
 pub use crate::font::{Font, SharedFont};
 pub use crate::image::{Color, Image, Rgb24, SharedImage};
+pub use crate::image_diff::ImageDiff;
 pub use crate::key::*;
+pub use crate::layer::{Layer, SharedLayer};
 pub use crate::music::{Music, SharedMusic, SharedSeq};
+pub use crate::pathfinding::{DiagonalRule, TilePos};
+pub use crate::perspective::{Mode7Camera, ScanlineTransform};
 pub use crate::pyxel::{init, reset_statics, Pyxel, RESET_FUNC};
+pub use crate::quantize::{ImportDither, ImportOptions, ImportReport, QuantizeMethod};
 pub use crate::settings::*;
 pub use crate::sound::{
     SharedSound, Sound, SoundEffect, SoundNote, SoundSpeed, SoundTone, SoundVolume,
 };
+pub use crate::sprite::Sprite;
 pub use crate::system::PyxelCallback;
-pub use crate::tilemap::{ImageSource, ImageTileCoord, SharedTilemap, Tile, Tilemap};
+pub use crate::terrain::{SharedTerrain, Terrain, TerrainMask, TerrainMode};
+pub use crate::text_layout::{measure_text, TextAlign, TextBounds, TextStyle};
+pub use crate::tilemap::{
+    ImageSource, ImageTileCoord, SharedTilemap, Tile, TileAttr, TileCoord, Tilemap,
+};
+pub use crate::tileset::{SharedTileset, TileFrame, TileInfo, Tileset};
+pub use crate::tmx_parser::{
+    TmxMap, TmxObject, TmxObjectGroup, TmxProperties, TmxProperty, TmxShape, TmxTileLayer,
+    TmxTileset,
+};
 pub use crate::tone::{SharedTone, Tone, ToneGain, ToneMode, ToneSample};
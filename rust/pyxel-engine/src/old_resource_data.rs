@@ -58,7 +58,7 @@ impl ResourceItem for Tilemap {
     }
 
     fn clear(&mut self) {
-        self.cls((0, 0));
+        self.cls((0, 0, 0));
     }
 
     fn deserialize(&mut self, version: u32, input: &str) {
@@ -70,7 +70,11 @@ impl ResourceItem for Tilemap {
                         self.canvas.write_data(
                             x,
                             y,
-                            ((tile % 32) as ImageTileCoord, (tile / 32) as ImageTileCoord),
+                            (
+                                (tile % 32) as ImageTileCoord,
+                                (tile / 32) as ImageTileCoord,
+                                0,
+                            ),
                         );
                     });
                 } else {
@@ -80,7 +84,7 @@ impl ResourceItem for Tilemap {
                         self.canvas.write_data(
                             x,
                             y,
-                            (tile_x as ImageTileCoord, tile_y as ImageTileCoord),
+                            (tile_x as ImageTileCoord, tile_y as ImageTileCoord, 0),
                         );
                     });
                 }
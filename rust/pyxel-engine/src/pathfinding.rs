@@ -0,0 +1,458 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::f32::consts::SQRT_2;
+
+use crate::tilemap::{Tile, Tilemap};
+
+pub type TilePos = (i32, i32);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiagonalRule {
+    None,
+    NoCorners,
+    All,
+}
+
+impl From<u32> for DiagonalRule {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::NoCorners,
+            2 => Self::All,
+            _ => Self::None,
+        }
+    }
+}
+
+const NEIGHBORS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (-1, 1),
+    (1, -1),
+    (-1, -1),
+];
+
+struct OpenNode {
+    priority: f32,
+    cost: f32,
+    index: usize,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    // Reversed so that the binary heap pops the lowest priority first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+impl Tilemap {
+    pub(crate) fn tile_at(&self, (x, y): TilePos) -> Option<Tile> {
+        if x < 0 || y < 0 || x >= self.width() as i32 || y >= self.height() as i32 {
+            return None;
+        }
+        Some(self.canvas.read_data(x as usize, y as usize))
+    }
+
+    fn pos_index(&self, (x, y): TilePos) -> usize {
+        y as usize * self.width() as usize + x as usize
+    }
+
+    // Yields passable neighbors with the cost of stepping onto them
+    fn path_neighbors<F>(
+        &self,
+        pos: TilePos,
+        diagonal: DiagonalRule,
+        cost: &F,
+    ) -> Vec<(TilePos, f32)>
+    where
+        F: Fn(Tile) -> Option<f32>,
+    {
+        let passable = |pos| self.tile_at(pos).and_then(cost);
+        let num_neighbors = if diagonal == DiagonalRule::None { 4 } else { 8 };
+
+        NEIGHBORS[..num_neighbors]
+            .iter()
+            .filter_map(|&(dx, dy)| {
+                let next = (pos.0 + dx, pos.1 + dy);
+                let step_cost = passable(next)?;
+                if dx == 0 || dy == 0 {
+                    return Some((next, step_cost));
+                }
+                if diagonal == DiagonalRule::NoCorners
+                    && (passable((pos.0 + dx, pos.1)).is_none()
+                        || passable((pos.0, pos.1 + dy)).is_none())
+                {
+                    return None;
+                }
+                Some((next, step_cost * SQRT_2))
+            })
+            .collect()
+    }
+
+    // Tile costs are expected to be at least 1 to keep the distance estimate admissible
+    pub fn find_path<F>(
+        &self,
+        start: TilePos,
+        goal: TilePos,
+        diagonal: DiagonalRule,
+        cost: F,
+    ) -> Option<Vec<TilePos>>
+    where
+        F: Fn(Tile) -> Option<f32>,
+    {
+        self.tile_at(start)?;
+        self.tile_at(goal).and_then(&cost)?;
+
+        let heuristic = |(x, y): TilePos| {
+            let dx = (x - goal.0).abs() as f32;
+            let dy = (y - goal.1).abs() as f32;
+            if diagonal == DiagonalRule::None {
+                dx + dy
+            } else {
+                dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy)
+            }
+        };
+
+        let num_tiles = (self.width() * self.height()) as usize;
+        let mut costs = vec![f32::INFINITY; num_tiles];
+        let mut came_from: Vec<Option<TilePos>> = vec![None; num_tiles];
+        let mut open = BinaryHeap::new();
+
+        costs[self.pos_index(start)] = 0.0;
+        open.push(OpenNode {
+            priority: heuristic(start),
+            cost: 0.0,
+            index: self.pos_index(start),
+        });
+
+        while let Some(OpenNode { cost: g, index, .. }) = open.pop() {
+            let width = self.width() as usize;
+            let pos = ((index % width) as i32, (index / width) as i32);
+            if pos == goal {
+                let mut path = vec![goal];
+                let mut pos = goal;
+                while let Some(prev) = came_from[self.pos_index(pos)] {
+                    path.push(prev);
+                    pos = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            // Entries superseded by a cheaper path carry a higher cost than the recorded one
+            if g > costs[index] {
+                continue;
+            }
+
+            for (next, step_cost) in self.path_neighbors(pos, diagonal, &cost) {
+                let next_index = self.pos_index(next);
+                let next_cost = g + step_cost;
+                if next_cost < costs[next_index] {
+                    costs[next_index] = next_cost;
+                    came_from[next_index] = Some(pos);
+                    open.push(OpenNode {
+                        priority: next_cost + heuristic(next),
+                        cost: next_cost,
+                        index: next_index,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn reachable<F>(
+        &self,
+        start: TilePos,
+        diagonal: DiagonalRule,
+        max_cost: Option<f32>,
+        cost: F,
+    ) -> Vec<TilePos>
+    where
+        F: Fn(Tile) -> Option<f32>,
+    {
+        if self.tile_at(start).is_none() {
+            return Vec::new();
+        }
+
+        let max_cost = max_cost.unwrap_or(f32::INFINITY);
+        let mut costs = vec![f32::INFINITY; (self.width() * self.height()) as usize];
+        let mut queue = VecDeque::from([start]);
+        costs[self.pos_index(start)] = 0.0;
+
+        while let Some(pos) = queue.pop_front() {
+            let pos_cost = costs[self.pos_index(pos)];
+            for (next, step_cost) in self.path_neighbors(pos, diagonal, &cost) {
+                let next_index = self.pos_index(next);
+                let next_cost = pos_cost + step_cost;
+                if next_cost <= max_cost && next_cost < costs[next_index] {
+                    costs[next_index] = next_cost;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let width = self.width() as usize;
+        costs
+            .iter()
+            .enumerate()
+            .filter(|(_, cost)| cost.is_finite())
+            .map(|(index, _)| ((index % width) as i32, (index / width) as i32))
+            .collect()
+    }
+
+    // The end tiles themselves never block the line, so walls can be seen
+    pub fn line_of_sight<F>(&self, from: TilePos, to: TilePos, blocks: F) -> bool
+    where
+        F: Fn(Tile) -> bool,
+    {
+        let dx = (to.0 - from.0).abs();
+        let dy = -(to.1 - from.1).abs();
+        let step_x = if from.0 < to.0 { 1 } else { -1 };
+        let step_y = if from.1 < to.1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        let (mut x, mut y) = from;
+
+        while (x, y) != to {
+            if (x, y) != from && self.tile_at((x, y)).is_some_and(&blocks) {
+                return false;
+            }
+            let error2 = error * 2;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+
+        true
+    }
+
+    pub fn field_of_view<F>(&self, origin: TilePos, radius: u32, blocks: F) -> Vec<TilePos>
+    where
+        F: Fn(Tile) -> bool,
+    {
+        if self.tile_at(origin).is_none() {
+            return Vec::new();
+        }
+
+        let mut visible = vec![false; (self.width() * self.height()) as usize];
+        visible[self.pos_index(origin)] = true;
+
+        for (xx, xy, yx, yy) in [
+            (1, 0, 0, 1),
+            (0, 1, 1, 0),
+            (0, -1, 1, 0),
+            (-1, 0, 0, 1),
+            (-1, 0, 0, -1),
+            (0, -1, -1, 0),
+            (0, 1, -1, 0),
+            (1, 0, 0, -1),
+        ] {
+            self.cast_light(
+                origin,
+                radius as i32,
+                1,
+                1.0,
+                0.0,
+                (xx, xy, yx, yy),
+                &blocks,
+                &mut visible,
+            );
+        }
+
+        let width = self.width() as usize;
+        visible
+            .iter()
+            .enumerate()
+            .filter(|(_, visible)| **visible)
+            .map(|(index, _)| ((index % width) as i32, (index / width) as i32))
+            .collect()
+    }
+
+    // Recursive shadowcasting over one octant, where the transform maps octant space to the map
+    fn cast_light<F>(
+        &self,
+        origin: TilePos,
+        radius: i32,
+        row: i32,
+        mut start_slope: f32,
+        end_slope: f32,
+        transform: (i32, i32, i32, i32),
+        blocks: &F,
+        visible: &mut [bool],
+    ) where
+        F: Fn(Tile) -> bool,
+    {
+        if start_slope < end_slope {
+            return;
+        }
+
+        let (xx, xy, yx, yy) = transform;
+        let mut next_start_slope = start_slope;
+        for distance in row..=radius {
+            let mut is_blocked = false;
+            let dy = -distance;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start_slope < right_slope {
+                    continue;
+                }
+                if end_slope > left_slope {
+                    break;
+                }
+
+                let pos = (origin.0 + dx * xx + dy * xy, origin.1 + dx * yx + dy * yy);
+                let tile = self.tile_at(pos);
+                if tile.is_some() && dx * dx + dy * dy <= radius * radius {
+                    visible[self.pos_index(pos)] = true;
+                }
+
+                let is_wall = tile.is_none_or(blocks);
+                if is_blocked {
+                    if is_wall {
+                        next_start_slope = right_slope;
+                    } else {
+                        is_blocked = false;
+                        start_slope = next_start_slope;
+                    }
+                } else if is_wall && distance < radius {
+                    is_blocked = true;
+                    self.cast_light(
+                        origin,
+                        radius,
+                        distance + 1,
+                        start_slope,
+                        left_slope,
+                        transform,
+                        blocks,
+                        visible,
+                    );
+                    next_start_slope = right_slope;
+                }
+            }
+            if is_blocked {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::ImageSource;
+
+    const WALL: Tile = (1, 0, 0);
+
+    fn cost(tile: Tile) -> Option<f32> {
+        (tile != WALL).then_some(1.0)
+    }
+
+    #[test]
+    fn test_pathfinding() {
+        let tilemap = Tilemap::new(5, 5, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.line(2.0, 0.0, 2.0, 3.0, WALL);
+
+        let path = tilemap
+            .find_path((0, 0), (4, 0), DiagonalRule::None, cost)
+            .unwrap();
+        assert_eq!(path.first(), Some(&(0, 0)));
+        assert_eq!(path.last(), Some(&(4, 0)));
+        assert_eq!(path.len(), 13);
+        assert!(path.contains(&(2, 4)));
+
+        let path = tilemap
+            .find_path((0, 0), (4, 0), DiagonalRule::All, cost)
+            .unwrap();
+        assert_eq!(path.len(), 9);
+        assert!(tilemap
+            .find_path((0, 0), (2, 0), DiagonalRule::All, cost)
+            .is_none());
+
+        tilemap.pset(2.0, 4.0, WALL);
+        assert!(tilemap
+            .find_path((0, 0), (4, 0), DiagonalRule::All, cost)
+            .is_none());
+        assert_eq!(
+            tilemap
+                .reachable((0, 0), DiagonalRule::None, None, cost)
+                .len(),
+            10
+        );
+        assert_eq!(
+            tilemap
+                .reachable((0, 0), DiagonalRule::None, Some(1.0), cost)
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_find_path_matches_reachable() {
+        let tilemap = Tilemap::new(31, 17, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.line(10.0, 0.0, 10.0, 12.0, WALL);
+        tilemap.line(20.0, 4.0, 20.0, 16.0, WALL);
+        tilemap.line(3.0, 8.0, 8.0, 8.0, WALL);
+        tilemap.rect(24.0, 2.0, 3.0, 3.0, WALL);
+
+        for diagonal in [
+            DiagonalRule::None,
+            DiagonalRule::NoCorners,
+            DiagonalRule::All,
+        ] {
+            let reachable = tilemap.reachable((0, 0), diagonal, None, cost);
+            assert!(reachable.len() > 400);
+            for y in 0..17 {
+                for x in 0..31 {
+                    assert_eq!(
+                        tilemap.find_path((0, 0), (x, y), diagonal, cost).is_some(),
+                        reachable.contains(&(x, y)),
+                        "{diagonal:?} ({x}, {y})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_visibility() {
+        let tilemap = Tilemap::new(7, 7, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.pset(3.0, 2.0, WALL);
+        let blocks = |tile| tile == WALL;
+
+        assert!(tilemap.line_of_sight((3, 3), (3, 2), blocks));
+        assert!(!tilemap.line_of_sight((3, 3), (3, 0), blocks));
+        assert!(tilemap.line_of_sight((3, 3), (6, 6), blocks));
+
+        let visible = tilemap.field_of_view((3, 3), 3, blocks);
+        assert!(visible.contains(&(3, 3)));
+        assert!(visible.contains(&(3, 2)));
+        assert!(!visible.contains(&(3, 0)));
+        assert!(visible.contains(&(6, 3)));
+        assert!(visible.contains(&(3, 6)));
+    }
+}
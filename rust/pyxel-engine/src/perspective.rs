@@ -0,0 +1,93 @@
+use crate::settings::DEFAULT_MODE7_FOV;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanlineTransform {
+    pub u: f32,
+    pub v: f32,
+    pub du: f32,
+    pub dv: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Mode7Camera {
+    pub x: f32,
+    pub y: f32,
+    pub height: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub fov: f32,
+}
+
+impl Mode7Camera {
+    pub fn new(x: f32, y: f32, height: f32, pitch: f32, yaw: f32) -> Self {
+        Self {
+            x,
+            y,
+            height,
+            pitch,
+            yaw,
+            fov: DEFAULT_MODE7_FOV,
+        }
+    }
+
+    pub fn scanline_transforms(&self, width: u32, height: u32) -> Vec<Option<ScanlineTransform>> {
+        let (sin_yaw, cos_yaw) = self.yaw.to_radians().sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.to_radians().sin_cos();
+        let focal_length = width as f32 / 2.0 / (self.fov.to_radians() / 2.0).tan();
+
+        let forward = (cos_yaw * cos_pitch, sin_yaw * cos_pitch);
+        let right = (-sin_yaw, cos_yaw);
+        let up = (cos_yaw * sin_pitch, sin_yaw * sin_pitch);
+        let left_x = 0.5 - width as f32 / 2.0;
+
+        (0..height)
+            .map(|row| {
+                let screen_y = row as f32 + 0.5 - height as f32 / 2.0;
+                let denominator = focal_length * sin_pitch + screen_y * cos_pitch;
+                if denominator <= f32::EPSILON {
+                    return None;
+                }
+
+                let distance = self.height / denominator;
+                Some(ScanlineTransform {
+                    u: self.x
+                        + distance
+                            * (focal_length * forward.0 - screen_y * up.0 + left_x * right.0),
+                    v: self.y
+                        + distance
+                            * (focal_length * forward.1 - screen_y * up.1 + left_x * right.1),
+                    du: distance * right.0,
+                    dv: distance * right.1,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanline_transforms() {
+        let mut camera = Mode7Camera::new(100.0, 50.0, 10.0, 90.0, 0.0);
+        camera.fov = 90.0;
+        let lines = camera.scanline_transforms(20, 10);
+        assert_eq!(lines.len(), 10);
+
+        // Looking straight down, every row has the same scale of height / focal length
+        for line in &lines {
+            let line = line.unwrap();
+            assert!(line.du.abs() < 1e-4);
+            assert!((line.dv - 1.0).abs() < 1e-4);
+            assert!((line.v - (50.0 - 9.5)).abs() < 1e-3);
+        }
+
+        // Looking at the horizon, only the rows below the centre hit the ground
+        camera.pitch = 0.0;
+        let lines = camera.scanline_transforms(20, 10);
+        assert!(lines[..5].iter().all(Option::is_none));
+        assert!(lines[5..].iter().all(Option::is_some));
+        assert!(lines[9].unwrap().u < lines[5].unwrap().u);
+    }
+}
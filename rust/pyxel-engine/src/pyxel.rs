@@ -1,15 +1,20 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::LazyLock;
 
 use parking_lot::Mutex;
 
+use crate::animation::SharedAnimation;
 use crate::audio::Audio;
 use crate::channel::{Channel, SharedChannel};
+use crate::font::SharedFont;
 use crate::graphics::Graphics;
 use crate::image::{Color, Image, Rgb24, SharedImage};
 use crate::input::Input;
 use crate::key::Key;
+use crate::layer::SharedLayer;
 use crate::music::{Music, SharedMusic};
+use crate::raster::Raster;
+use crate::replay::Replay;
 use crate::resource::Resource;
 use crate::settings::{
     CURSOR_DATA, CURSOR_HEIGHT, CURSOR_WIDTH, DEFAULT_COLORS, DEFAULT_FPS, DEFAULT_QUIT_KEY,
@@ -19,11 +24,13 @@ use crate::settings::{
     TILEMAP_SIZE,
 };
 use crate::sound::{SharedSound, Sound};
+use crate::sprite::SpriteBatch;
 use crate::system::System;
 use crate::tilemap::{ImageSource, SharedTilemap, Tilemap};
 use crate::tone::{SharedTone, Tone};
 
 static IS_INITIALIZED: AtomicBool = AtomicBool::new(false);
+pub(crate) static FRAME_COUNT: AtomicU32 = AtomicU32::new(0);
 
 type ResetFunc = Option<Box<dyn FnMut() + Send + 'static>>;
 pub static RESET_FUNC: LazyLock<Mutex<ResetFunc>> = LazyLock::new(|| Mutex::new(None));
@@ -32,6 +39,8 @@ pub static COLORS: LazyLock<shared_type!(Vec<Rgb24>)> = LazyLock::new(init_color
 pub static IMAGES: LazyLock<shared_type!(Vec<SharedImage>)> = LazyLock::new(init_images);
 static TILEMAPS: LazyLock<shared_type!(Vec<SharedTilemap>)> = LazyLock::new(init_tilemaps);
 static CURSOR_IMAGE: LazyLock<SharedImage> = LazyLock::new(init_cursor_image);
+pub(crate) static DEFAULT_FONT: LazyLock<shared_type!(Option<SharedFont>)> =
+    LazyLock::new(|| new_shared_type!(None));
 pub static FONT_IMAGE: LazyLock<SharedImage> = LazyLock::new(init_font_image);
 
 pub static CHANNELS: LazyLock<shared_type!(Vec<SharedChannel>)> = LazyLock::new(init_channels);
@@ -57,13 +66,18 @@ pub struct Pyxel {
     pub input_keys: Vec<Key>,
     pub input_text: String,
     pub dropped_files: Vec<String>,
+    pub(crate) replay: Replay,
 
     // Graphics
-    pub(crate) graphics: Graphics,
+    pub(crate) graphics: Option<Graphics>,
+    pub(crate) sprite_batch: SpriteBatch,
+    pub(crate) raster: Raster,
     pub colors: shared_type!(Vec<Rgb24>),
     pub images: shared_type!(Vec<SharedImage>),
     pub tilemaps: shared_type!(Vec<SharedTilemap>),
+    pub animations: shared_type!(Vec<SharedAnimation>),
     pub screen: SharedImage,
+    pub layers: shared_type!(Vec<SharedLayer>),
     pub cursor: SharedImage,
     pub font: SharedImage,
 
@@ -126,13 +140,18 @@ pub fn init(
     let input_keys = Vec::new();
     let input_text = String::new();
     let dropped_files = Vec::new();
+    let replay = Replay::new();
 
     // Graphics
     let graphics = Graphics::new();
+    let sprite_batch = SpriteBatch::new();
+    let raster = Raster::new(height);
     let colors = COLORS.clone();
     let images = IMAGES.clone();
     let tilemaps = TILEMAPS.clone();
+    let animations = new_shared_type!(Vec::new());
     let screen = Image::new(width, height);
+    let layers = new_shared_type!(Vec::new());
     let cursor = CURSOR_IMAGE.clone();
     let font = FONT_IMAGE.clone();
 
@@ -161,13 +180,18 @@ pub fn init(
         input_keys,
         input_text,
         dropped_files,
+        replay,
 
         // Graphics
         graphics,
+        sprite_batch,
+        raster,
         colors,
         images,
         tilemaps,
+        animations,
         screen,
+        layers,
         cursor,
         font,
 
@@ -184,12 +208,14 @@ pub fn init(
 
 pub fn reset_statics() {
     IS_INITIALIZED.store(false, Ordering::Relaxed);
+    FRAME_COUNT.store(0, Ordering::Relaxed);
 
     (*COLORS.lock()).clone_from(&init_colors().lock());
     (*IMAGES.lock()).clone_from(&init_images().lock());
     (*TILEMAPS.lock()).clone_from(&init_tilemaps().lock());
     (*CURSOR_IMAGE.lock()).clone_from(&init_cursor_image().lock());
     (*FONT_IMAGE.lock()).clone_from(&init_font_image().lock());
+    *DEFAULT_FONT.lock() = None;
     (*CHANNELS.lock()).clone_from(&init_channels().lock());
     (*TONES.lock()).clone_from(&init_tones().lock());
     (*SOUNDS.lock()).clone_from(&init_sounds().lock());
@@ -284,3 +310,50 @@ fn init_sounds() -> shared_type!(Vec<SharedSound>) {
 fn init_musics() -> shared_type!(Vec<SharedMusic>) {
     new_shared_type!((0..NUM_MUSICS).map(|_| Music::new()).collect())
 }
+
+// Tests that create a Pyxel or change the shared statics are serialized
+#[cfg(test)]
+pub(crate) static STATICS_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[cfg(all(test, feature = "headless"))]
+pub(crate) fn with_test_pyxel<R>(width: u32, height: u32, f: impl FnOnce(&mut Pyxel) -> R) -> R {
+    struct ResetGuard;
+
+    impl Drop for ResetGuard {
+        fn drop(&mut self) {
+            reset_statics();
+        }
+    }
+
+    let _lock = STATICS_TEST_LOCK.lock();
+    let _guard = ResetGuard;
+    let mut pyxel = init(width, height, None, None, None, None, None, None);
+    f(&mut pyxel)
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use pyxel_platform::Event;
+
+    use super::*;
+    use crate::key::KEY_A;
+
+    #[test]
+    fn test_headless_frame() {
+        with_test_pyxel(32, 16, |pyxel| {
+            pyxel_platform::push_events(vec![Event::KeyPressed { key: KEY_A }]);
+            pyxel.flip();
+            assert_eq!(pyxel.frame_count, 1);
+            assert!(pyxel.btn(KEY_A));
+
+            pyxel_platform::push_events(vec![Event::KeyReleased { key: KEY_A }]);
+            pyxel.flip();
+            assert!(!pyxel.btn(KEY_A));
+
+            pyxel.cls(1);
+            pyxel.pset(3.0, 4.0, 7);
+            assert_eq!(pyxel.pget(3.0, 4.0), 7);
+            assert_eq!(pyxel.pget(0.0, 0.0), 1);
+        });
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+
+use crate::image::{Color, Rgb24};
+use crate::settings::{KMEANS_ITERATIONS, MAX_COLORS, ORDERED_DITHER_SPREAD};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum QuantizeMethod {
+    MedianCut,
+    KMeans,
+}
+
+impl From<u32> for QuantizeMethod {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::KMeans,
+            _ => Self::MedianCut,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportDither {
+    None,
+    FloydSteinberg,
+    Ordered,
+}
+
+impl From<u32> for ImportDither {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::FloydSteinberg,
+            2 => Self::Ordered,
+            _ => Self::None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ImportOptions {
+    pub include_colors: bool,
+    pub max_colors: u32,
+    pub method: QuantizeMethod,
+    pub dither: ImportDither,
+    pub transparent: Option<Color>,
+    pub perceptual: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            include_colors: false,
+            max_colors: MAX_COLORS,
+            method: QuantizeMethod::MedianCut,
+            dither: ImportDither::None,
+            transparent: None,
+            perceptual: false,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ImportReport {
+    pub num_source_colors: u32,
+    pub num_colors: u32,
+    pub merged_colors: Vec<(Rgb24, Color)>,
+    pub num_transparent_pixels: u32,
+    pub transparent: Option<Color>,
+}
+
+pub struct Quantizer {
+    colors: Vec<Rgb24>,
+    labs: Option<Vec<[f32; 3]>>,
+    skipped_color: Option<Color>,
+    nearest_colors: HashMap<Rgb24, Color>,
+}
+
+impl Quantizer {
+    // Colors are matched by weighted RGB distance, or by Lab distance when perceptual is set
+    pub fn new(colors: &[Rgb24], skipped_color: Option<Color>, perceptual: bool) -> Self {
+        Self {
+            colors: colors.to_vec(),
+            labs: perceptual.then(|| colors.iter().map(|&rgb| rgb_to_lab(rgb)).collect()),
+            skipped_color,
+            nearest_colors: HashMap::new(),
+        }
+    }
+
+    pub fn nearest_color(&mut self, rgb: Rgb24) -> Color {
+        if let Some(&color) = self.nearest_colors.get(&rgb) {
+            return color;
+        }
+
+        let lab = self.labs.as_ref().map(|_| rgb_to_lab(rgb));
+        let color = (0..self.colors.len())
+            .filter(|&i| Some(i as Color) != self.skipped_color)
+            .map(|i| {
+                let dist = match (&self.labs, lab) {
+                    (Some(labs), Some(lab)) => lab_dist(lab, labs[i]),
+                    _ => rgb_dist(rgb, self.colors[i]),
+                };
+                (i as Color, dist)
+            })
+            .min_by(|(_, dist1), (_, dist2)| dist1.total_cmp(dist2))
+            .map_or(0, |(color, _)| color);
+        self.nearest_colors.insert(rgb, color);
+        color
+    }
+
+    pub fn color_rgb(&self, color: Color) -> Rgb24 {
+        self.colors.get(color as usize).copied().unwrap_or(0)
+    }
+
+    // Maps RGB pixels to colors, where None marks a transparent pixel
+    pub fn map_pixels(
+        &mut self,
+        pixels: &[Option<Rgb24>],
+        width: u32,
+        dither: ImportDither,
+    ) -> Vec<Option<Color>> {
+        const BAYER_MATRIX: [[f32; 4]; 4] = [
+            [0.0, 8.0, 2.0, 10.0],
+            [12.0, 4.0, 14.0, 6.0],
+            [3.0, 11.0, 1.0, 9.0],
+            [15.0, 7.0, 13.0, 5.0],
+        ];
+
+        let width = width as usize;
+        let mut errors = vec![
+            [0.0; 3];
+            if dither == ImportDither::FloydSteinberg {
+                pixels.len()
+            } else {
+                0
+            }
+        ];
+        let mut colors = Vec::with_capacity(pixels.len());
+
+        for (i, pixel) in pixels.iter().enumerate() {
+            let Some(rgb) = *pixel else {
+                colors.push(None);
+                continue;
+            };
+            let (x, y) = (i % width, i / width);
+
+            let color = match dither {
+                ImportDither::None => self.nearest_color(rgb),
+                ImportDither::Ordered => {
+                    let threshold = (BAYER_MATRIX[y % 4][x % 4] + 0.5) / 16.0 - 0.5;
+                    let offset = threshold * ORDERED_DITHER_SPREAD;
+                    self.nearest_color(offset_rgb(rgb, [offset; 3]))
+                }
+                ImportDither::FloydSteinberg => {
+                    let target = offset_rgb(rgb, errors[i]);
+                    let color = self.nearest_color(target);
+                    let actual = self.color_rgb(color);
+                    let error: [f32; 3] = array_from_channels(|shift| {
+                        ((target >> shift) & 0xff) as f32 - ((actual >> shift) & 0xff) as f32
+                    });
+
+                    let is_last_column = x + 1 == width;
+                    for (dx, dy, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+                        if (dx < 0 && x == 0) || (dx > 0 && is_last_column) {
+                            continue;
+                        }
+                        let index = (i + dy * width).wrapping_add_signed(dx);
+                        if index < pixels.len() && pixels[index].is_some() {
+                            for channel in 0..3 {
+                                errors[index][channel] += error[channel] * weight / 16.0;
+                            }
+                        }
+                    }
+                    color
+                }
+            };
+            colors.push(Some(color));
+        }
+
+        colors
+    }
+}
+
+pub fn generate_palette(
+    histogram: &[(Rgb24, u32)],
+    max_colors: u32,
+    method: QuantizeMethod,
+) -> Vec<Rgb24> {
+    let max_colors = max_colors as usize;
+    if histogram.len() <= max_colors {
+        return histogram.iter().map(|&(rgb, _)| rgb).collect();
+    }
+
+    let palette = median_cut(histogram, max_colors);
+    match method {
+        QuantizeMethod::MedianCut => palette,
+        QuantizeMethod::KMeans => k_means(histogram, palette),
+    }
+}
+
+fn median_cut(histogram: &[(Rgb24, u32)], max_colors: usize) -> Vec<Rgb24> {
+    let mut boxes = vec![histogram.to_vec()];
+
+    while boxes.len() < max_colors {
+        let widest_box = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| {
+                let (shift, range) = widest_channel(colors);
+                (i, shift, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((index, shift, _)) = widest_box else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|&(rgb, _)| (rgb >> shift) & 0xff);
+        let total: u64 = colors.iter().map(|&(_, count)| count as u64).sum();
+        let mut accumulated = 0;
+        let split = colors
+            .iter()
+            .position(|&(_, count)| {
+                accumulated += count as u64;
+                accumulated * 2 >= total
+            })
+            .map_or(1, |i| i + 1)
+            .clamp(1, colors.len() - 1);
+
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|colors| mean_rgb(colors)).collect()
+}
+
+fn k_means(histogram: &[(Rgb24, u32)], mut palette: Vec<Rgb24>) -> Vec<Rgb24> {
+    let labs: Vec<[f32; 3]> = histogram.iter().map(|&(rgb, _)| rgb_to_lab(rgb)).collect();
+
+    for _ in 0..KMEANS_ITERATIONS {
+        let palette_labs: Vec<[f32; 3]> = palette.iter().map(|&rgb| rgb_to_lab(rgb)).collect();
+        let mut clusters = vec![Vec::new(); palette.len()];
+
+        for (&entry, lab) in histogram.iter().zip(&labs) {
+            let nearest = palette_labs
+                .iter()
+                .enumerate()
+                .min_by(|(_, lab1), (_, lab2)| {
+                    lab_dist(*lab, **lab1).total_cmp(&lab_dist(*lab, **lab2))
+                })
+                .map_or(0, |(i, _)| i);
+            clusters[nearest].push(entry);
+        }
+
+        let next_palette: Vec<Rgb24> = clusters
+            .iter()
+            .zip(&palette)
+            .map(|(colors, &rgb)| {
+                if colors.is_empty() {
+                    rgb
+                } else {
+                    mean_rgb(colors)
+                }
+            })
+            .collect();
+        if next_palette == palette {
+            break;
+        }
+        palette = next_palette;
+    }
+
+    palette
+}
+
+fn widest_channel(colors: &[(Rgb24, u32)]) -> (u32, u32) {
+    [16, 8, 0]
+        .into_iter()
+        .map(|shift| {
+            let values = colors.iter().map(|&(rgb, _)| (rgb >> shift) & 0xff);
+            let min = values.clone().min().unwrap_or(0);
+            let max = values.max().unwrap_or(0);
+            (shift, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap()
+}
+
+fn mean_rgb(colors: &[(Rgb24, u32)]) -> Rgb24 {
+    let total: u64 = colors.iter().map(|&(_, count)| count as u64).sum();
+    [16, 8, 0].into_iter().fold(0, |mean, shift| {
+        let sum: u64 = colors
+            .iter()
+            .map(|&(rgb, count)| ((rgb >> shift) & 0xff) as u64 * count as u64)
+            .sum();
+        mean | ((((sum + total / 2) / total.max(1)) as Rgb24) << shift)
+    })
+}
+
+fn array_from_channels(mut f: impl FnMut(u32) -> f32) -> [f32; 3] {
+    [f(16), f(8), f(0)]
+}
+
+fn offset_rgb(rgb: Rgb24, offset: [f32; 3]) -> Rgb24 {
+    [16, 8, 0]
+        .into_iter()
+        .zip(offset)
+        .fold(0, |result, (shift, offset)| {
+            let value = (((rgb >> shift) & 0xff) as f32 + offset)
+                .round()
+                .clamp(0.0, 255.0);
+            result | ((value as Rgb24) << shift)
+        })
+}
+
+pub fn rgb_to_lab(rgb: Rgb24) -> [f32; 3] {
+    let [red, green, blue] = array_from_channels(|shift| {
+        let value = ((rgb >> shift) & 0xff) as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    });
+
+    // Normalized by the D65 white point
+    let x = (0.4124 * red + 0.3576 * green + 0.1805 * blue) / 0.95047;
+    let y = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    let z = (0.0193 * red + 0.1192 * green + 0.9505 * blue) / 1.08883;
+
+    let pivot = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (pivot(x), pivot(y), pivot(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn rgb_dist(rgb1: Rgb24, rgb2: Rgb24) -> f32 {
+    [(16, 0.30), (8, 0.59), (0, 0.11)]
+        .into_iter()
+        .map(|(shift, weight)| {
+            let diff = (((rgb1 >> shift) & 0xff) as f32 - ((rgb2 >> shift) & 0xff) as f32) * weight;
+            diff * diff
+        })
+        .sum()
+}
+
+fn lab_dist(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    lab1.iter()
+        .zip(lab2)
+        .map(|(value1, value2)| (value1 - value2) * (value1 - value2))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_palette() {
+        let histogram = [
+            (0x000000, 10),
+            (0x010101, 1),
+            (0xff0000, 5),
+            (0xfe0000, 5),
+            (0x0000ff, 3),
+        ];
+
+        let palette = generate_palette(&histogram, 8, QuantizeMethod::MedianCut);
+        assert_eq!(
+            palette,
+            vec![0x000000, 0x010101, 0xff0000, 0xfe0000, 0x0000ff]
+        );
+
+        let histogram = [
+            (0x000000, 10),
+            (0x020202, 10),
+            (0xfafafa, 10),
+            (0xffffff, 10),
+        ];
+        for method in [QuantizeMethod::MedianCut, QuantizeMethod::KMeans] {
+            let mut palette = generate_palette(&histogram, 2, method);
+            palette.sort_unstable();
+            assert_eq!(palette, vec![0x010101, 0xfdfdfd]);
+        }
+    }
+
+    #[test]
+    fn test_map_pixels() {
+        let mut quantizer = Quantizer::new(&[0x000000, 0xffffff, 0x808080], Some(2), false);
+        let pixels = [Some(0x909090); 4];
+
+        let colors = quantizer.map_pixels(&pixels, 2, ImportDither::None);
+        assert!(colors.iter().all(|&color| color == Some(1)));
+
+        // Error diffusion alternates between black and white for a light grey
+        let colors = quantizer.map_pixels(&pixels, 4, ImportDither::FloydSteinberg);
+        assert!(colors.contains(&Some(0)) && colors.contains(&Some(1)));
+
+        let colors = quantizer.map_pixels(&[None, Some(0)], 2, ImportDither::Ordered);
+        assert_eq!(colors, vec![None, Some(0)]);
+    }
+
+    #[test]
+    fn test_nearest_color() {
+        let colors = [0x000000, 0x7e2072];
+        assert_eq!(
+            Quantizer::new(&colors, None, false).nearest_color(0x0000ff),
+            0
+        );
+        assert_eq!(
+            Quantizer::new(&colors, None, true).nearest_color(0x0000ff),
+            1
+        );
+        assert_eq!(
+            Quantizer::new(&colors, Some(0), false).nearest_color(0x000000),
+            1
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::image::{Color, Rgb24};
+use crate::pyxel::Pyxel;
+
+#[derive(Clone, Default)]
+pub struct RasterLine {
+    pub offset: i32,
+    pub colors: Option<Vec<Rgb24>>,
+}
+
+#[derive(Clone)]
+pub struct PaletteCycle {
+    pub start: Color,
+    pub end: Color,
+    pub speed: f32,
+}
+
+pub struct Raster {
+    lines: Vec<RasterLine>,
+    cycles: Vec<PaletteCycle>,
+}
+
+impl Raster {
+    pub fn new(height: u32) -> Self {
+        Self {
+            lines: vec![RasterLine::default(); height as usize],
+            cycles: Vec::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.cycles.is_empty()
+            || self
+                .lines
+                .iter()
+                .any(|line| line.offset != 0 || line.colors.is_some())
+    }
+
+    pub fn line(&self, y: u32) -> &RasterLine {
+        &self.lines[y as usize]
+    }
+
+    pub fn line_palettes(&self) -> impl Iterator<Item = &Vec<Rgb24>> {
+        self.lines.iter().filter_map(|line| line.colors.as_ref())
+    }
+
+    // RGBA per line: (offset low, offset high, palette row low, palette row high)
+    pub fn line_params(&self, first_palette_row: u32) -> Vec<u8> {
+        let mut next_row = first_palette_row;
+        let mut params = Vec::with_capacity(self.lines.len() * 4);
+
+        for line in &self.lines {
+            let offset = (line.offset.clamp(-32768, 32767) + 32768) as u16;
+            let row = if line.colors.is_some() {
+                next_row += 1;
+                next_row - 1
+            } else {
+                0
+            } as u16;
+            params.extend_from_slice(&offset.to_le_bytes());
+            params.extend_from_slice(&row.to_le_bytes());
+        }
+
+        params
+    }
+
+    pub fn cycle_colors(&self, colors: &[Rgb24], time_sec: f32) -> Vec<Rgb24> {
+        let mut cycled_colors = colors.to_vec();
+
+        for cycle in &self.cycles {
+            let start = cycle.start as usize;
+            let end = (cycle.end as usize).min(colors.len().saturating_sub(1));
+            if start >= end {
+                continue;
+            }
+
+            let num_colors = (end - start + 1) as i64;
+            let shift = ((time_sec * cycle.speed).floor() as i64).rem_euclid(num_colors) as usize;
+            let source = cycled_colors[start..=end].to_vec();
+            for (i, color) in cycled_colors[start..=end].iter_mut().enumerate() {
+                *color = source[(i + shift) % num_colors as usize];
+            }
+        }
+
+        cycled_colors
+    }
+}
+
+impl Pyxel {
+    pub fn raster(&mut self, y: u32, colors: Option<Vec<Rgb24>>, offset: i32) {
+        if let Some(line) = self.raster.lines.get_mut(y as usize) {
+            *line = RasterLine { offset, colors };
+        }
+    }
+
+    pub fn raster0(&mut self) {
+        self.raster.lines.fill(RasterLine::default());
+    }
+
+    pub fn pal_cycle(&mut self, start: Color, end: Color, speed: f32) {
+        self.raster.cycles.push(PaletteCycle { start, end, speed });
+    }
+
+    pub fn pal_cycle0(&mut self) {
+        self.raster.cycles.clear();
+    }
+
+    pub(crate) fn cycle_colors(&self, colors: &[Rgb24]) -> Vec<Rgb24> {
+        let time_sec = self.frame_count as f32 / self.system.fps as f32;
+        self.raster.cycle_colors(colors, time_sec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycle_colors() {
+        let mut raster = Raster::new(1);
+        raster.cycles.push(PaletteCycle {
+            start: 1,
+            end: 3,
+            speed: 2.0,
+        });
+        let colors = [10, 11, 12, 13, 14];
+
+        assert_eq!(raster.cycle_colors(&colors, 0.0), vec![10, 11, 12, 13, 14]);
+        assert_eq!(raster.cycle_colors(&colors, 0.5), vec![10, 12, 13, 11, 14]);
+        assert_eq!(raster.cycle_colors(&colors, 1.0), vec![10, 13, 11, 12, 14]);
+
+        raster.cycles[0].speed = -2.0;
+        assert_eq!(raster.cycle_colors(&colors, 0.5), vec![10, 13, 11, 12, 14]);
+    }
+
+    #[test]
+    fn test_line_params() {
+        let mut raster = Raster::new(3);
+        raster.lines[0].offset = -1;
+        raster.lines[1].colors = Some(vec![0]);
+        raster.lines[2].colors = Some(vec![0]);
+
+        assert_eq!(
+            raster.line_params(5),
+            vec![0xff, 0x7f, 0, 0, 0, 0x80, 5, 0, 0, 0x80, 6, 0]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::fs::{read_to_string, write};
+
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+
+use crate::input::InputEvent;
+use crate::pyxel::Pyxel;
+use crate::settings::{REPLAY_FILE_EXTENSION, REPLAY_FORMAT_VERSION};
+use crate::utils;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ReplayFrame {
+    frame: u32,
+    events: Vec<InputEvent>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ReplayData {
+    format_version: u32,
+    width: u32,
+    height: u32,
+    fps: u32,
+    rng_seed: u32,
+    noise_seed: u32,
+    num_frames: u32,
+    frames: Vec<ReplayFrame>,
+}
+
+#[derive(PartialEq)]
+enum ReplayMode {
+    Idle,
+    Recording,
+    Playing,
+}
+
+pub struct Replay {
+    mode: ReplayMode,
+    data: Option<ReplayData>,
+    start_frame: u32,
+    next_frame_index: usize,
+    was_deterministic: bool,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self {
+            mode: ReplayMode::Idle,
+            data: None,
+            start_frame: 0,
+            next_frame_index: 0,
+            was_deterministic: false,
+        }
+    }
+}
+
+impl Pyxel {
+    pub fn start_recording(&mut self, seed: Option<u32>) {
+        let rng_seed = seed.unwrap_or_else(|| rand::rng().random());
+        let noise_seed = seed.unwrap_or_else(|| rand::rng().random());
+        Self::rseed(rng_seed);
+        Self::nseed(noise_seed);
+        self.enter_replay_mode(ReplayMode::Recording);
+
+        self.replay.start_frame = self.frame_count;
+        self.replay.data = Some(ReplayData {
+            format_version: REPLAY_FORMAT_VERSION,
+            width: self.width,
+            height: self.height,
+            fps: self.system.fps,
+            rng_seed,
+            noise_seed,
+            num_frames: 0,
+            frames: Vec::new(),
+        });
+    }
+
+    pub fn stop_recording(&mut self) {
+        if self.replay.mode == ReplayMode::Recording {
+            self.leave_replay_mode();
+        }
+    }
+
+    pub fn save_replay(&self, filename: &str) -> Result<(), String> {
+        let Some(replay_data) = &self.replay.data else {
+            return Err("No replay has been recorded".to_string());
+        };
+
+        let filename = utils::add_file_extension(filename, REPLAY_FILE_EXTENSION);
+        let toml_text = toml::to_string(replay_data).unwrap();
+        write(&filename, toml_text).map_err(|_e| format!("Failed to open file '{filename}'"))?;
+
+        pyxel_platform::export_browser_file(&filename);
+        Ok(())
+    }
+
+    pub fn play_replay(&mut self, filename: &str) -> Result<(), String> {
+        let filename = utils::add_file_extension(filename, REPLAY_FILE_EXTENSION);
+        let toml_text =
+            read_to_string(&filename).map_err(|_e| format!("Failed to open file '{filename}'"))?;
+        let replay_data: ReplayData = toml::from_str(&toml_text)
+            .map_err(|_e| format!("Failed to parse replay file '{filename}'"))?;
+
+        if replay_data.format_version > REPLAY_FORMAT_VERSION {
+            return Err(format!(
+                "Unknown replay file version '{}'",
+                replay_data.format_version
+            ));
+        }
+        if replay_data.width != self.width
+            || replay_data.height != self.height
+            || replay_data.fps != self.system.fps
+        {
+            return Err(format!(
+                "Replay file '{filename}' was recorded with a different screen size or fps"
+            ));
+        }
+
+        Self::rseed(replay_data.rng_seed);
+        Self::nseed(replay_data.noise_seed);
+        self.enter_replay_mode(ReplayMode::Playing);
+
+        self.replay.start_frame = self.frame_count;
+        self.replay.next_frame_index = 0;
+        self.replay.data = Some(replay_data);
+        Ok(())
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.mode == ReplayMode::Playing
+    }
+
+    // The deterministic mode in effect before recording or playback is restored afterwards
+    fn enter_replay_mode(&mut self, mode: ReplayMode) {
+        if self.replay.mode == ReplayMode::Idle {
+            self.replay.was_deterministic = self.is_deterministic();
+        }
+        self.deterministic(true);
+        self.replay.mode = mode;
+    }
+
+    fn leave_replay_mode(&mut self) {
+        self.deterministic(self.replay.was_deterministic);
+        self.replay.mode = ReplayMode::Idle;
+    }
+
+    pub(crate) fn replay_input_events(&mut self, events: Vec<InputEvent>) -> Vec<InputEvent> {
+        let frame = self.frame_count - self.replay.start_frame;
+
+        match self.replay.mode {
+            ReplayMode::Idle => events,
+
+            ReplayMode::Recording => {
+                let replay_data = self.replay.data.as_mut().unwrap();
+                if !events.is_empty() {
+                    replay_data.frames.push(ReplayFrame {
+                        frame,
+                        events: events.clone(),
+                    });
+                }
+                replay_data.num_frames = frame + 1;
+                events
+            }
+
+            ReplayMode::Playing => {
+                let replay_data = self.replay.data.as_ref().unwrap();
+                if frame >= replay_data.num_frames {
+                    self.leave_replay_mode();
+                    return events;
+                }
+
+                match replay_data.frames.get(self.replay.next_frame_index) {
+                    Some(replay_frame) if replay_frame.frame == frame => {
+                        self.replay.next_frame_index += 1;
+                        replay_frame.events.clone()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use std::fs::remove_file;
+
+    use pyxel_platform::Event;
+
+    use crate::key::KEY_A;
+    use crate::pyxel::with_test_pyxel;
+
+    #[test]
+    fn test_record_and_play_replay() {
+        with_test_pyxel(16, 16, |pyxel| {
+            let script = [Some(true), None, Some(false), None];
+
+            pyxel.start_recording(Some(1));
+            let mut recorded = Vec::new();
+            for &pressed in &script {
+                pyxel_platform::push_events(match pressed {
+                    Some(true) => vec![Event::KeyPressed { key: KEY_A }],
+                    Some(false) => vec![Event::KeyReleased { key: KEY_A }],
+                    None => Vec::new(),
+                });
+                pyxel.flip();
+                recorded.push(pyxel.btn(KEY_A));
+            }
+            pyxel.stop_recording();
+            assert_eq!(recorded, [true, true, false, false]);
+            assert!(!pyxel.is_deterministic());
+
+            let filename = std::env::temp_dir()
+                .join(format!("pyxel_replay_{}", std::process::id()))
+                .to_string_lossy()
+                .into_owned();
+            pyxel.save_replay(&filename).unwrap();
+            pyxel.play_replay(&filename).unwrap();
+            remove_file(filename + ".pyxrep").unwrap();
+            assert!(pyxel.is_deterministic());
+
+            let mut replayed = Vec::new();
+            for _ in &script {
+                pyxel.flip();
+                replayed.push(pyxel.btn(KEY_A));
+            }
+            assert_eq!(replayed, recorded);
+
+            while pyxel.is_replaying() {
+                pyxel.flip();
+            }
+            assert!(!pyxel.is_deterministic());
+        });
+    }
+}
@@ -266,3 +266,69 @@ impl Pyxel {
             .collect()
     }
 }
+
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use crate::animation::{Animation, AnimationFrame, AnimationMode};
+    use crate::pyxel::with_test_pyxel;
+
+    fn frame(image_x: f32, duration: u32, events: &[&str]) -> AnimationFrame {
+        AnimationFrame {
+            image: 1,
+            image_x,
+            image_y: 16.0,
+            width: 8.0,
+            height: -8.0,
+            duration,
+            events: events.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_animations() {
+        with_test_pyxel(16, 16, |pyxel| {
+            let animation = Animation::new();
+            {
+                let mut animation = animation.lock();
+                animation.add_frame("walk", frame(0.0, 4, &["step", "dust"]));
+                animation.add_frame("walk", frame(8.0, 6, &[]));
+                animation.add_frame("idle", frame(16.0, 10, &[]));
+                animation.set_mode("walk", AnimationMode::PingPong).unwrap();
+                animation.set_mode("idle", AnimationMode::OneShot).unwrap();
+            }
+            pyxel.animations.lock().push(animation);
+
+            let filename = std::env::temp_dir()
+                .join(format!("pyxel_animations_{}.pyxres", std::process::id()))
+                .to_string_lossy()
+                .into_owned();
+            pyxel.save(&filename, None, None, None, None).unwrap();
+            pyxel.animations.lock().clear();
+            pyxel.load(&filename, Some(true), None, None, None).unwrap();
+            assert!(pyxel.animations.lock().is_empty());
+            pyxel.load(&filename, None, None, None, None).unwrap();
+            std::fs::remove_file(&filename).unwrap();
+
+            let animations = pyxel.animations.lock();
+            assert_eq!(animations.len(), 1);
+            let animation = animations[0].lock();
+            assert_eq!(animation.clips.keys().collect::<Vec<_>>(), ["walk", "idle"]);
+
+            let walk = &animation.clips["walk"];
+            assert_eq!(walk.mode, AnimationMode::PingPong);
+            assert_eq!(walk.frames.len(), 2);
+            let first = &walk.frames[0];
+            assert_eq!(first.image, 1);
+            assert_eq!((first.image_x, first.image_y), (0.0, 16.0));
+            assert_eq!((first.width, first.height), (8.0, -8.0));
+            assert_eq!(first.duration, 4);
+            assert_eq!(first.events, ["step", "dust"]);
+            assert_eq!(walk.frames[1].image_x, 8.0);
+            assert!(walk.frames[1].events.is_empty());
+
+            let idle = &animation.clips["idle"];
+            assert_eq!(idle.mode, AnimationMode::OneShot);
+            assert_eq!(idle.frames[0].duration, 10);
+        });
+    }
+}
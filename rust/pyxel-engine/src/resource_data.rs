@@ -1,12 +1,14 @@
 use serde::{Deserialize, Serialize};
 
+use crate::animation::{Animation, AnimationFrame, SharedAnimation};
 use crate::image::{Color, Image, SharedImage};
 use crate::music::{Music, SharedMusic};
 use crate::pyxel::Pyxel;
+use crate::settings::TILE_SIZE;
 use crate::sound::{
     SharedSound, Sound, SoundEffect, SoundNote, SoundSpeed, SoundTone, SoundVolume,
 };
-use crate::tilemap::{ImageSource, ImageTileCoord, SharedTilemap, Tilemap};
+use crate::tilemap::{ImageSource, ImageTileCoord, SharedTilemap, TileAttr, Tilemap};
 use crate::utils::{compress_vec2, expand_vec2, trim_empty_vecs};
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -51,15 +53,36 @@ impl ImageData {
 }
 
 #[derive(Clone, Serialize, Deserialize)]
-struct TilemapData {
+pub(crate) struct TilemapData {
     width: u32,
     height: u32,
     imgsrc: u32,
     data: Vec<Vec<ImageTileCoord>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attrs: Vec<Vec<TileAttr>>,
+    #[serde(
+        default = "default_tile_size",
+        skip_serializing_if = "is_default_tile_size"
+    )]
+    tile_width: u32,
+    #[serde(
+        default = "default_tile_size",
+        skip_serializing_if = "is_default_tile_size"
+    )]
+    tile_height: u32,
+}
+
+const fn default_tile_size() -> u32 {
+    TILE_SIZE
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_default_tile_size(tile_size: &u32) -> bool {
+    *tile_size == TILE_SIZE
 }
 
 impl TilemapData {
-    fn from_tilemap(tilemap: SharedTilemap) -> Self {
+    pub(crate) fn from_tilemap(tilemap: SharedTilemap) -> Self {
         let tilemap = tilemap.lock();
         let width = tilemap.width();
         let height = tilemap.height();
@@ -72,7 +95,7 @@ impl TilemapData {
             .canvas
             .data
             .iter()
-            .flat_map(|(tx, ty)| [*tx, *ty].to_vec())
+            .flat_map(|(tx, ty, _)| [*tx, *ty].to_vec())
             .collect();
         let data: Vec<Vec<_>> = data
             .chunks((width * 2) as usize)
@@ -80,28 +103,138 @@ impl TilemapData {
             .collect();
         let data = compress_vec2(&data);
 
+        // Attributes are stored separately so that files without them stay loadable
+        let attrs: Vec<Vec<_>> = if tilemap.canvas.data.iter().any(|tile| tile.2 != 0) {
+            let attrs: Vec<Vec<_>> = tilemap
+                .canvas
+                .data
+                .chunks(width as usize)
+                .map(|tiles| tiles.iter().map(|tile| tile.2).collect())
+                .collect();
+            compress_vec2(&attrs)
+        } else {
+            Vec::new()
+        };
+
         Self {
             width,
             height,
             imgsrc,
             data,
+            attrs,
+            tile_width: tilemap.tile_width,
+            tile_height: tilemap.tile_height,
         }
     }
 
-    fn to_tilemap(&self) -> SharedTilemap {
+    pub(crate) fn to_tilemap(&self) -> SharedTilemap {
         let data = expand_vec2(&self.data, self.height as usize, (self.width * 2) as usize);
         let tilemap = Tilemap::new(self.width, self.height, ImageSource::Index(self.imgsrc));
 
         {
             let mut tilemap = tilemap.lock();
+            tilemap.tile_width = self.tile_width.max(1);
+            tilemap.tile_height = self.tile_height.max(1);
             let data: Vec<_> = data.clone().into_iter().flatten().collect();
-            tilemap.canvas.data = data.chunks(2).map(|chunk| (chunk[0], chunk[1])).collect();
+            let attrs: Vec<_> = if self.attrs.is_empty() {
+                vec![0; (self.width * self.height) as usize]
+            } else {
+                expand_vec2(&self.attrs, self.height as usize, self.width as usize)
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            };
+            tilemap.canvas.data = data
+                .chunks(2)
+                .zip(attrs)
+                .map(|(chunk, attr)| (chunk[0], chunk[1], attr))
+                .collect();
         }
 
         tilemap
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+struct AnimationFrameData {
+    image: u32,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    duration: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    events: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct AnimationClipData {
+    name: String,
+    mode: u32,
+    frames: Vec<AnimationFrameData>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct AnimationData {
+    clips: Vec<AnimationClipData>,
+}
+
+impl AnimationData {
+    fn from_animation(animation: SharedAnimation) -> Self {
+        let animation = animation.lock();
+        let clips = animation
+            .clips
+            .iter()
+            .map(|(name, clip)| AnimationClipData {
+                name: name.clone(),
+                mode: clip.mode.into(),
+                frames: clip
+                    .frames
+                    .iter()
+                    .map(|frame| AnimationFrameData {
+                        image: frame.image,
+                        x: frame.image_x,
+                        y: frame.image_y,
+                        width: frame.width,
+                        height: frame.height,
+                        duration: frame.duration,
+                        events: frame.events.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self { clips }
+    }
+
+    fn to_animation(&self) -> SharedAnimation {
+        let animation = Animation::new();
+
+        {
+            let mut animation = animation.lock();
+            for clip in &self.clips {
+                for frame in &clip.frames {
+                    animation.add_frame(
+                        &clip.name,
+                        AnimationFrame {
+                            image: frame.image,
+                            image_x: frame.x,
+                            image_y: frame.y,
+                            width: frame.width,
+                            height: frame.height,
+                            duration: frame.duration,
+                            events: frame.events.clone(),
+                        },
+                    );
+                }
+                let _ = animation.set_mode(&clip.name, clip.mode.into());
+            }
+        }
+
+        animation
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct SoundData {
     notes: Vec<SoundNote>,
@@ -176,6 +309,8 @@ pub struct ResourceData {
     tilemaps: Vec<TilemapData>,
     sounds: Vec<SoundData>,
     musics: Vec<MusicData>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    animations: Vec<AnimationData>,
 }
 
 impl ResourceData {
@@ -190,6 +325,7 @@ impl ResourceData {
             tilemaps: Vec::new(),
             sounds: Vec::new(),
             musics: Vec::new(),
+            animations: Vec::new(),
         };
 
         for image in &*pyxel.images.lock() {
@@ -204,6 +340,12 @@ impl ResourceData {
                 .push(TilemapData::from_tilemap(tilemap.clone()));
         }
 
+        for animation in &*pyxel.animations.lock() {
+            resource_data
+                .animations
+                .push(AnimationData::from_animation(animation.clone()));
+        }
+
         for sound in &*pyxel.sounds.lock() {
             resource_data
                 .sounds
@@ -235,6 +377,15 @@ impl ResourceData {
             *pyxel.images.lock() = images;
         }
 
+        // Animations refer to image banks, so they are loaded and saved along with images
+        if !exclude_images && !self.animations.is_empty() {
+            let mut animations = Vec::new();
+            for animation_data in &self.animations {
+                animations.push(animation_data.to_animation());
+            }
+            *pyxel.animations.lock() = animations;
+        }
+
         if !exclude_tilemaps && !self.tilemaps.is_empty() {
             let mut tilemaps = Vec::new();
             for tilemap_data in &self.tilemaps {
@@ -271,6 +422,7 @@ impl ResourceData {
 
         if exclude_images {
             resource_data.images.clear();
+            resource_data.animations.clear();
         }
 
         if exclude_tilemaps {
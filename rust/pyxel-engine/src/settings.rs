@@ -2,6 +2,8 @@ use crate::channel::ChannelGain;
 use crate::image::{Color, Rgb24};
 use crate::key::{Key, KEY_ESCAPE};
 use crate::sound::{SoundEffect, SoundSpeed, SoundTone, SoundVolume};
+use crate::terrain::TerrainMask;
+use crate::tilemap::TileAttr;
 use crate::tone::{ToneMode, ToneSample};
 
 // System
@@ -48,6 +50,16 @@ pub const RESOURCE_FILE_EXTENSION: &str = ".pyxres";
 pub const RESOURCE_ARCHIVE_NAME: &str = "pyxel_resource.toml";
 pub const RESOURCE_FORMAT_VERSION: u32 = 4;
 pub const PALETTE_FILE_EXTENSION: &str = ".pyxpal";
+pub const REPLAY_FILE_EXTENSION: &str = ".pyxrep";
+pub const REPLAY_FORMAT_VERSION: u32 = 1;
+pub const QUANTIZE_MEDIAN_CUT: u32 = 0;
+pub const QUANTIZE_KMEANS: u32 = 1;
+pub const IMPORT_DITHER_NONE: u32 = 0;
+pub const IMPORT_DITHER_FLOYD_STEINBERG: u32 = 1;
+pub const IMPORT_DITHER_ORDERED: u32 = 2;
+pub const KMEANS_ITERATIONS: u32 = 8;
+pub const ORDERED_DITHER_SPREAD: f32 = 32.0;
+pub const IMPORT_ALPHA_THRESHOLD: u8 = 128;
 
 // Graphics
 pub const NUM_COLORS: u32 = 16;
@@ -100,7 +112,45 @@ pub const FONT_DATA: [u32; MAX_FONT_CODE as usize - MIN_FONT_CODE as usize + 1]
     0x06aa62, 0x068880, 0x06c6c0, 0x4e4460, 0x0aaa60, 0x0aaa40, 0x0aaee0, 0x0a44a0, 0x0aa624,
     0x0e24e0, 0x64c460, 0x444440, 0xc464c0, 0x6c0000, 0xeeeee0,
 ];
+pub const FILL_RULE_EVEN_ODD: u32 = 0;
+pub const FILL_RULE_NONZERO: u32 = 1;
+pub const LINE_CAP_BUTT: u32 = 0;
+pub const LINE_CAP_ROUND: u32 = 1;
+pub const LINE_CAP_SQUARE: u32 = 2;
+pub const MAX_CURVE_SEGMENTS: u32 = 1024;
+pub const DEFAULT_MODE7_FOV: f32 = 60.0;
+pub const BLEND_NONE: u32 = 0;
+pub const BLEND_ADD: u32 = 1;
+pub const BLEND_SUBTRACT: u32 = 2;
+pub const BLEND_MULTIPLY: u32 = 3;
+pub const BLEND_AVERAGE: u32 = 4;
+pub const BLEND_TABLE_SIZE: u32 = 256;
+pub const ANIM_LOOP: u32 = 0;
+pub const ANIM_PINGPONG: u32 = 1;
+pub const ANIM_ONESHOT: u32 = 2;
+pub const TILE_FLIP_X: TileAttr = 1;
+pub const TILE_FLIP_Y: TileAttr = 2;
+pub const TILE_ROTATE: TileAttr = 4; // Ignored unless tile_width == tile_height
+pub const TILE_PALETTE_SHIFT: TileAttr = 4;
+pub const DIAGONAL_NONE: u32 = 0;
+pub const DIAGONAL_NO_CORNERS: u32 = 1;
+pub const DIAGONAL_ALL: u32 = 2;
+pub const TERRAIN_EDGES: u32 = 0;
+pub const TERRAIN_BLOB: u32 = 1;
+pub const TERRAIN_N: TerrainMask = 1;
+pub const TERRAIN_NE: TerrainMask = 2;
+pub const TERRAIN_E: TerrainMask = 4;
+pub const TERRAIN_SE: TerrainMask = 8;
+pub const TERRAIN_S: TerrainMask = 16;
+pub const TERRAIN_SW: TerrainMask = 32;
+pub const TERRAIN_W: TerrainMask = 64;
+pub const TERRAIN_NW: TerrainMask = 128;
+pub const ALIGN_LEFT: u32 = 0;
+pub const ALIGN_CENTER: u32 = 1;
+pub const ALIGN_RIGHT: u32 = 2;
+pub const ALIGN_JUSTIFY: u32 = 3;
 pub const NUM_SCREEN_TYPES: u32 = 3;
+pub const MAX_LAYERS: u32 = 4;
 
 // Audio
 pub const AUDIO_CLOCK_RATE: u32 = 1_789_773; // NTSC NES APU clock rate
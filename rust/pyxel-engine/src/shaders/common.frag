@@ -2,9 +2,27 @@ uniform vec2 u_screenPos;
 uniform vec2 u_screenSize;
 uniform float u_screenScale;
 uniform int u_numColors;
+uniform int u_numColorRows;
 uniform vec3 u_backgroundColor;
 uniform sampler2D u_screenTexture;
 uniform sampler2D u_colorsTexture;
+uniform sampler2D u_sourceTexture;
+uniform vec2 u_sourceSize;
+uniform float u_screenTransparent;
+uniform sampler2D u_rasterTexture;
+uniform float u_rasterEnabled;
+uniform sampler2D u_layerTexture0;
+uniform sampler2D u_layerTexture1;
+uniform sampler2D u_layerTexture2;
+uniform sampler2D u_layerTexture3;
+uniform vec4 u_layerRect0;
+uniform vec4 u_layerRect1;
+uniform vec4 u_layerRect2;
+uniform vec4 u_layerRect3;
+uniform vec4 u_layerFlags0;
+uniform vec4 u_layerFlags1;
+uniform vec4 u_layerFlags2;
+uniform vec4 u_layerFlags3;
 
 void getScreenParams(out vec2 screenFragCoord, out vec2 screenTexCoord) {
     screenFragCoord = gl_FragCoord.xy - u_screenPos;
@@ -16,8 +34,62 @@ bool isInScreen(vec2 screenTexCoord) {
     return all(greaterThanEqual(screenTexCoord, vec2(0.0))) && all(lessThanEqual(screenTexCoord, vec2(1.0)));
 }
 
-vec3 getScreenColor(vec2 screenTexCoord) {
-    float indexColor = texture2D(u_screenTexture, screenTexCoord).r * 255.0;
-    vec2 colorsTexCoord = vec2((indexColor + 0.5) / float(u_numColors), 0.5);
+vec3 getPaletteColor(float indexColor, float row) {
+    vec2 colorsTexCoord = vec2((indexColor + 0.5) / float(u_numColors), (row + 0.5) / float(u_numColorRows));
     return texture2D(u_colorsTexture, colorsTexCoord).rgb;
 }
+
+// rect: (offsetX, offsetY, width, height), flags: (transparent, front, wrap, visible)
+void compositeLayer(inout vec3 color, vec2 screenPixelCoord, float front, sampler2D layerTexture, vec4 rect, vec4 flags, float row) {
+    if (flags.w < 0.5 || abs(flags.y - front) > 0.5) {
+        return;
+    }
+
+    vec2 layerCoord = screenPixelCoord + rect.xy;
+    if (flags.z > 0.5) {
+        layerCoord = mod(layerCoord, rect.zw);
+    } else if (any(lessThan(layerCoord, vec2(0.0))) || any(greaterThanEqual(layerCoord, rect.zw))) {
+        return;
+    }
+
+    float indexColor = floor(texture2D(layerTexture, (layerCoord + 0.5) / rect.zw).r * 255.0 + 0.5);
+    if (indexColor != flags.x) {
+        color = getPaletteColor(indexColor, row);
+    }
+}
+
+vec3 getScreenColor(vec2 screenTexCoord) {
+    vec2 screenPixelSize = u_screenSize / u_screenScale;
+    vec2 screenPixelCoord = min(floor(screenTexCoord * screenPixelSize), screenPixelSize - 1.0);
+    float screenRow = 0.0;
+
+    // raster: (offset low, offset high, palette row low, palette row high)
+    if (u_rasterEnabled > 0.5) {
+        vec4 raster = floor(texture2D(u_rasterTexture, vec2(0.5, (screenPixelCoord.y + 0.5) / screenPixelSize.y)) * 255.0 + 0.5);
+        float offset = raster.r + raster.g * 256.0 - 32768.0;
+        vec2 rasterPixelCoord = vec2(mod(screenPixelCoord.x + offset, screenPixelSize.x), screenPixelCoord.y);
+        screenTexCoord = (rasterPixelCoord + 0.5) / screenPixelSize;
+        screenRow = raster.b + raster.a * 256.0;
+    }
+
+    float indexColor = floor(texture2D(u_screenTexture, screenTexCoord).r * 255.0 + 0.5);
+    vec3 color = getPaletteColor(indexColor, screenRow);
+
+    if (indexColor == u_screenTransparent) {
+        compositeLayer(color, screenPixelCoord, 0.0, u_layerTexture0, u_layerRect0, u_layerFlags0, 1.0);
+        compositeLayer(color, screenPixelCoord, 0.0, u_layerTexture1, u_layerRect1, u_layerFlags1, 2.0);
+        compositeLayer(color, screenPixelCoord, 0.0, u_layerTexture2, u_layerRect2, u_layerFlags2, 3.0);
+        compositeLayer(color, screenPixelCoord, 0.0, u_layerTexture3, u_layerRect3, u_layerFlags3, 4.0);
+    }
+
+    compositeLayer(color, screenPixelCoord, 1.0, u_layerTexture0, u_layerRect0, u_layerFlags0, 1.0);
+    compositeLayer(color, screenPixelCoord, 1.0, u_layerTexture1, u_layerRect1, u_layerFlags1, 2.0);
+    compositeLayer(color, screenPixelCoord, 1.0, u_layerTexture2, u_layerRect2, u_layerFlags2, 3.0);
+    compositeLayer(color, screenPixelCoord, 1.0, u_layerTexture3, u_layerRect3, u_layerFlags3, 4.0);
+
+    return color;
+}
+
+vec3 getSourceColor(vec2 fragCoord) {
+    return texture2D(u_sourceTexture, fragCoord / u_sourceSize).rgb;
+}
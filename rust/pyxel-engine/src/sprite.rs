@@ -0,0 +1,278 @@
+use std::mem::take;
+
+use crate::image::{Color, Image, SharedImage};
+use crate::pyxel::Pyxel;
+use crate::rect_area::RectArea;
+use crate::settings::MAX_COLORS;
+use crate::utils::f32_to_i32;
+
+#[derive(Clone)]
+pub struct Sprite {
+    pub image: SharedImage,
+    pub x: f32,
+    pub y: f32,
+    pub image_x: f32,
+    pub image_y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub z: i32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub color_key: Option<Color>,
+    pub rotate: f32,
+    pub scale: f32,
+    pub palette: Option<Vec<Color>>,
+}
+
+impl Sprite {
+    pub fn new(
+        x: f32,
+        y: f32,
+        image: SharedImage,
+        image_x: f32,
+        image_y: f32,
+        width: f32,
+        height: f32,
+    ) -> Self {
+        Self {
+            image,
+            x,
+            y,
+            image_x,
+            image_y,
+            width,
+            height,
+            z: 0,
+            flip_x: false,
+            flip_y: false,
+            color_key: None,
+            rotate: 0.0,
+            scale: 1.0,
+            palette: None,
+        }
+    }
+
+    fn is_transformed(&self) -> bool {
+        self.rotate != 0.0 || self.scale != 1.0
+    }
+
+    fn dst_rows(&self, camera_y: i32) -> (i32, i32) {
+        let y = f32_to_i32(self.y) - camera_y;
+        let height = f32_to_i32(self.height).abs();
+        if !self.is_transformed() {
+            return (y, y + height - 1);
+        }
+
+        let half_width = (f32_to_i32(self.width).abs() - 1) as f32 / 2.0;
+        let half_height = (height - 1) as f32 / 2.0;
+        let rotate = self.rotate.to_radians();
+        let offset_y =
+            (half_width * rotate.sin().abs() + half_height * rotate.cos().abs() + 1.0) * self.scale;
+        let center_y = y as f32 + half_height;
+        (
+            f32_to_i32(center_y - offset_y),
+            f32_to_i32(center_y + offset_y),
+        )
+    }
+
+    fn draw(&self, screen: &mut Image, palette: &[Color]) {
+        let width = if self.flip_x { -self.width } else { self.width };
+        let height = if self.flip_y {
+            -self.height
+        } else {
+            self.height
+        };
+
+        let copied_canvas;
+        let locked_image = self.image.try_lock();
+        let canvas = if let Some(image) = &locked_image {
+            &image.canvas
+        } else {
+            copied_canvas = screen.canvas.clone();
+            &copied_canvas
+        };
+
+        if self.is_transformed() {
+            screen.canvas.blt_transform(
+                self.x,
+                self.y,
+                canvas,
+                self.image_x,
+                self.image_y,
+                width,
+                height,
+                self.color_key,
+                Some(palette),
+                self.rotate,
+                self.scale,
+                false,
+            );
+        } else {
+            screen.canvas.blt(
+                self.x,
+                self.y,
+                canvas,
+                self.image_x,
+                self.image_y,
+                width,
+                height,
+                self.color_key,
+                Some(palette),
+            );
+        }
+    }
+}
+
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+    scanline_limit: Option<u32>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        Self {
+            sprites: Vec::new(),
+            scanline_limit: None,
+        }
+    }
+}
+
+impl Pyxel {
+    pub fn spr(&mut self, sprite: Sprite) {
+        self.sprite_batch.sprites.push(sprite);
+    }
+
+    pub fn sprite_limit(&mut self, limit: Option<u32>) {
+        self.sprite_batch.scanline_limit = limit;
+    }
+
+    pub fn flush_sprites(&mut self) {
+        let mut sprites = take(&mut self.sprite_batch.sprites);
+        if sprites.is_empty() {
+            return;
+        }
+        sprites.sort_by_key(|sprite| sprite.z);
+
+        let mut screen = self.screen.lock();
+        let screen_palette = screen.palette;
+        let camera_y = screen.canvas.camera_y;
+        let clip_rect = screen.canvas.clip_rect;
+
+        // Claim scanlines in a frame-rotated order so that dropped sprites flicker
+        let sprite_rows: Vec<Vec<(i32, i32)>> = match self.sprite_batch.scanline_limit {
+            Some(limit) => {
+                let mut num_sprites = vec![0; self.height as usize];
+                let mut sprite_rows = vec![Vec::new(); sprites.len()];
+                let offset = self.frame_count as usize % sprites.len();
+
+                for index in (0..sprites.len()).map(|i| (i + offset) % sprites.len()) {
+                    let (top, bottom) = sprites[index].dst_rows(camera_y);
+                    let top = top.max(clip_rect.top());
+                    let bottom = bottom.min(clip_rect.bottom());
+                    let rows = &mut sprite_rows[index];
+
+                    for y in top..=bottom {
+                        let count = &mut num_sprites[y as usize];
+                        if *count >= limit {
+                            continue;
+                        }
+                        *count += 1;
+                        match rows.last_mut() {
+                            Some((_, run_bottom)) if *run_bottom == y - 1 => *run_bottom = y,
+                            _ => rows.push((y, y)),
+                        }
+                    }
+                }
+                sprite_rows
+            }
+            None => vec![vec![(clip_rect.top(), clip_rect.bottom())]; sprites.len()],
+        };
+
+        for (sprite, rows) in sprites.iter().zip(sprite_rows) {
+            let palette: Vec<Color> = match &sprite.palette {
+                Some(sprite_palette) => (0..MAX_COLORS as usize)
+                    .map(|color| {
+                        let color = sprite_palette.get(color).map_or(color, |&c| c as usize);
+                        screen_palette[color.min(MAX_COLORS as usize - 1)]
+                    })
+                    .collect(),
+                None => screen_palette.to_vec(),
+            };
+
+            for (top, bottom) in rows {
+                screen.canvas.clip_rect = clip_rect.intersects(RectArea::new(
+                    clip_rect.left(),
+                    top,
+                    clip_rect.width(),
+                    (bottom - top + 1) as u32,
+                ));
+                sprite.draw(&mut screen, &palette);
+            }
+        }
+
+        screen.canvas.clip_rect = clip_rect;
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use super::*;
+    use crate::pyxel::with_test_pyxel;
+
+    fn test_image() -> SharedImage {
+        let image = Image::new(16, 4);
+        for i in 0..4 {
+            image
+                .lock()
+                .rect(i as f32 * 4.0, 0.0, 4.0, 4.0, 1 + i as Color);
+        }
+        image
+    }
+
+    #[test]
+    fn test_sprite_z_order() {
+        with_test_pyxel(16, 8, |pyxel| {
+            let image = test_image();
+            let mut front = Sprite::new(0.0, 0.0, image.clone(), 0.0, 0.0, 4.0, 4.0);
+            front.z = 1;
+            let back = Sprite::new(2.0, 0.0, image, 4.0, 0.0, 4.0, 4.0);
+            pyxel.spr(front);
+            pyxel.spr(back);
+            pyxel.flush_sprites();
+
+            let mut screen = pyxel.screen.lock();
+            assert_eq!(screen.pget(1.0, 0.0), 1);
+            assert_eq!(screen.pget(3.0, 0.0), 1);
+            assert_eq!(screen.pget(5.0, 0.0), 2);
+        });
+    }
+
+    #[test]
+    fn test_sprite_scanline_limit() {
+        with_test_pyxel(16, 8, |pyxel| {
+            let image = test_image();
+            let draw_sprites = |pyxel: &mut Pyxel| {
+                pyxel.screen.lock().cls(0);
+                for i in 0..3 {
+                    let x = i as f32 * 4.0;
+                    pyxel.spr(Sprite::new(x, 0.0, image.clone(), x, 0.0, 4.0, 4.0));
+                }
+                pyxel.flush_sprites();
+                let mut screen = pyxel.screen.lock();
+                (0..3)
+                    .map(|i| screen.pget(i as f32 * 4.0, 0.0))
+                    .collect::<Vec<_>>()
+            };
+
+            pyxel.sprite_limit(Some(2));
+            assert_eq!(draw_sprites(pyxel), [1, 2, 0]);
+
+            // The dropped sprite rotates with the frame count
+            pyxel.frame_count = 1;
+            assert_eq!(draw_sprites(pyxel), [0, 2, 3]);
+
+            pyxel.sprite_limit(None);
+            assert_eq!(draw_sprites(pyxel), [1, 2, 3]);
+        });
+    }
+}
@@ -1,7 +1,10 @@
+use std::sync::atomic::Ordering;
+
 use pyxel_platform::key::GAMEPAD1_BUTTON_BACK;
 use pyxel_platform::Event;
 
 use crate::image::{Color, Image, SharedImage};
+use crate::input::InputEvent;
 use crate::key::{
     Key, GAMEPAD1_BUTTON_A, GAMEPAD1_BUTTON_B, GAMEPAD1_BUTTON_DPAD_DOWN,
     GAMEPAD1_BUTTON_DPAD_LEFT, GAMEPAD1_BUTTON_DPAD_RIGHT, GAMEPAD1_BUTTON_DPAD_UP,
@@ -9,7 +12,7 @@ use crate::key::{
     KEY_RETURN, KEY_SHIFT,
 };
 use crate::profiler::Profiler;
-use crate::pyxel::Pyxel;
+use crate::pyxel::{Pyxel, FRAME_COUNT};
 use crate::settings::{MAX_FRAME_DELAY_MS, NUM_MEASURE_FRAMES, NUM_SCREEN_TYPES};
 use crate::utils;
 use crate::window_watcher::WindowWatcher;
@@ -20,7 +23,7 @@ pub trait PyxelCallback {
 }
 
 pub struct System {
-    fps: u32,
+    pub fps: u32,
     frame_ms: f32,
     quit_key: Key,
     paused: bool,
@@ -29,11 +32,15 @@ pub struct System {
     draw_profiler: Profiler,
     perf_monitor_enabled: bool,
     integer_scale_enabled: bool,
+    deterministic_enabled: bool,
     window_watcher: WindowWatcher,
     pub screen_x: i32,
     pub screen_y: i32,
     pub screen_scale: f32,
     pub screen_mode: u32,
+    pub num_screen_modes: u32,
+    pub screen_effects: Vec<u32>,
+    pub screen_transparent: Option<Color>,
 }
 
 impl System {
@@ -48,11 +55,15 @@ impl System {
             draw_profiler: Profiler::new(NUM_MEASURE_FRAMES),
             perf_monitor_enabled: false,
             integer_scale_enabled: false,
+            deterministic_enabled: false,
             window_watcher: WindowWatcher::new(),
             screen_x: 0,
             screen_y: 0,
             screen_scale: 0.0,
             screen_mode: 0,
+            num_screen_modes: NUM_SCREEN_TYPES,
+            screen_effects: Vec::new(),
+            screen_transparent: None,
         }
     }
 }
@@ -60,15 +71,16 @@ impl System {
 impl Pyxel {
     pub fn run<T: PyxelCallback>(&mut self, mut callback: T) {
         pyxel_platform::run_frame_loop(self.system.fps, move |delta_ms| {
-            let ticks = pyxel_platform::ticks();
+            let ticks = self.ticks();
             self.system.fps_profiler.end(ticks);
             self.system.fps_profiler.start(ticks);
 
-            let update_count = if delta_ms > MAX_FRAME_DELAY_MS as f32 {
-                1
-            } else {
-                (delta_ms / self.system.frame_ms) as u32
-            };
+            let update_count =
+                if self.system.deterministic_enabled || delta_ms > MAX_FRAME_DELAY_MS as f32 {
+                    1
+                } else {
+                    (delta_ms / self.system.frame_ms) as u32
+                };
             for _ in 1..update_count {
                 self.update_frame(Some(&mut callback));
                 self.frame_count += 1;
@@ -121,14 +133,14 @@ impl Pyxel {
     }
 
     pub fn flip(&mut self) {
-        self.system.update_profiler.end(pyxel_platform::ticks());
+        self.system.update_profiler.end(self.ticks());
 
         self.draw_frame(None);
         self.frame_count += 1;
 
         pyxel_platform::step_frame(self.system.fps);
 
-        let ticks = pyxel_platform::ticks();
+        let ticks = self.ticks();
         self.system.fps_profiler.end(ticks);
         self.system.fps_profiler.start(ticks);
 
@@ -204,6 +216,14 @@ impl Pyxel {
         self.system.integer_scale_enabled = enabled;
     }
 
+    pub fn deterministic(&mut self, enabled: bool) {
+        self.system.deterministic_enabled = enabled;
+    }
+
+    pub(crate) fn is_deterministic(&self) -> bool {
+        self.system.deterministic_enabled
+    }
+
     pub fn screen_mode(&mut self, screen_mode: u32) {
         self.system.screen_mode = screen_mode;
     }
@@ -212,10 +232,19 @@ impl Pyxel {
         pyxel_platform::set_fullscreen(enabled);
     }
 
+    fn ticks(&self) -> u32 {
+        if self.system.deterministic_enabled {
+            (self.frame_count as u64 * 1000 / self.system.fps as u64) as u32
+        } else {
+            pyxel_platform::ticks()
+        }
+    }
+
     fn process_events(&mut self) {
         self.start_input_frame();
 
         let events = pyxel_platform::poll_events();
+        let mut input_events = Vec::new();
 
         for event in events {
             match event {
@@ -228,25 +257,46 @@ impl Pyxel {
                     pyxel_platform::pause_audio(true);
                 }
                 Event::KeyPressed { key } => {
-                    self.press_key(key);
+                    input_events.push(InputEvent::KeyPressed { key });
                 }
                 Event::KeyReleased { key } => {
-                    self.release_key(key);
+                    input_events.push(InputEvent::KeyReleased { key });
                 }
                 Event::KeyValueChanged { key, value } => {
-                    self.change_key_value(key, value);
+                    let value = self.translate_key_value(key, value);
+                    input_events.push(InputEvent::KeyValueChanged { key, value });
                 }
                 Event::TextInput { text } => {
-                    self.add_input_text(&text);
+                    input_events.push(InputEvent::TextInput { text });
                 }
                 Event::FileDropped { filename } => {
-                    self.add_dropped_file(&filename);
+                    input_events.push(InputEvent::FileDropped { filename });
                 }
                 Event::Quit => {
                     pyxel_platform::quit();
                 }
             }
         }
+
+        for event in self.replay_input_events(input_events) {
+            match event {
+                InputEvent::KeyPressed { key } => {
+                    self.press_key(key);
+                }
+                InputEvent::KeyReleased { key } => {
+                    self.release_key(key);
+                }
+                InputEvent::KeyValueChanged { key, value } => {
+                    self.change_key_value(key, value);
+                }
+                InputEvent::TextInput { text } => {
+                    self.add_input_text(&text);
+                }
+                InputEvent::FileDropped { filename } => {
+                    self.add_dropped_file(&filename);
+                }
+            }
+        }
     }
 
     fn check_special_input(&mut self) {
@@ -280,7 +330,7 @@ impl Pyxel {
                 self.integer_scale(!self.system.integer_scale_enabled);
             } else if self.btnp(KEY_9, None, None) {
                 self.reset_key(KEY_9);
-                self.screen_mode((self.system.screen_mode + 1) % NUM_SCREEN_TYPES);
+                self.screen_mode((self.system.screen_mode + 1) % self.system.num_screen_modes);
             } else if self.btnp(KEY_0, None, None) {
                 self.reset_key(KEY_0);
                 self.perf_monitor(!self.system.perf_monitor_enabled);
@@ -304,7 +354,7 @@ impl Pyxel {
                 self.integer_scale(!self.system.integer_scale_enabled);
             } else if self.btnp(GAMEPAD1_BUTTON_DPAD_RIGHT, None, None) {
                 self.reset_key(GAMEPAD1_BUTTON_DPAD_DOWN);
-                self.screen_mode((self.system.screen_mode + 1) % NUM_SCREEN_TYPES);
+                self.screen_mode((self.system.screen_mode + 1) % self.system.num_screen_modes);
             } else if self.btnp(GAMEPAD1_BUTTON_DPAD_UP, None, None) {
                 self.reset_key(GAMEPAD1_BUTTON_DPAD_LEFT);
                 self.perf_monitor(!self.system.perf_monitor_enabled);
@@ -343,7 +393,7 @@ impl Pyxel {
     }
 
     fn update_frame(&mut self, callback: Option<&mut dyn PyxelCallback>) {
-        self.system.update_profiler.start(pyxel_platform::ticks());
+        self.system.update_profiler.start(self.ticks());
 
         self.process_events();
 
@@ -352,10 +402,11 @@ impl Pyxel {
         }
 
         self.check_special_input();
+        FRAME_COUNT.store(self.frame_count, Ordering::Relaxed);
 
         if let Some(callback) = callback {
             callback.update(self);
-            self.system.update_profiler.end(pyxel_platform::ticks());
+            self.system.update_profiler.end(self.ticks());
         }
     }
 
@@ -445,17 +496,19 @@ impl Pyxel {
     }
 
     fn draw_frame(&mut self, callback: Option<&mut dyn PyxelCallback>) {
-        self.system.draw_profiler.start(pyxel_platform::ticks());
+        self.system.draw_profiler.start(self.ticks());
 
         if self.system.paused {
             return;
         }
 
         self.update_screen_params();
+        FRAME_COUNT.store(self.frame_count, Ordering::Relaxed);
 
         if let Some(callback) = callback {
             callback.draw(self);
         }
+        self.flush_sprites();
 
         self.system.window_watcher.update();
         self.draw_perf_monitor();
@@ -463,6 +516,6 @@ impl Pyxel {
         self.render_screen();
         self.capture_screen();
 
-        self.system.draw_profiler.end(pyxel_platform::ticks());
+        self.system.draw_profiler.end(self.ticks());
     }
 }
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use crate::settings::{
+    TERRAIN_E, TERRAIN_N, TERRAIN_NE, TERRAIN_NW, TERRAIN_S, TERRAIN_SE, TERRAIN_SW, TERRAIN_W,
+};
+use crate::tilemap::{Tile, TileCoord, Tilemap};
+
+pub type TerrainMask = u8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TerrainMode {
+    Edges,
+    Blob,
+}
+
+impl From<u32> for TerrainMode {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::Blob,
+            _ => Self::Edges,
+        }
+    }
+}
+
+const EDGE_BITS: TerrainMask = TERRAIN_N | TERRAIN_E | TERRAIN_S | TERRAIN_W;
+
+const NEIGHBOR_BITS: [(i32, i32, TerrainMask); 8] = [
+    (0, -1, TERRAIN_N),
+    (1, -1, TERRAIN_NE),
+    (1, 0, TERRAIN_E),
+    (1, 1, TERRAIN_SE),
+    (0, 1, TERRAIN_S),
+    (-1, 1, TERRAIN_SW),
+    (-1, 0, TERRAIN_W),
+    (-1, -1, TERRAIN_NW),
+];
+
+// Each corner only matters when both of its adjacent edges connect
+const CORNER_EDGES: [(TerrainMask, TerrainMask); 4] = [
+    (TERRAIN_NE, TERRAIN_N | TERRAIN_E),
+    (TERRAIN_SE, TERRAIN_S | TERRAIN_E),
+    (TERRAIN_SW, TERRAIN_S | TERRAIN_W),
+    (TERRAIN_NW, TERRAIN_N | TERRAIN_W),
+];
+
+pub struct Terrain {
+    pub mode: TerrainMode,
+    pub rules: HashMap<TerrainMask, TileCoord>,
+}
+
+pub type SharedTerrain = shared_type!(Terrain);
+
+impl Terrain {
+    pub fn new(mode: TerrainMode) -> SharedTerrain {
+        new_shared_type!(Self {
+            mode,
+            rules: HashMap::new(),
+        })
+    }
+
+    // Edge terrains keep 16 masks and blob terrains reduce the 256 neighbor masks to 47
+    pub fn normalize_mask(&self, mask: TerrainMask) -> TerrainMask {
+        match self.mode {
+            TerrainMode::Edges => mask & EDGE_BITS,
+            TerrainMode::Blob => CORNER_EDGES.iter().fold(mask, |mask, &(corner, edges)| {
+                if mask & edges == edges {
+                    mask
+                } else {
+                    mask & !corner
+                }
+            }),
+        }
+    }
+
+    pub fn set_rule(&mut self, mask: TerrainMask, tile: TileCoord) {
+        self.rules.insert(self.normalize_mask(mask), tile);
+    }
+
+    pub fn rule(&self, mask: TerrainMask) -> Option<TileCoord> {
+        self.rules.get(&self.normalize_mask(mask)).copied()
+    }
+
+    pub fn contains(&self, tile: Tile) -> bool {
+        self.rules.values().any(|&coord| coord == (tile.0, tile.1))
+    }
+
+    // The fully connected tile stands in for a cell whose mask has no rule
+    fn fill_tile(&self) -> Option<TileCoord> {
+        self.rule(TerrainMask::MAX).or_else(|| {
+            self.rules
+                .iter()
+                .min_by_key(|(mask, _)| **mask)
+                .map(|(_, tile)| *tile)
+        })
+    }
+}
+
+impl Tilemap {
+    // Cells outside the tilemap count as connected so that terrain reaching the border has no edge
+    pub fn terrain_mask(&self, x: i32, y: i32, terrain: &Terrain) -> TerrainMask {
+        let mask = NEIGHBOR_BITS
+            .iter()
+            .filter(|(dx, dy, _)| {
+                self.tile_at((x + dx, y + dy))
+                    .is_none_or(|tile| terrain.contains(tile))
+            })
+            .fold(0, |mask, (_, _, bit)| mask | bit);
+        terrain.normalize_mask(mask)
+    }
+
+    pub fn resolve_terrain(&mut self, x: i32, y: i32, width: i32, height: i32, terrain: &Terrain) {
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + width).min(self.width() as i32);
+        let bottom = (y + height).min(self.height() as i32);
+
+        for yi in top..bottom {
+            for xi in left..right {
+                let tile = self.canvas.read_data(xi as usize, yi as usize);
+                if !terrain.contains(tile) {
+                    continue;
+                }
+                if let Some((tile_x, tile_y)) = terrain.rule(self.terrain_mask(xi, yi, terrain)) {
+                    self.canvas
+                        .write_data(xi as usize, yi as usize, (tile_x, tile_y, tile.2));
+                }
+            }
+        }
+    }
+
+    pub fn paint_terrain(&mut self, x: i32, y: i32, terrain: &Terrain) {
+        let Some((tile_x, tile_y)) = terrain.fill_tile() else {
+            return;
+        };
+        if self.tile_at((x, y)).is_none() {
+            return;
+        }
+
+        self.canvas
+            .write_data(x as usize, y as usize, (tile_x, tile_y, 0));
+        self.resolve_terrain(x - 1, y - 1, 3, 3, terrain);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::tilemap::ImageSource;
+
+    #[test]
+    fn test_normalize_mask() {
+        let terrain = Terrain::new(TerrainMode::Blob);
+        let terrain = terrain.lock();
+        let masks: HashSet<_> = (0..=TerrainMask::MAX)
+            .map(|mask| terrain.normalize_mask(mask))
+            .collect();
+        assert_eq!(masks.len(), 47);
+        assert_eq!(terrain.normalize_mask(TERRAIN_NE | TERRAIN_N), TERRAIN_N);
+
+        let terrain = Terrain::new(TerrainMode::Edges);
+        let terrain = terrain.lock();
+        let masks: HashSet<_> = (0..=TerrainMask::MAX)
+            .map(|mask| terrain.normalize_mask(mask))
+            .collect();
+        assert_eq!(masks.len(), 16);
+    }
+
+    #[test]
+    fn test_paint_terrain() {
+        let terrain = Terrain::new(TerrainMode::Edges);
+        let mut terrain = terrain.lock();
+        for mask in 0..16 {
+            let bits = [TERRAIN_N, TERRAIN_E, TERRAIN_S, TERRAIN_W]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .fold(0, |bits, (_, bit)| bits | bit);
+            terrain.set_rule(bits, (mask, 1));
+        }
+
+        let tilemap = Tilemap::new(5, 5, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.paint_terrain(1, 2, &terrain);
+        assert_eq!(tilemap.canvas.read_data(1, 2), (0, 1, 0));
+
+        tilemap.paint_terrain(2, 2, &terrain);
+        tilemap.paint_terrain(3, 2, &terrain);
+        assert_eq!(tilemap.canvas.read_data(1, 2), (2, 1, 0));
+        assert_eq!(tilemap.canvas.read_data(2, 2), (10, 1, 0));
+        assert_eq!(tilemap.canvas.read_data(3, 2), (8, 1, 0));
+
+        tilemap.pset(2.0, 2.0, (0, 0, 0));
+        tilemap.resolve_terrain(0, 0, 5, 5, &terrain);
+        assert_eq!(tilemap.canvas.read_data(1, 2), (0, 1, 0));
+        assert_eq!(tilemap.canvas.read_data(3, 2), (0, 1, 0));
+
+        tilemap.paint_terrain(0, 0, &terrain);
+        assert_eq!(tilemap.canvas.read_data(0, 0), (9, 1, 0));
+    }
+}
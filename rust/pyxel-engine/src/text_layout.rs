@@ -0,0 +1,528 @@
+use std::iter::once;
+
+use crate::font::{Font, SharedFont};
+use crate::image::{Color, Image};
+use crate::pyxel::DEFAULT_FONT;
+use crate::settings::{FONT_HEIGHT, FONT_WIDTH, MAX_FONT_CODE, MIN_FONT_CODE};
+use crate::text_shaping::{is_combining_char, visual_order};
+use crate::utils;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+impl From<u32> for TextAlign {
+    fn from(index: u32) -> Self {
+        match index {
+            1 => Self::Center,
+            2 => Self::Right,
+            3 => Self::Justify,
+            _ => Self::Left,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    pub width: Option<u32>,
+    pub align: TextAlign,
+    pub line_spacing: i32,
+    pub shadow: Option<(Color, i32, i32)>,
+    pub outline: Option<Color>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            width: None,
+            align: TextAlign::Left,
+            line_spacing: 0,
+            shadow: None,
+            outline: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl TextBounds {
+    fn union(self, other: Self) -> Self {
+        let left = self.x.min(other.x);
+        let top = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct StyledChar {
+    c: char,
+    color: Color,
+    font: usize,
+}
+
+// Combining marks are kept in the glyph of their base character
+struct PlacedGlyph {
+    x: i32,
+    y: i32,
+    text: String,
+    color: Color,
+    font: usize,
+}
+
+struct TextLine {
+    chars: Vec<StyledChar>,
+    ends_paragraph: bool,
+}
+
+// Index 0 is the base font and None stands for the built-in font
+struct TextFonts<'a> {
+    fonts: &'a [Option<SharedFont>],
+}
+
+impl TextFonts<'_> {
+    fn advance(&self, ch: StyledChar) -> i32 {
+        match &self.fonts[ch.font] {
+            None if (MIN_FONT_CODE..=MAX_FONT_CODE).contains(&ch.c) => FONT_WIDTH as i32,
+            None => 0,
+            Some(_) if Font::should_skip_char(ch.c) => 0,
+            Some(font) => font.lock().advance(ch.c),
+        }
+    }
+
+    fn kerning(&self, left: StyledChar, right: StyledChar) -> i32 {
+        if left.font != right.font {
+            return 0;
+        }
+        self.fonts[left.font]
+            .as_ref()
+            .map_or(0, |font| font.lock().kerning(left.c, right.c))
+    }
+
+    fn line_height(&self, font: usize) -> i32 {
+        self.fonts[font]
+            .as_ref()
+            .map_or(FONT_HEIGHT as i32, |font| font.lock().line_height())
+    }
+
+    fn width(&self, chars: &[StyledChar]) -> i32 {
+        let advances: i32 = chars.iter().map(|&ch| self.advance(ch)).sum();
+        let kernings: i32 = chars
+            .windows(2)
+            .map(|pair| self.kerning(pair[0], pair[1]))
+            .sum();
+        advances + kernings
+    }
+}
+
+// Supports {col=N} and {font=N} with their closing {/col} and {/font}, and {{ for a brace
+fn parse_markup(text: &str, color: Color, num_fonts: usize) -> Vec<StyledChar> {
+    let mut chars = Vec::new();
+    let mut colors = vec![color];
+    let mut fonts = vec![0];
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") {
+            rest = &rest[2..];
+        } else if let Some(end) = rest.find('}').filter(|_| c == '{') {
+            let tag = &rest[1..end];
+            let is_tag = match tag {
+                "/col" => {
+                    if colors.len() > 1 {
+                        colors.pop();
+                    }
+                    true
+                }
+                "/font" => {
+                    if fonts.len() > 1 {
+                        fonts.pop();
+                    }
+                    true
+                }
+                _ => {
+                    if let Some(color) = tag.strip_prefix("col=").and_then(|v| v.parse().ok()) {
+                        colors.push(color);
+                        true
+                    } else if let Some(font) = tag
+                        .strip_prefix("font=")
+                        .and_then(|v| v.parse().ok())
+                        .filter(|&font| font < num_fonts)
+                    {
+                        fonts.push(font);
+                        true
+                    } else {
+                        false
+                    }
+                }
+            };
+            if is_tag {
+                rest = &rest[end + 1..];
+                continue;
+            }
+            rest = &rest[1..];
+        } else {
+            rest = &rest[c.len_utf8()..];
+        }
+
+        chars.push(StyledChar {
+            c,
+            color: *colors.last().unwrap(),
+            font: *fonts.last().unwrap(),
+        });
+    }
+
+    chars
+}
+
+// Greedy word wrapping that drops spaces at wrap points and splits words wider than a line
+fn break_lines(chars: &[StyledChar], fonts: &TextFonts, width: Option<i32>) -> Vec<TextLine> {
+    let mut lines = Vec::new();
+
+    for paragraph in chars.split(|ch| ch.c == '\n') {
+        let mut line: Vec<StyledChar> = Vec::new();
+        let mut spaces: Vec<StyledChar> = Vec::new();
+        let mut is_wrapped = false;
+
+        for token in paragraph.chunk_by(|a, b| (a.c == ' ') == (b.c == ' ')) {
+            if token[0].c == ' ' {
+                if !line.is_empty() || !is_wrapped {
+                    spaces.extend_from_slice(token);
+                }
+                continue;
+            }
+
+            let mut word = token;
+            if let Some(width) = width {
+                let candidate: Vec<_> = line.iter().chain(&spaces).chain(word).copied().collect();
+                if !line.is_empty() && fonts.width(&candidate) > width {
+                    lines.push(TextLine {
+                        chars: line.split_off(0),
+                        ends_paragraph: false,
+                    });
+                    spaces.clear();
+                    is_wrapped = true;
+                }
+
+                while line.is_empty() && word.len() > 1 && fonts.width(word) > width {
+                    let count = (1..word.len())
+                        .rev()
+                        .find(|&count| fonts.width(&word[..count]) <= width)
+                        .unwrap_or(1);
+                    lines.push(TextLine {
+                        chars: word[..count].to_vec(),
+                        ends_paragraph: false,
+                    });
+                    word = &word[count..];
+                    spaces.clear();
+                    is_wrapped = true;
+                }
+            }
+
+            line.append(&mut spaces);
+            line.extend_from_slice(word);
+        }
+
+        lines.push(TextLine {
+            chars: line,
+            ends_paragraph: true,
+        });
+    }
+
+    lines
+}
+
+fn layout_text(
+    text: &str,
+    color: Color,
+    fonts: &[Option<SharedFont>],
+    style: &TextStyle,
+) -> (Vec<PlacedGlyph>, TextBounds) {
+    let fonts = TextFonts { fonts };
+    let chars = parse_markup(text, color, fonts.fonts.len());
+    let width = style.width.map(|width| width as i32);
+    let lines = break_lines(&chars, &fonts, width);
+    let line_widths: Vec<_> = lines.iter().map(|line| fonts.width(&line.chars)).collect();
+    let box_width = width.unwrap_or_else(|| line_widths.iter().copied().max().unwrap_or(0));
+
+    let mut glyphs: Vec<PlacedGlyph> = Vec::new();
+    let mut left = i32::MAX;
+    let mut right = i32::MIN;
+    let mut y = 0;
+
+    for (i, (line, &line_width)) in lines.iter().zip(&line_widths).enumerate() {
+        if i > 0 {
+            y += style.line_spacing;
+        }
+        let line_height = line
+            .chars
+            .iter()
+            .map(|ch| fonts.line_height(ch.font))
+            .max()
+            .unwrap_or_else(|| fonts.line_height(0));
+        let free_width = (box_width - line_width).max(0);
+        let num_spaces = line.chars.iter().filter(|ch| ch.c == ' ').count() as i32;
+        let is_justified =
+            style.align == TextAlign::Justify && !line.ends_paragraph && num_spaces > 0;
+
+        let mut x = match style.align {
+            TextAlign::Left | TextAlign::Justify => 0,
+            TextAlign::Center => free_width / 2,
+            TextAlign::Right => free_width,
+        };
+        left = left.min(x);
+
+        let mut space_index = 0;
+        let mut prev_char = None;
+        let line_start = glyphs.len();
+        for ch in visual_order(&line.chars, |ch| ch.c) {
+            if is_combining_char(ch.c) {
+                if let Some(glyph) = glyphs[line_start..]
+                    .last_mut()
+                    .filter(|glyph| glyph.font == ch.font)
+                {
+                    glyph.text.push(ch.c);
+                    continue;
+                }
+            }
+
+            if let Some(prev_char) = prev_char {
+                x += fonts.kerning(prev_char, ch);
+            }
+            prev_char = Some(ch);
+
+            if ch.c == ' ' {
+                x += fonts.advance(ch);
+                if is_justified {
+                    x += free_width / num_spaces + i32::from(space_index < free_width % num_spaces);
+                    space_index += 1;
+                }
+                continue;
+            }
+
+            glyphs.push(PlacedGlyph {
+                x,
+                y: y + line_height - fonts.line_height(ch.font),
+                text: ch.c.to_string(),
+                color: ch.color,
+                font: ch.font,
+            });
+            x += fonts.advance(ch);
+        }
+
+        right = right.max(x);
+        y += line_height;
+    }
+
+    let mut bounds = TextBounds {
+        x: left,
+        y: 0,
+        width: right - left,
+        height: y,
+    };
+    let glyph_bounds = bounds;
+    if style.outline.is_some() {
+        bounds = bounds.union(TextBounds {
+            x: glyph_bounds.x - 1,
+            y: glyph_bounds.y - 1,
+            width: glyph_bounds.width + 2,
+            height: glyph_bounds.height + 2,
+        });
+    }
+    if let Some((_, dx, dy)) = style.shadow {
+        bounds = bounds.union(TextBounds {
+            x: glyph_bounds.x + dx,
+            y: glyph_bounds.y + dy,
+            ..glyph_bounds
+        });
+    }
+
+    (glyphs, bounds)
+}
+
+fn text_fonts(font: Option<SharedFont>, fonts: &[SharedFont]) -> Vec<Option<SharedFont>> {
+    let font = font.or_else(|| DEFAULT_FONT.lock().clone());
+    once(font).chain(fonts.iter().cloned().map(Some)).collect()
+}
+
+// Markup indexes fonts with 0 as the base font and 1 onward as the extra fonts
+pub fn measure_text(
+    text: &str,
+    font: Option<SharedFont>,
+    fonts: &[SharedFont],
+    style: &TextStyle,
+) -> TextBounds {
+    layout_text(text, 0, &text_fonts(font, fonts), style).1
+}
+
+impl Image {
+    pub fn text_box(
+        &mut self,
+        x: f32,
+        y: f32,
+        text: &str,
+        color: Color,
+        font: Option<SharedFont>,
+        fonts: &[SharedFont],
+        style: &TextStyle,
+    ) -> TextBounds {
+        let fonts = text_fonts(font, fonts);
+        let (glyphs, bounds) = layout_text(text, color, &fonts, style);
+        let x = utils::f32_to_i32(x);
+        let y = utils::f32_to_i32(y);
+
+        if let Some((shadow_color, dx, dy)) = style.shadow {
+            self.draw_glyphs(x + dx, y + dy, &glyphs, &fonts, Some(shadow_color));
+        }
+        if let Some(outline_color) = style.outline {
+            for (dx, dy) in [
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ] {
+                self.draw_glyphs(x + dx, y + dy, &glyphs, &fonts, Some(outline_color));
+            }
+        }
+        self.draw_glyphs(x, y, &glyphs, &fonts, None);
+
+        TextBounds {
+            x: bounds.x + x,
+            y: bounds.y + y,
+            ..bounds
+        }
+    }
+
+    fn draw_glyphs(
+        &mut self,
+        x: i32,
+        y: i32,
+        glyphs: &[PlacedGlyph],
+        fonts: &[Option<SharedFont>],
+        color: Option<Color>,
+    ) {
+        for glyph in glyphs {
+            self.text(
+                (x + glyph.x) as f32,
+                (y + glyph.y) as f32,
+                &glyph.text,
+                color.unwrap_or(glyph.color),
+                fonts[glyph.font].clone(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph_positions(text: &str, style: &TextStyle) -> Vec<(char, i32, i32)> {
+        layout_text(text, 7, &[None], style)
+            .0
+            .iter()
+            .map(|glyph| (glyph.text.chars().next().unwrap(), glyph.x, glyph.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_layout_text() {
+        let mut style = TextStyle {
+            width: Some(24),
+            ..TextStyle::default()
+        };
+        assert_eq!(
+            measure_text("hello world", None, &[], &style),
+            TextBounds {
+                x: 0,
+                y: 0,
+                width: 20,
+                height: 12
+            }
+        );
+        assert_eq!(
+            glyph_positions(
+                "abcdefgh",
+                &TextStyle {
+                    width: Some(12),
+                    ..style
+                }
+            )
+            .last(),
+            Some(&('h', 4, 12))
+        );
+
+        style.align = TextAlign::Right;
+        assert_eq!(measure_text("hello world", None, &[], &style).x, 4);
+
+        style.align = TextAlign::Justify;
+        assert_eq!(
+            glyph_positions("ab c de", &style),
+            vec![
+                ('a', 0, 0),
+                ('b', 4, 0),
+                ('c', 20, 0),
+                ('d', 0, 6),
+                ('e', 4, 6)
+            ]
+        );
+
+        style.line_spacing = 2;
+        style.outline = Some(1);
+        style.shadow = Some((0, 2, 3));
+        assert_eq!(
+            measure_text("ab c de", None, &[], &style),
+            TextBounds {
+                x: -1,
+                y: -1,
+                width: 27,
+                height: 18
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_markup() {
+        let chars = parse_markup("{col=8}a{font=1}b{/col}c{/font}{{d}{font=2}", 3, 2);
+        let chars: Vec<_> = chars.iter().map(|ch| (ch.c, ch.color, ch.font)).collect();
+        assert_eq!(
+            chars,
+            vec![
+                ('a', 8, 0),
+                ('b', 8, 1),
+                ('c', 3, 1),
+                ('{', 3, 0),
+                ('d', 3, 0),
+                ('}', 3, 0),
+                ('{', 3, 0),
+                ('f', 3, 0),
+                ('o', 3, 0),
+                ('n', 3, 0),
+                ('t', 3, 0),
+                ('=', 3, 0),
+                ('2', 3, 0),
+                ('}', 3, 0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Ltr,
+    Rtl,
+    Neutral,
+}
+
+pub(crate) fn is_combining_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036F
+            | 0x0483..=0x0489
+            | 0x0591..=0x05BD
+            | 0x05BF
+            | 0x05C1..=0x05C2
+            | 0x05C4..=0x05C5
+            | 0x05C7
+            | 0x0610..=0x061A
+            | 0x064B..=0x065F
+            | 0x0670
+            | 0x06D6..=0x06DC
+            | 0x06DF..=0x06E4
+            | 0x06E7..=0x06E8
+            | 0x06EA..=0x06ED
+            | 0x0E31
+            | 0x0E34..=0x0E3A
+            | 0x0E47..=0x0E4E
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x20D0..=0x20FF
+            | 0x3099..=0x309A
+            | 0xFE20..=0xFE2F
+    )
+}
+
+pub(crate) fn is_rtl_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF
+    )
+}
+
+fn direction(c: char) -> Direction {
+    if is_rtl_char(c) {
+        Direction::Rtl
+    } else if c.is_alphanumeric() {
+        Direction::Ltr
+    } else {
+        Direction::Neutral
+    }
+}
+
+// Simplified bidi reordering for a single line with a left-to-right base direction:
+// right-to-left runs, including neutrals between them, are reversed cluster by cluster
+// so that combining marks stay after their base character
+pub(crate) fn visual_order<T: Copy>(items: &[T], char_of: impl Fn(T) -> char) -> Vec<T> {
+    if !items.iter().any(|&item| is_rtl_char(char_of(item))) {
+        return items.to_vec();
+    }
+
+    let clusters: Vec<&[T]> = items
+        .chunk_by(|_, &next| is_combining_char(char_of(next)))
+        .collect();
+    let mut directions: Vec<_> = clusters
+        .iter()
+        .map(|cluster| direction(char_of(cluster[0])))
+        .collect();
+
+    for i in 0..directions.len() {
+        if directions[i] != Direction::Neutral {
+            continue;
+        }
+        let prev = directions[..i]
+            .iter()
+            .rev()
+            .find(|&&dir| dir != Direction::Neutral);
+        let next = directions[i + 1..]
+            .iter()
+            .find(|&&dir| dir != Direction::Neutral);
+        directions[i] = if prev == Some(&Direction::Rtl) && next == Some(&Direction::Rtl) {
+            Direction::Rtl
+        } else {
+            Direction::Ltr
+        };
+    }
+
+    let mut ordered = Vec::with_capacity(items.len());
+    let mut start = 0;
+    while start < clusters.len() {
+        let is_rtl = directions[start] == Direction::Rtl;
+        let end = (start..clusters.len())
+            .find(|&i| (directions[i] == Direction::Rtl) != is_rtl)
+            .unwrap_or(clusters.len());
+        if is_rtl {
+            for cluster in clusters[start..end].iter().rev() {
+                ordered.extend_from_slice(cluster);
+            }
+        } else {
+            for cluster in &clusters[start..end] {
+                ordered.extend_from_slice(cluster);
+            }
+        }
+        start = end;
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reorder(text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        visual_order(&chars, |c| c).into_iter().collect()
+    }
+
+    #[test]
+    fn test_visual_order() {
+        assert_eq!(reorder("abc def"), "abc def");
+        assert_eq!(
+            reorder("ab \u{5d0}\u{5d1} \u{5d2} cd"),
+            "ab \u{5d2} \u{5d1}\u{5d0} cd"
+        );
+        assert_eq!(reorder("\u{5d0}\u{5b8}\u{5d1}"), "\u{5d1}\u{5d0}\u{5b8}");
+        assert_eq!(reorder("e\u{301}"), "e\u{301}");
+    }
+}
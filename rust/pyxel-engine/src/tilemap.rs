@@ -1,11 +1,16 @@
-use crate::canvas::{Canvas, ToIndex};
-use crate::image::SharedImage;
-use crate::settings::TILE_SIZE;
+use crate::canvas::{Canvas, FillRule, LineCap, ToIndex};
+use crate::image::{Color, SharedImage};
+use crate::settings::{
+    MAX_COLORS, NUM_COLORS, TILE_FLIP_X, TILE_FLIP_Y, TILE_PALETTE_SHIFT, TILE_ROTATE, TILE_SIZE,
+};
+use crate::tileset::SharedTileset;
 use crate::tmx_parser::parse_tmx;
 use crate::utils::{f32_to_u32, parse_hex_string, simplify_string};
 
 pub type ImageTileCoord = u8;
-pub type Tile = (ImageTileCoord, ImageTileCoord);
+pub type TileCoord = (ImageTileCoord, ImageTileCoord);
+pub type TileAttr = u8;
+pub type Tile = (ImageTileCoord, ImageTileCoord, TileAttr);
 
 impl ToIndex for Tile {
     fn to_index(&self) -> usize {
@@ -13,6 +18,11 @@ impl ToIndex for Tile {
     }
 }
 
+pub(crate) fn tile_color(tile: Tile, color: Color) -> Color {
+    let offset = (tile.2 >> TILE_PALETTE_SHIFT) as u32 * NUM_COLORS;
+    ((color as u32 + offset) % MAX_COLORS) as Color
+}
+
 #[derive(Clone)]
 pub enum ImageSource {
     Index(u32),
@@ -21,6 +31,9 @@ pub enum ImageSource {
 
 pub struct Tilemap {
     pub imgsrc: ImageSource,
+    pub tileset: Option<SharedTileset>,
+    pub tile_width: u32,
+    pub tile_height: u32,
 
     pub(crate) canvas: Canvas<Tile>,
 }
@@ -31,6 +44,9 @@ impl Tilemap {
     pub fn new(width: u32, height: u32, imgsrc: ImageSource) -> SharedTilemap {
         new_shared_type!(Self {
             imgsrc,
+            tileset: None,
+            tile_width: TILE_SIZE,
+            tile_height: TILE_SIZE,
 
             canvas: Canvas::new(width, height),
         })
@@ -48,6 +64,32 @@ impl Tilemap {
         self.canvas.height()
     }
 
+    // Maps a pixel offset within a tile to the image, rotating clockwise before flipping.
+    // Rotation would not fit a non-square tile into its cell, so it only applies to square tiles.
+    pub(crate) fn tile_pixel(&self, tile: Tile, x: i32, y: i32) -> (i32, i32) {
+        let tile_width = self.tile_width as i32;
+        let tile_height = self.tile_height as i32;
+        let x = if tile.2 & TILE_FLIP_X != 0 {
+            tile_width - 1 - x
+        } else {
+            x
+        };
+        let y = if tile.2 & TILE_FLIP_Y != 0 {
+            tile_height - 1 - y
+        } else {
+            y
+        };
+        let (x, y) = if tile.2 & TILE_ROTATE != 0 && tile_width == tile_height {
+            (y, tile_width - 1 - x)
+        } else {
+            (x, y)
+        };
+        (
+            tile.0 as i32 * tile_width + x,
+            tile.1 as i32 * tile_height + y,
+        )
+    }
+
     pub fn data_ptr(&mut self) -> *mut Tile {
         self.canvas.data_ptr()
     }
@@ -70,6 +112,7 @@ impl Tilemap {
                         (
                             ((tile >> 8) & 0xff) as ImageTileCoord,
                             (tile & 0xff) as ImageTileCoord,
+                            0,
                         ),
                     );
                 }
@@ -175,6 +218,56 @@ impl Tilemap {
         self.canvas.trib(x1, y1, x2, y2, x3, y3, tile);
     }
 
+    pub fn poly(&mut self, points: &[(f32, f32)], tile: Tile, fill_rule: FillRule) {
+        self.canvas.poly(points, tile, fill_rule);
+    }
+
+    pub fn polyb(&mut self, points: &[(f32, f32)], tile: Tile) {
+        self.canvas.polyb(points, tile);
+    }
+
+    pub fn thick_line(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        width: f32,
+        tile: Tile,
+        line_cap: LineCap,
+    ) {
+        self.canvas
+            .thick_line(x1, y1, x2, y2, width, tile, line_cap);
+    }
+
+    pub fn arc(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        tile: Tile,
+    ) {
+        self.canvas.arc(x, y, radius, start_angle, end_angle, tile);
+    }
+
+    pub fn pie(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        tile: Tile,
+    ) {
+        self.canvas.pie(x, y, radius, start_angle, end_angle, tile);
+    }
+
+    pub fn bezier(&mut self, points: &[(f32, f32)], tile: Tile) {
+        self.canvas.bezier(points, tile);
+    }
+
     pub fn fill(&mut self, x: f32, y: f32, tile: Tile) {
         self.canvas.fill(x, y, tile);
     }
@@ -317,124 +410,86 @@ impl Tilemap {
         dy: f32,
         walls: &[Tile],
     ) -> (f32, f32) {
-        let mut ndx = dx;
-        let mut ndy = dy;
-        let x_first = dx.abs() >= dy.abs();
+        let collision = self.sweep(x, y, w, h, dx, dy, walls);
+        (collision.dx, collision.dy)
+    }
+}
 
-        let mut cur_x = x;
-        let mut cur_y = y;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tileset::Tileset;
+
+    #[test]
+    fn test_tile_attrs() {
+        let tilemap = Tilemap::new(1, 1, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        let last = TILE_SIZE as i32 - 1;
+        let base = TILE_SIZE as i32;
+        assert_eq!(tilemap.tile_pixel((1, 2, 0), 1, 0), (base + 1, base * 2));
+        assert_eq!(
+            tilemap.tile_pixel((1, 0, TILE_FLIP_X), 1, 0),
+            (base + last - 1, 0)
+        );
+        assert_eq!(tilemap.tile_pixel((0, 0, TILE_FLIP_Y), 1, 0), (1, last));
+        assert_eq!(tilemap.tile_pixel((0, 0, TILE_ROTATE), last, 0), (0, 0));
+        assert_eq!(tilemap.tile_pixel((0, 0, TILE_ROTATE), 0, 1), (1, last));
+        assert_eq!(
+            tilemap.tile_pixel((0, 0, TILE_ROTATE | TILE_FLIP_X), 0, 0),
+            (0, 0)
+        );
 
-        if x_first {
-            ndx = self.collide_resolve_x(cur_x, cur_y, w, h, ndx, walls);
-            cur_x += ndx;
-            ndy = self.collide_resolve_y(cur_x, cur_y, w, h, ndy, walls);
-        } else {
-            ndy = self.collide_resolve_y(cur_x, cur_y, w, h, ndy, walls);
-            cur_y += ndy;
-            ndx = self.collide_resolve_x(cur_x, cur_y, w, h, ndx, walls);
-        }
+        tilemap.tile_width = 8;
+        tilemap.tile_height = 16;
+        assert_eq!(tilemap.tile_pixel((1, 2, 0), 1, 3), (9, 35));
+        assert_eq!(tilemap.tile_pixel((0, 1, TILE_FLIP_Y), 1, 0), (1, 31));
+        // TILE_ROTATE is documented to be ignored on non-square tiles
+        assert_eq!(tilemap.tile_pixel((0, 0, TILE_ROTATE), 1, 3), (1, 3));
 
-        (ndx, ndy)
+        assert_eq!(tile_color((0, 0, 0), 3), 3);
+        assert_eq!(tile_color((0, 0, 2 << TILE_PALETTE_SHIFT), 3), 35);
     }
 
-    fn collide_resolve_x(&self, x: f32, y: f32, w: f32, h: f32, dx: f32, walls: &[Tile]) -> f32 {
-        if dx == 0.0 {
-            return dx;
-        }
+    #[test]
+    fn test_tile_data_layout() {
+        let tilemap = Tilemap::new(2, 1, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.pset(1.0, 0.0, (4, 5, 6));
 
-        let tile_size = TILE_SIZE as f32;
-        let ty0 = (y / tile_size).floor() as i32;
-        let ty1 = ((y + h - 1.0) / tile_size).floor() as i32;
-
-        if dx > 0.0 {
-            let cur_right = x + w - 1.0;
-            let new_right = x + dx + w - 1.0;
-            let start_tx = (cur_right / tile_size).floor() as i32 + 1;
-            let end_tx = (new_right / tile_size).floor() as i32;
-
-            if start_tx <= end_tx {
-                for tx in start_tx..=end_tx {
-                    for ty in ty0..=ty1 {
-                        if self.collide_is_wall(tx, ty, walls) {
-                            return tx as f32 * tile_size - w - x;
-                        }
-                    }
-                }
-            }
-        } else {
-            let cur_left = x;
-            let new_left = x + dx;
-            let start_tx = (cur_left / tile_size).floor() as i32 - 1;
-            let end_tx = (new_left / tile_size).floor() as i32;
-
-            if start_tx >= end_tx {
-                for tx in (end_tx..=start_tx).rev() {
-                    for ty in ty0..=ty1 {
-                        if self.collide_is_wall(tx, ty, walls) {
-                            return (tx + 1) as f32 * tile_size - x;
-                        }
-                    }
-                }
-            }
-        }
-
-        dx
+        // data_ptr and tile_data_ptr in Python expose tiles as x, y and attr bytes
+        let bytes = unsafe { std::slice::from_raw_parts(tilemap.data_ptr() as *const u8, 6) };
+        assert_eq!(bytes, [0, 0, 0, 4, 5, 6]);
     }
 
-    fn collide_resolve_y(&self, x: f32, y: f32, w: f32, h: f32, dy: f32, walls: &[Tile]) -> f32 {
-        if dy == 0.0 {
-            return dy;
-        }
-
-        let tile_size = TILE_SIZE as f32;
-        let tx0 = (x / tile_size).floor() as i32;
-        let tx1 = ((x + w - 1.0) / tile_size).floor() as i32;
-
-        if dy > 0.0 {
-            let cur_bottom = y + h - 1.0;
-            let new_bottom = y + dy + h - 1.0;
-            let start_ty = (cur_bottom / tile_size).floor() as i32 + 1;
-            let end_ty = (new_bottom / tile_size).floor() as i32;
-
-            if start_ty <= end_ty {
-                for ty in start_ty..=end_ty {
-                    for tx in tx0..=tx1 {
-                        if self.collide_is_wall(tx, ty, walls) {
-                            return ty as f32 * tile_size - h - y;
-                        }
-                    }
-                }
-            }
-        } else {
-            let cur_top = y;
-            let new_top = y + dy;
-            let start_ty = (cur_top / tile_size).floor() as i32 - 1;
-            let end_ty = (new_top / tile_size).floor() as i32;
-
-            if start_ty >= end_ty {
-                for ty in (end_ty..=start_ty).rev() {
-                    for tx in tx0..=tx1 {
-                        if self.collide_is_wall(tx, ty, walls) {
-                            return (ty + 1) as f32 * tile_size - y;
-                        }
-                    }
-                }
-            }
-        }
+    #[test]
+    fn test_collide_with_tileset() {
+        let tilemap = Tilemap::new(4, 4, ImageSource::Index(0));
+        let mut tilemap = tilemap.lock();
+        tilemap.pset(2.0, 0.0, (1, 0, 0));
+        tilemap.pset(0.0, 2.0, (2, 0, 0));
+
+        let size = TILE_SIZE as f32;
+        assert_eq!(
+            tilemap.collide(0.0, 0.0, size, size, 12.0, 0.0, &[]),
+            (12.0, 0.0)
+        );
 
-        dy
-    }
+        let tileset = Tileset::new();
+        tileset.lock().tile_mut((1, 0, 0)).solid = true;
+        tileset.lock().tile_mut((2, 0, 0)).one_way = true;
+        tilemap.tileset = Some(tileset);
 
-    fn collide_is_wall(&self, tx: i32, ty: i32, walls: &[Tile]) -> bool {
-        if tx < 0 || ty < 0 {
-            return false;
-        }
-        let width = self.canvas.width() as i32;
-        let height = self.canvas.height() as i32;
-        if tx >= width || ty >= height {
-            return false;
-        }
-        let tile = self.canvas.read_data(tx as usize, ty as usize);
-        walls.contains(&tile)
+        assert_eq!(
+            tilemap.collide(0.0, 0.0, size, size, 12.0, 0.0, &[]),
+            (size, 0.0)
+        );
+        assert_eq!(
+            tilemap.collide(0.0, 0.0, size, size, 0.0, 12.0, &[]),
+            (0.0, size)
+        );
+        assert_eq!(
+            tilemap.collide(0.0, size * 3.0, size, size, 0.0, -12.0, &[]),
+            (0.0, -12.0)
+        );
     }
 }
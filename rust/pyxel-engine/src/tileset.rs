@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use crate::tilemap::{Tile, TileCoord};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileFrame {
+    pub tile: TileCoord,
+    pub duration: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileInfo {
+    pub frames: Vec<TileFrame>,
+    pub solid: bool,
+    pub one_way: bool,
+    pub slope: Option<(f32, f32)>,
+    pub damage: i32,
+    pub friction: f32,
+}
+
+impl Default for TileInfo {
+    fn default() -> Self {
+        Self {
+            frames: Vec::new(),
+            solid: false,
+            one_way: false,
+            slope: None,
+            damage: 0,
+            friction: 1.0,
+        }
+    }
+}
+
+impl TileInfo {
+    fn frame_at(&self, frame_count: u32) -> Option<TileCoord> {
+        let total: u32 = self.frames.iter().map(|frame| frame.duration.max(1)).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut tick = frame_count % total;
+        for frame in &self.frames {
+            let duration = frame.duration.max(1);
+            if tick < duration {
+                return Some(frame.tile);
+            }
+            tick -= duration;
+        }
+        None
+    }
+}
+
+pub struct Tileset {
+    pub tiles: HashMap<TileCoord, TileInfo>,
+}
+
+pub type SharedTileset = shared_type!(Tileset);
+
+impl Tileset {
+    pub fn new() -> SharedTileset {
+        new_shared_type!(Self {
+            tiles: HashMap::new(),
+        })
+    }
+
+    pub fn tile(&self, tile: Tile) -> Option<&TileInfo> {
+        self.tiles.get(&(tile.0, tile.1))
+    }
+
+    pub fn tile_mut(&mut self, tile: Tile) -> &mut TileInfo {
+        self.tiles.entry((tile.0, tile.1)).or_default()
+    }
+
+    pub fn is_solid(&self, tile: Tile) -> bool {
+        self.tile(tile).is_some_and(|info| info.solid)
+    }
+
+    pub fn is_one_way(&self, tile: Tile) -> bool {
+        self.tile(tile).is_some_and(|info| info.one_way)
+    }
+
+    pub(crate) fn has_animations(&self) -> bool {
+        self.tiles.values().any(|info| !info.frames.is_empty())
+    }
+
+    // Animated tiles keep the attributes of the placed tile and advance in sync
+    pub fn animate(&self, tile: Tile, frame_count: u32) -> Tile {
+        self.tile(tile)
+            .and_then(|info| info.frame_at(frame_count))
+            .map_or(tile, |(x, y)| (x, y, tile.2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::TILE_FLIP_X;
+
+    #[test]
+    fn test_tileset() {
+        let tileset = Tileset::new();
+        let mut tileset = tileset.lock();
+        let water = tileset.tile_mut((1, 0, 0));
+        water.solid = true;
+        water.frames = vec![
+            TileFrame {
+                tile: (1, 0),
+                duration: 2,
+            },
+            TileFrame {
+                tile: (2, 0),
+                duration: 1,
+            },
+        ];
+
+        assert!(tileset.is_solid((1, 0, TILE_FLIP_X)));
+        assert!(!tileset.is_solid((0, 0, 0)));
+        assert!(!tileset.is_one_way((1, 0, 0)));
+        assert_eq!(tileset.tile((3, 3, 0)), None);
+
+        let tiles: Vec<Tile> = (0..4)
+            .map(|frame_count| tileset.animate((1, 0, TILE_FLIP_X), frame_count))
+            .collect();
+        assert_eq!(
+            tiles,
+            vec![
+                (1, 0, TILE_FLIP_X),
+                (1, 0, TILE_FLIP_X),
+                (2, 0, TILE_FLIP_X),
+                (1, 0, TILE_FLIP_X)
+            ]
+        );
+        assert_eq!(tileset.animate((5, 5, 0), 3), (5, 5, 0));
+    }
+}
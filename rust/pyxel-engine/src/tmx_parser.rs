@@ -1,110 +1,707 @@
-use std::fs::File;
+use std::collections::HashMap;
+use std::fs;
 use std::io::Read;
+use std::path::Path;
 
+use flate2::read::{GzDecoder, ZlibDecoder};
+use indexmap::IndexMap;
 use serde::Deserialize;
 
-use crate::settings::TILE_SIZE;
-use crate::tilemap::{ImageSource, Tilemap};
-use crate::utils::remove_whitespace;
+use crate::settings::{TILE_FLIP_X, TILE_FLIP_Y, TILE_ROTATE};
+use crate::tilemap::{ImageSource, TileAttr, TileCoord, Tilemap};
+use crate::utils::{decode_base64, remove_whitespace};
 use crate::SharedTilemap;
 
+const GID_FLIP_HORIZONTAL: u32 = 0x8000_0000;
+const GID_FLIP_VERTICAL: u32 = 0x4000_0000;
+const GID_FLIP_DIAGONAL: u32 = 0x2000_0000;
+const GID_MASK: u32 = 0x0fff_ffff;
+
+#[derive(Debug, Deserialize, Default)]
+struct PropertiesData {
+    #[serde(rename = "property", default)]
+    properties: Vec<PropertyData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PropertyData {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@type")]
+    property_type: Option<String>,
+    #[serde(rename = "@value")]
+    value: Option<String>,
+    #[serde(rename = "#text")]
+    text: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
-struct Tileset {
+struct TileData {
+    #[serde(rename = "@id")]
+    id: u32,
+    properties: Option<PropertiesData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TilesetData {
     #[serde(rename = "@firstgid")]
-    firstgid: u32,
+    firstgid: Option<u32>,
+    #[serde(rename = "@source")]
+    source: Option<String>,
     #[serde(rename = "@columns")]
     columns: Option<u32>,
+    #[serde(rename = "tile", default)]
+    tiles: Vec<TileData>,
 }
 
 #[derive(Debug, Deserialize)]
-struct LayerData {
+struct LayerDataData {
     #[serde(rename = "@encoding")]
-    encoding: String,
+    encoding: Option<String>,
+    #[serde(rename = "@compression")]
+    compression: Option<String>,
     #[serde(rename = "#text")]
-    tiles: String,
+    tiles: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-struct Layer {
+struct LayerData {
+    #[serde(rename = "@name", default)]
+    name: String,
     #[serde(rename = "@width")]
     width: u32,
     #[serde(rename = "@height")]
     height: u32,
-    data: LayerData,
+    #[serde(rename = "@visible")]
+    visible: Option<u32>,
+    properties: Option<PropertiesData>,
+    data: LayerDataData,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmptyData {}
+
+#[derive(Debug, Deserialize)]
+struct PointsData {
+    #[serde(rename = "@points")]
+    points: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ObjectData {
+    #[serde(rename = "@id", default)]
+    id: u32,
+    #[serde(rename = "@name", default)]
+    name: String,
+    #[serde(rename = "@type")]
+    object_type: Option<String>,
+    #[serde(rename = "@class")]
+    class: Option<String>,
+    #[serde(rename = "@x", default)]
+    x: f32,
+    #[serde(rename = "@y", default)]
+    y: f32,
+    #[serde(rename = "@width", default)]
+    width: f32,
+    #[serde(rename = "@height", default)]
+    height: f32,
+    #[serde(rename = "@rotation", default)]
+    rotation: f32,
+    #[serde(rename = "@gid")]
+    gid: Option<u32>,
+    ellipse: Option<EmptyData>,
+    point: Option<EmptyData>,
+    polygon: Option<PointsData>,
+    polyline: Option<PointsData>,
+    properties: Option<PropertiesData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ObjectGroupData {
+    #[serde(rename = "@name", default)]
+    name: String,
+    #[serde(rename = "@visible")]
+    visible: Option<u32>,
+    properties: Option<PropertiesData>,
+    #[serde(rename = "object", default)]
+    objects: Vec<ObjectData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupData {
+    #[serde(rename = "@visible")]
+    visible: Option<u32>,
+    #[serde(rename = "#content", default)]
+    elements: Vec<MapElement>,
+}
+
+#[derive(Debug, Deserialize)]
+enum MapElement {
+    #[serde(rename = "properties")]
+    Properties(PropertiesData),
+    #[serde(rename = "tileset")]
+    Tileset(TilesetData),
+    #[serde(rename = "layer")]
+    Layer(LayerData),
+    #[serde(rename = "objectgroup")]
+    ObjectGroup(ObjectGroupData),
+    #[serde(rename = "group")]
+    Group(GroupData),
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename = "map")]
 struct TiledMapFile {
+    #[serde(rename = "@width")]
+    width: u32,
+    #[serde(rename = "@height")]
+    height: u32,
     #[serde(rename = "@tilewidth")]
     tilewidth: u32,
     #[serde(rename = "@tileheight")]
     tileheight: u32,
-    #[serde(rename = "tileset", default)]
-    tilesets: Vec<Tileset>,
-    #[serde(rename = "layer", default)]
-    layers: Vec<Layer>,
+    #[serde(rename = "#content", default)]
+    elements: Vec<MapElement>,
 }
 
-pub fn parse_tmx(filename: &str, layer_index: u32) -> Result<SharedTilemap, String> {
-    let mut file = File::open(filename).map_err(|_| format!("Failed to open file '{filename}'"))?;
+#[derive(Clone, Debug, PartialEq)]
+pub enum TmxProperty {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+pub type TmxProperties = IndexMap<String, TmxProperty>;
+
+#[derive(Clone)]
+pub struct TmxTileset {
+    pub first_gid: u32,
+    pub columns: u32,
+    pub tile_properties: HashMap<TileCoord, TmxProperties>,
+}
 
-    let mut tmx_text = String::new();
-    file.read_to_string(&mut tmx_text)
-        .map_err(|_| "Failed to read TMX file".to_string())?;
+#[derive(Clone)]
+pub struct TmxTileLayer {
+    pub name: String,
+    pub visible: bool,
+    pub grouped: bool,
+    pub tilemap: SharedTilemap,
+    pub properties: TmxProperties,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TmxShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    Polygon(Vec<(f32, f32)>),
+    Polyline(Vec<(f32, f32)>),
+}
 
+#[derive(Clone, Debug)]
+pub struct TmxObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub rotation: f32,
+    pub gid: Option<u32>,
+    pub shape: TmxShape,
+    pub properties: TmxProperties,
+}
+
+#[derive(Clone, Debug)]
+pub struct TmxObjectGroup {
+    pub name: String,
+    pub visible: bool,
+    pub objects: Vec<TmxObject>,
+    pub properties: TmxProperties,
+}
+
+#[derive(Clone)]
+pub struct TmxMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub properties: TmxProperties,
+    pub tilesets: Vec<TmxTileset>,
+    pub layers: Vec<TmxTileLayer>,
+    pub object_groups: Vec<TmxObjectGroup>,
+}
+
+impl TmxMap {
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let tmx_text = fs::read_to_string(filename)
+            .map_err(|_| format!("Failed to open file '{filename}'"))?;
+        let base_dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        parse_tmx_text(&tmx_text, base_dir)
+    }
+}
+
+// Layers inside groups are skipped so that layer indices keep counting top-level layers only
+pub fn parse_tmx(filename: &str, layer_index: u32) -> Result<SharedTilemap, String> {
+    let tmx = TmxMap::load(filename)?;
+    tmx.layers
+        .iter()
+        .filter(|layer| !layer.grouped)
+        .nth(layer_index as usize)
+        .map(|layer| layer.tilemap.clone())
+        .ok_or_else(|| format!("Layer {layer_index} not found in TMX file"))
+}
+
+fn parse_tmx_text(tmx_text: &str, base_dir: &Path) -> Result<TmxMap, String> {
     let tmx: TiledMapFile =
-        serde_xml_rs::from_str(&tmx_text).map_err(|_| "Failed to parse TMX file".to_string())?;
+        serde_xml_rs::from_str(tmx_text).map_err(|_| "Failed to parse TMX file".to_string())?;
 
-    if tmx.tilewidth != TILE_SIZE || tmx.tileheight != TILE_SIZE {
-        return Err(format!(
-            "TMX file's tile size is not {TILE_SIZE}x{TILE_SIZE}"
-        ));
+    if tmx.tilewidth == 0 || tmx.tileheight == 0 {
+        return Err("TMX file's tile size is zero".to_string());
     }
 
-    if tmx.tilesets.is_empty() {
+    let mut tmx_map = TmxMap {
+        width: tmx.width,
+        height: tmx.height,
+        tile_width: tmx.tilewidth,
+        tile_height: tmx.tileheight,
+        properties: TmxProperties::new(),
+        tilesets: Vec::new(),
+        layers: Vec::new(),
+        object_groups: Vec::new(),
+    };
+
+    for element in &tmx.elements {
+        match element {
+            MapElement::Properties(properties) => tmx_map.properties = parse_properties(properties),
+            MapElement::Tileset(tileset) => {
+                let tileset = load_tileset(tileset, base_dir)?;
+                tmx_map.tilesets.push(tileset);
+            }
+            _ => {}
+        }
+    }
+    if tmx_map.tilesets.is_empty() {
         return Err("Tileset not found in TMX file".to_string());
     }
 
-    let tileset = &tmx.tilesets[0];
-    let tileset_columns = tileset
+    add_layers(&mut tmx_map, &tmx.elements, true, false)?;
+    Ok(tmx_map)
+}
+
+fn load_tileset(tileset: &TilesetData, base_dir: &Path) -> Result<TmxTileset, String> {
+    let first_gid = tileset.firstgid.unwrap_or(1);
+
+    let external_tileset;
+    let tileset = if let Some(source) = &tileset.source {
+        let path = base_dir.join(source);
+        let tsx_text = fs::read_to_string(&path)
+            .map_err(|_| format!("Failed to open file '{}'", path.display()))?;
+        external_tileset = serde_xml_rs::from_str::<TilesetData>(&tsx_text)
+            .map_err(|_| "Failed to parse TSX file".to_string())?;
+        &external_tileset
+    } else {
+        tileset
+    };
+
+    let columns = tileset
         .columns
-        .ok_or_else(|| "Tileset is not embedded in TMX file".to_string())?;
+        .filter(|&columns| columns > 0)
+        .ok_or_else(|| "Tileset has no columns in TMX file".to_string())?;
+    let tile_properties = tileset
+        .tiles
+        .iter()
+        .filter_map(|tile| {
+            let properties = tile.properties.as_ref()?;
+            let tile_coord = ((tile.id % columns) as u8, (tile.id / columns) as u8);
+            Some((tile_coord, parse_properties(properties)))
+        })
+        .collect();
 
-    if layer_index >= tmx.layers.len() as u32 {
-        return Err(format!("Layer {layer_index} not found in TMX file"));
-    }
+    Ok(TmxTileset {
+        first_gid,
+        columns,
+        tile_properties,
+    })
+}
 
-    let layer = &tmx.layers[layer_index as usize];
-    if layer.data.encoding != "csv" {
-        return Err("TMX file's encoding is not CSV".to_string());
+fn add_layers(
+    tmx_map: &mut TmxMap,
+    elements: &[MapElement],
+    visible: bool,
+    grouped: bool,
+) -> Result<(), String> {
+    for element in elements {
+        match element {
+            MapElement::Layer(layer) => {
+                let tile_size = (tmx_map.tile_width, tmx_map.tile_height);
+                let mut layer = parse_tile_layer(layer, &tmx_map.tilesets, tile_size, visible)?;
+                layer.grouped = grouped;
+                tmx_map.layers.push(layer);
+            }
+            MapElement::ObjectGroup(object_group) => {
+                tmx_map
+                    .object_groups
+                    .push(parse_object_group(object_group, visible));
+            }
+            MapElement::Group(group) => {
+                add_layers(
+                    tmx_map,
+                    &group.elements,
+                    visible && group.visible != Some(0),
+                    true,
+                )?;
+            }
+            _ => {}
+        }
     }
+    Ok(())
+}
 
-    let layer_data: Vec<u32> = remove_whitespace(&layer.data.tiles)
-        .split(',')
-        .map(|s| {
-            s.parse::<u32>()
-                .map_err(|_| "Failed to parse CSV tile data".to_string())
-        })
-        .collect::<Result<_, _>>()?;
+fn parse_tile_layer(
+    layer: &LayerData,
+    tilesets: &[TmxTileset],
+    (tile_width, tile_height): (u32, u32),
+    visible: bool,
+) -> Result<TmxTileLayer, String> {
+    let gids = parse_layer_data(&layer.data)?;
+    if gids.len() < (layer.width * layer.height) as usize {
+        return Err("TMX layer data is shorter than the layer size".to_string());
+    }
 
+    let mut imgsrc = None;
     let tilemap = Tilemap::new(layer.width, layer.height, ImageSource::Index(0));
     {
         let mut tilemap = tilemap.lock();
-        for (i, tile_id) in layer_data.iter().enumerate() {
+        tilemap.tile_width = tile_width;
+        tilemap.tile_height = tile_height;
+        for (i, raw_gid) in gids
+            .iter()
+            .enumerate()
+            .take((layer.width * layer.height) as usize)
+        {
             let x = i % layer.width as usize;
             let y = i / layer.width as usize;
+            let gid = raw_gid & GID_MASK;
+
+            let tileset_index = tilesets
+                .iter()
+                .rposition(|tileset| tileset.first_gid <= gid)
+                .unwrap_or(0);
+            let tileset = &tilesets[tileset_index];
+            let tile_id = gid.saturating_sub(tileset.first_gid);
 
-            let tile_id = if *tile_id > tileset.firstgid {
-                tile_id - tileset.firstgid
-            } else {
-                0
+            // A tilemap draws from a single image, so every tile must come from one tileset
+            if gid != 0 {
+                match imgsrc {
+                    None => imgsrc = Some(tileset_index as u32),
+                    Some(index) if index != tileset_index as u32 => {
+                        return Err(format!(
+                            "TMX layer '{}' uses tiles from multiple tilesets",
+                            layer.name
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+
+            let tile_x = (tile_id % tileset.columns) as u8;
+            let tile_y = (tile_id / tileset.columns) as u8;
+            tilemap
+                .canvas
+                .write_data(x, y, (tile_x, tile_y, tile_attr(*raw_gid)));
+        }
+        tilemap.imgsrc = ImageSource::Index(imgsrc.unwrap_or(0));
+    }
+
+    Ok(TmxTileLayer {
+        name: layer.name.clone(),
+        visible: visible && layer.visible != Some(0),
+        grouped: false,
+        tilemap,
+        properties: layer
+            .properties
+            .as_ref()
+            .map(parse_properties)
+            .unwrap_or_default(),
+    })
+}
+
+fn parse_layer_data(data: &LayerDataData) -> Result<Vec<u32>, String> {
+    let tiles = data
+        .tiles
+        .as_ref()
+        .ok_or_else(|| "Infinite TMX maps are not supported".to_string())?;
+
+    match data.encoding.as_deref() {
+        Some("csv") => remove_whitespace(tiles)
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<u32>()
+                    .map_err(|_| "Failed to parse CSV tile data".to_string())
+            })
+            .collect(),
+        Some("base64") => {
+            let bytes = decode_base64(tiles).map_err(|_| "Failed to decode base64 tile data")?;
+            let mut decompressed = Vec::new();
+            let bytes = match data.compression.as_deref() {
+                None => bytes,
+                Some("zlib") => {
+                    ZlibDecoder::new(&bytes[..])
+                        .read_to_end(&mut decompressed)
+                        .map_err(|_| "Failed to decompress TMX tile data".to_string())?;
+                    decompressed
+                }
+                Some("gzip") => {
+                    GzDecoder::new(&bytes[..])
+                        .read_to_end(&mut decompressed)
+                        .map_err(|_| "Failed to decompress TMX tile data".to_string())?;
+                    decompressed
+                }
+                Some(compression) => {
+                    return Err(format!("TMX compression '{compression}' is not supported"))
+                }
             };
-            let tile_x = (tile_id % tileset_columns) as u8;
-            let tile_y = (tile_id / tileset_columns) as u8;
-            tilemap.canvas.write_data(x, y, (tile_x, tile_y));
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect())
         }
+        _ => Err("TMX file's encoding is not CSV or base64".to_string()),
+    }
+}
+
+// Tiled flips diagonally before flipping horizontally and vertically, while tiles rotate
+// clockwise before flipping, so a diagonal flip becomes a rotation with a horizontal flip
+fn tile_attr(raw_gid: u32) -> TileAttr {
+    let flip_horizontal = raw_gid & GID_FLIP_HORIZONTAL != 0;
+    let flip_vertical = raw_gid & GID_FLIP_VERTICAL != 0;
+    let flip_diagonal = raw_gid & GID_FLIP_DIAGONAL != 0;
+
+    let mut attr = 0;
+    if flip_horizontal != flip_diagonal {
+        attr |= TILE_FLIP_X;
+    }
+    if flip_vertical {
+        attr |= TILE_FLIP_Y;
+    }
+    if flip_diagonal {
+        attr |= TILE_ROTATE;
     }
+    attr
+}
+
+fn parse_object_group(object_group: &ObjectGroupData, visible: bool) -> TmxObjectGroup {
+    TmxObjectGroup {
+        name: object_group.name.clone(),
+        visible: visible && object_group.visible != Some(0),
+        objects: object_group.objects.iter().map(parse_object).collect(),
+        properties: object_group
+            .properties
+            .as_ref()
+            .map(parse_properties)
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_object(object: &ObjectData) -> TmxObject {
+    let shape = if object.ellipse.is_some() {
+        TmxShape::Ellipse
+    } else if object.point.is_some() {
+        TmxShape::Point
+    } else if let Some(polygon) = &object.polygon {
+        TmxShape::Polygon(parse_points(&polygon.points))
+    } else if let Some(polyline) = &object.polyline {
+        TmxShape::Polyline(parse_points(&polyline.points))
+    } else {
+        TmxShape::Rectangle
+    };
+
+    TmxObject {
+        id: object.id,
+        name: object.name.clone(),
+        class: object
+            .class
+            .clone()
+            .or_else(|| object.object_type.clone())
+            .unwrap_or_default(),
+        x: object.x,
+        y: object.y,
+        width: object.width,
+        height: object.height,
+        rotation: object.rotation,
+        gid: object.gid.map(|gid| gid & GID_MASK),
+        shape,
+        properties: object
+            .properties
+            .as_ref()
+            .map(parse_properties)
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_points(points: &str) -> Vec<(f32, f32)> {
+    points
+        .split_whitespace()
+        .filter_map(|point| {
+            let (x, y) = point.split_once(',')?;
+            Some((x.parse().ok()?, y.parse().ok()?))
+        })
+        .collect()
+}
 
-    Ok(tilemap)
+fn parse_properties(properties: &PropertiesData) -> TmxProperties {
+    properties
+        .properties
+        .iter()
+        .map(|property| {
+            let value = property
+                .value
+                .clone()
+                .or_else(|| property.text.clone())
+                .unwrap_or_default();
+            let value = match property.property_type.as_deref() {
+                Some("bool") => TmxProperty::Bool(value == "true"),
+                Some("int" | "object") => value
+                    .parse()
+                    .map_or(TmxProperty::String(value), TmxProperty::Int),
+                Some("float") => value
+                    .parse()
+                    .map_or(TmxProperty::String(value), TmxProperty::Float),
+                _ => TmxProperty::String(value),
+            };
+            (property.name.clone(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMX_TEXT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" width="2" height="2" tilewidth="8" tileheight="16" infinite="0">
+ <properties>
+  <property name="title" value="Test"/>
+ </properties>
+ <tileset firstgid="1" name="a" tilewidth="8" tileheight="16" columns="4">
+  <image source="a.png" width="32" height="32"/>
+  <tile id="5">
+   <properties>
+    <property name="solid" type="bool" value="true"/>
+   </properties>
+  </tile>
+ </tileset>
+ <tileset firstgid="17" name="b" tilewidth="8" tileheight="16" columns="2"/>
+ <layer id="1" name="ground" width="2" height="2">
+  <data encoding="csv">
+6,0,
+2147483654,1610612737
+</data>
+ </layer>
+ <objectgroup id="2" name="objects">
+  <object id="1" name="spawn" type="player" x="4" y="8">
+   <point/>
+  </object>
+  <object id="2" x="0" y="0">
+   <polygon points="0,0 8,0 8,8"/>
+   <properties>
+    <property name="damage" type="int" value="3"/>
+    <property name="speed" type="float" value="1.5"/>
+   </properties>
+  </object>
+  <object id="3" x="1" y="2" width="3" height="4"/>
+ </objectgroup>
+ <group id="3" name="hidden" visible="0">
+  <layer id="4" name="upper" width="2" height="2">
+   <data encoding="base64">EgAAABMAAAAAAAAAAAAAAA==</data>
+  </layer>
+ </group>
+</map>"#;
+
+    #[test]
+    fn test_parse_tmx_text() {
+        let tmx = parse_tmx_text(TMX_TEXT, Path::new("")).unwrap();
+        assert_eq!((tmx.width, tmx.height), (2, 2));
+        assert_eq!((tmx.tile_width, tmx.tile_height), (8, 16));
+        assert_eq!(
+            tmx.properties["title"],
+            TmxProperty::String("Test".to_string())
+        );
+        assert_eq!(tmx.tilesets.len(), 2);
+        assert_eq!(
+            tmx.tilesets[0].tile_properties[&(1, 1)]["solid"],
+            TmxProperty::Bool(true)
+        );
+
+        let ground = &tmx.layers[0];
+        let tilemap = ground.tilemap.lock();
+        assert_eq!((tilemap.tile_width, tilemap.tile_height), (8, 16));
+        assert_eq!(tilemap.canvas.read_data(0, 0), (1, 1, 0));
+        assert_eq!(tilemap.canvas.read_data(0, 1), (1, 1, TILE_FLIP_X));
+        assert_eq!(
+            tilemap.canvas.read_data(1, 1),
+            (0, 0, TILE_FLIP_X | TILE_FLIP_Y | TILE_ROTATE)
+        );
+        assert!(ground.visible);
+        assert!(!ground.grouped);
+
+        let upper = &tmx.layers[1];
+        assert!(!upper.visible);
+        assert!(upper.grouped);
+        assert!(matches!(upper.tilemap.lock().imgsrc, ImageSource::Index(1)));
+        assert_eq!(upper.tilemap.lock().canvas.read_data(1, 0), (0, 1, 0));
+
+        let objects = &tmx.object_groups[0].objects;
+        assert_eq!(objects[0].shape, TmxShape::Point);
+        assert_eq!(objects[0].class, "player");
+        assert_eq!(
+            objects[1].shape,
+            TmxShape::Polygon(vec![(0.0, 0.0), (8.0, 0.0), (8.0, 8.0)])
+        );
+        assert_eq!(objects[1].properties["damage"], TmxProperty::Int(3));
+        assert_eq!(objects[1].properties["speed"], TmxProperty::Float(1.5));
+        assert_eq!(objects[2].shape, TmxShape::Rectangle);
+        assert_eq!(objects[2].height, 4.0);
+    }
+
+    #[test]
+    fn test_parse_tmx_layer_index() {
+        let path = std::env::temp_dir().join(format!("pyxel_tmx_{}.tmx", std::process::id()));
+        fs::write(&path, TMX_TEXT).unwrap();
+        let filename = path.to_string_lossy();
+        let ground = parse_tmx(&filename, 0);
+        let grouped = parse_tmx(&filename, 1);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(ground.unwrap().lock().canvas.read_data(0, 0), (1, 1, 0));
+        assert!(grouped.is_err());
+    }
+
+    #[test]
+    fn test_parse_tmx_mixed_tilesets() {
+        let tmx_text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" width="2" height="1" tilewidth="8" tileheight="8" infinite="0">
+ <tileset firstgid="1" name="a" tilewidth="8" tileheight="8" columns="4"/>
+ <tileset firstgid="17" name="b" tilewidth="8" tileheight="8" columns="2"/>
+ <layer id="1" name="mixed" width="2" height="1">
+  <data encoding="csv">2,18</data>
+ </layer>
+</map>"#;
+        let result = parse_tmx_text(tmx_text, Path::new(""));
+        assert_eq!(
+            result.err().unwrap(),
+            "TMX layer 'mixed' uses tiles from multiple tilesets"
+        );
+
+        let tmx = parse_tmx_text(&tmx_text.replace("2,18", "0,18"), Path::new("")).unwrap();
+        let tilemap = tmx.layers[0].tilemap.lock();
+        assert!(matches!(tilemap.imgsrc, ImageSource::Index(1)));
+        assert_eq!(tilemap.canvas.read_data(1, 0), (1, 0, 0));
+    }
 }
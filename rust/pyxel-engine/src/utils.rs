@@ -60,6 +60,31 @@ pub fn parse_hex_string(string: &str) -> Result<u32, &str> {
     Ok(result)
 }
 
+pub fn decode_base64(string: &str) -> Result<Vec<u8>, &str> {
+    let mut result = Vec::with_capacity(string.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut num_bits = 0;
+
+    for c in remove_whitespace(string).trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err("invalid base64 string"),
+        };
+        buffer = (buffer << 6) | value as u32;
+        num_bits += 6;
+        if num_bits >= 8 {
+            num_bits -= 8;
+            result.push((buffer >> num_bits) as u8);
+        }
+    }
+
+    Ok(result)
+}
+
 pub fn add_file_extension(filename: &str, ext: &str) -> String {
     if filename.to_lowercase().ends_with(ext) {
         filename.to_string()
@@ -134,6 +159,13 @@ pub fn trim_empty_vecs<T: Clone>(vecs: &[Vec<T>]) -> Vec<Vec<T>> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("AQIDBA==").unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(decode_base64("SGVs\nbG8").unwrap(), b"Hello".to_vec());
+        assert!(decode_base64("SGV*").is_err());
+    }
+
     #[test]
     fn test_string_loop() {
         let test_string = "TEST_STRING";
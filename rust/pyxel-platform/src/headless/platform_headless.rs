@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+use std::mem::take;
+
+use glow::Context;
+
+use crate::event::Event;
+use crate::platform::GLProfile;
+
+const DISPLAY_WIDTH: u32 = 1920;
+const DISPLAY_HEIGHT: u32 = 1080;
+
+type AudioCallback = Box<dyn FnMut(&mut [i16])>;
+
+pub struct AudioSink {
+    pub callback: Option<AudioCallback>,
+    pub sample_rate: u32,
+    pub paused: bool,
+    pub capture_enabled: bool,
+    pub sample_carry: f64,
+    pub captured_samples: Vec<i16>,
+}
+
+pub struct PlatformHeadless {
+    pub ticks_ms: f64,
+    pub quit_requested: bool,
+    pub window_title: String,
+    pub window_x: i32,
+    pub window_y: i32,
+    pub window_width: u32,
+    pub window_height: u32,
+    pub fullscreen: bool,
+    pub mouse_x: i32,
+    pub mouse_y: i32,
+    pub mouse_visible: bool,
+    pub scripted_events: VecDeque<Vec<Event>>,
+    pub audio_sink: AudioSink,
+}
+
+impl PlatformHeadless {
+    pub fn new() -> Self {
+        Self {
+            ticks_ms: 0.0,
+            quit_requested: false,
+            window_title: String::new(),
+            window_x: 0,
+            window_y: 0,
+            window_width: 0,
+            window_height: 0,
+            fullscreen: false,
+            mouse_x: i32::MIN,
+            mouse_y: i32::MIN,
+            mouse_visible: true,
+            scripted_events: VecDeque::new(),
+            audio_sink: AudioSink {
+                callback: None,
+                sample_rate: 0,
+                paused: true,
+                capture_enabled: false,
+                sample_carry: 0.0,
+                captured_samples: Vec::new(),
+            },
+        }
+    }
+
+    //
+    // Core
+    //
+    pub fn init(&mut self) {
+        self.ticks_ms = 0.0;
+        self.quit_requested = false;
+    }
+
+    pub fn quit(&mut self) {
+        self.quit_requested = true;
+        self.pause_audio(true);
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.ticks_ms as u32
+    }
+
+    pub fn export_browser_file(&self, _filename: &str) {
+        // Do nothing
+    }
+
+    //
+    // Window
+    //
+    pub fn init_window(&mut self, title: &str, width: u32, height: u32) {
+        self.window_title = title.to_string();
+        self.window_width = width;
+        self.window_height = height;
+    }
+
+    pub fn window_pos(&mut self) -> (i32, i32) {
+        (self.window_x, self.window_y)
+    }
+
+    pub fn set_window_pos(&mut self, x: i32, y: i32) {
+        self.window_x = x;
+        self.window_y = y;
+    }
+
+    pub fn window_size(&mut self) -> (u32, u32) {
+        (self.window_width, self.window_height)
+    }
+
+    pub fn set_window_size(&mut self, width: u32, height: u32) {
+        self.window_width = width;
+        self.window_height = height;
+    }
+
+    pub fn set_window_title(&mut self, title: &str) {
+        self.window_title = title.to_string();
+    }
+
+    pub fn set_window_icon(&mut self, _width: u32, _height: u32, _rgba: &[u8]) {
+        // Do nothing
+    }
+
+    pub fn is_fullscreen(&mut self) -> bool {
+        self.fullscreen
+    }
+
+    pub fn set_fullscreen(&mut self, enabled: bool) {
+        self.fullscreen = enabled;
+    }
+
+    pub fn set_mouse_pos(&mut self, x: i32, y: i32) {
+        self.mouse_x = x;
+        self.mouse_y = y;
+    }
+
+    pub fn set_mouse_visible(&mut self, visible: bool) {
+        self.mouse_visible = visible;
+    }
+
+    pub fn display_size(&self) -> (u32, u32) {
+        (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+    }
+
+    //
+    // Audio
+    //
+    pub fn start_audio<F: FnMut(&mut [i16]) + 'static>(
+        &mut self,
+        sample_rate: u32,
+        _buffer_size: u32,
+        callback: F,
+    ) {
+        let sink = &mut self.audio_sink;
+        sink.callback = Some(Box::new(callback));
+        sink.sample_rate = sample_rate;
+        sink.sample_carry = 0.0;
+        self.pause_audio(false);
+    }
+
+    pub fn pause_audio(&mut self, paused: bool) {
+        self.audio_sink.paused = paused;
+    }
+
+    pub fn set_audio_capture(&mut self, enabled: bool) {
+        self.audio_sink.capture_enabled = enabled;
+        self.audio_sink.sample_carry = 0.0;
+    }
+
+    pub fn take_captured_audio(&mut self) -> Vec<i16> {
+        take(&mut self.audio_sink.captured_samples)
+    }
+
+    fn render_audio(&mut self, elapsed_ms: f64) {
+        let sink = &mut self.audio_sink;
+        if sink.paused || !sink.capture_enabled {
+            return;
+        }
+        let Some(callback) = sink.callback.as_mut() else {
+            return;
+        };
+
+        let num_samples = sink.sample_carry + sink.sample_rate as f64 * elapsed_ms / 1000.0;
+        sink.sample_carry = num_samples.fract();
+
+        let start = sink.captured_samples.len();
+        sink.captured_samples
+            .resize(start + num_samples as usize, 0);
+        callback(&mut sink.captured_samples[start..]);
+    }
+
+    //
+    // Frame
+    //
+    pub fn run_frame_loop<F: FnMut(f32)>(&mut self, fps: u32, mut callback: F) {
+        let frame_ms = 1000.0 / fps as f32;
+
+        while !self.quit_requested {
+            callback(frame_ms);
+            self.advance_clock(frame_ms as f64);
+        }
+    }
+
+    pub fn step_frame(&mut self, fps: u32) {
+        self.advance_clock(1000.0 / fps as f64);
+    }
+
+    pub fn advance_clock(&mut self, elapsed_ms: f64) {
+        self.ticks_ms += elapsed_ms;
+        self.render_audio(elapsed_ms);
+    }
+
+    pub fn push_events(&mut self, events: Vec<Event>) {
+        self.scripted_events.push_back(events);
+    }
+
+    pub fn poll_events(&mut self) -> Vec<Event> {
+        self.scripted_events.pop_front().unwrap_or_default()
+    }
+
+    pub fn gl_profile(&self) -> GLProfile {
+        GLProfile::None
+    }
+
+    pub fn gl_context(&mut self) -> &'static mut Context {
+        panic!("OpenGL context is not available in headless mode");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::KEY_A;
+
+    #[test]
+    fn test_virtual_clock() {
+        let mut platform = PlatformHeadless::new();
+        platform.init();
+        assert_eq!(platform.ticks(), 0);
+
+        platform.step_frame(30);
+        platform.step_frame(30);
+        platform.step_frame(30);
+        assert_eq!(platform.ticks(), 100);
+
+        platform.advance_clock(0.5);
+        assert_eq!(platform.ticks(), 100);
+
+        platform.init_window("test", 64, 32);
+        assert_eq!(platform.window_size(), (64, 32));
+        platform.quit();
+        platform.run_frame_loop(30, |_| panic!("Frame loop ran after quit"));
+    }
+
+    #[test]
+    fn test_scripted_events() {
+        let mut platform = PlatformHeadless::new();
+        platform.push_events(vec![Event::KeyPressed { key: KEY_A }]);
+        platform.push_events(Vec::new());
+        platform.push_events(vec![Event::Quit]);
+
+        assert!(matches!(
+            platform.poll_events().as_slice(),
+            [Event::KeyPressed { key: KEY_A }]
+        ));
+        assert!(platform.poll_events().is_empty());
+        assert!(matches!(platform.poll_events().as_slice(), [Event::Quit]));
+        assert!(platform.poll_events().is_empty());
+    }
+
+    #[test]
+    fn test_audio_capture() {
+        let mut platform = PlatformHeadless::new();
+        platform.start_audio(1000, 64, |samples: &mut [i16]| samples.fill(7));
+
+        platform.advance_clock(10.0);
+        assert!(platform.take_captured_audio().is_empty());
+
+        platform.set_audio_capture(true);
+        platform.advance_clock(10.0);
+        platform.advance_clock(2.5);
+        platform.advance_clock(2.5);
+        let samples = platform.take_captured_audio();
+        assert_eq!(samples.len(), 15);
+        assert!(samples.iter().all(|&sample| sample == 7));
+
+        platform.pause_audio(true);
+        platform.advance_clock(10.0);
+        assert!(platform.take_captured_audio().is_empty());
+    }
+}
@@ -16,11 +16,15 @@
 pub mod key;
 
 mod event;
+#[cfg(feature = "headless")]
+mod headless;
 mod platform;
-#[cfg(feature = "sdl2")]
+#[cfg(all(feature = "sdl2", not(feature = "headless")))]
 mod sdl2;
 
 pub use event::Event;
+#[cfg(feature = "headless")]
+pub use platform::{advance_clock, push_events, set_audio_capture, take_captured_audio};
 pub use platform::{
     display_size, export_browser_file, gl_context, gl_profile, init, init_window, is_fullscreen,
     pause_audio, poll_events, quit, run_frame_loop, set_fullscreen, set_mouse_pos,
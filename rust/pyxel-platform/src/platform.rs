@@ -4,7 +4,9 @@ use std::ptr::null_mut;
 use glow::Context;
 
 use crate::event::Event;
-#[cfg(feature = "sdl2")]
+#[cfg(feature = "headless")]
+use crate::headless::platform_headless::PlatformHeadless as Platform;
+#[cfg(all(feature = "sdl2", not(feature = "headless")))]
 use crate::sdl2::platform_sdl2::PlatformSdl2 as Platform;
 
 #[derive(PartialEq)]
@@ -133,3 +135,26 @@ pub fn gl_profile() -> GLProfile {
 pub fn gl_context() -> &'static mut Context {
     platform().gl_context()
 }
+
+//
+// Headless
+//
+#[cfg(feature = "headless")]
+pub fn push_events(events: Vec<Event>) {
+    platform().push_events(events);
+}
+
+#[cfg(feature = "headless")]
+pub fn advance_clock(elapsed_ms: f64) {
+    platform().advance_clock(elapsed_ms);
+}
+
+#[cfg(feature = "headless")]
+pub fn set_audio_capture(enabled: bool) {
+    platform().set_audio_capture(enabled);
+}
+
+#[cfg(feature = "headless")]
+pub fn take_captured_audio() -> Vec<i16> {
+    platform().take_captured_audio()
+}
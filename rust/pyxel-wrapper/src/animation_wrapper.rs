@@ -0,0 +1,95 @@
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use crate::pyxel_singleton::pyxel;
+
+#[pyclass(from_py_object)]
+#[derive(Clone)]
+pub struct Animation {
+    pub(crate) inner: pyxel::SharedAnimation,
+}
+
+impl Animation {
+    pub fn wrap(inner: pyxel::SharedAnimation) -> Self {
+        Self { inner }
+    }
+}
+
+#[pymethods]
+impl Animation {
+    #[new]
+    pub fn new() -> Self {
+        Self::wrap(pyxel::Animation::new())
+    }
+
+    #[getter]
+    pub fn clip(&self) -> Option<String> {
+        self.inner.lock().clip.clone()
+    }
+
+    #[getter]
+    pub fn clips(&self) -> Vec<String> {
+        self.inner.lock().clips.keys().cloned().collect()
+    }
+
+    #[getter]
+    pub fn events(&self) -> Vec<String> {
+        self.inner.lock().events.clone()
+    }
+
+    #[getter]
+    pub fn finished(&self) -> bool {
+        self.inner.lock().is_finished()
+    }
+
+    #[pyo3(signature = (clip, img, u, v, w, h, duration, events=None))]
+    pub fn add_frame(
+        &self,
+        clip: &str,
+        img: u32,
+        u: f32,
+        v: f32,
+        w: f32,
+        h: f32,
+        duration: u32,
+        events: Option<Vec<String>>,
+    ) {
+        self.inner.lock().add_frame(
+            clip,
+            pyxel::AnimationFrame {
+                image: img,
+                image_x: u,
+                image_y: v,
+                width: w,
+                height: h,
+                duration,
+                events: events.unwrap_or_default(),
+            },
+        );
+    }
+
+    pub fn set_mode(&self, clip: &str, mode: u32) -> PyResult<()> {
+        self.inner
+            .lock()
+            .set_mode(clip, pyxel::AnimationMode::from(mode))
+            .map_err(PyException::new_err)
+    }
+
+    #[pyo3(signature = (clip, restart=None))]
+    pub fn play(&self, clip: &str, restart: Option<bool>) -> PyResult<()> {
+        self.inner
+            .lock()
+            .play(clip, restart.unwrap_or(false))
+            .map_err(PyException::new_err)
+    }
+
+    #[pyo3(signature = (x, y, colkey=None))]
+    pub fn draw(&self, x: f32, y: f32, colkey: Option<pyxel::Color>) {
+        pyxel().draw_animation(x, y, &mut self.inner.lock(), colkey);
+    }
+}
+
+pub fn add_animation_class(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Animation>()?;
+    Ok(())
+}
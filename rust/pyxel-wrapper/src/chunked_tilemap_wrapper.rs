@@ -0,0 +1,269 @@
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::image_wrapper::Image;
+use crate::tilemap_wrapper::{collision_to_dict, to_tiles, TileTuple, Tilemap};
+use crate::tileset_wrapper::Tileset;
+
+#[pyclass(from_py_object)]
+#[derive(Clone)]
+pub struct ChunkedTilemap {
+    pub(crate) inner: pyxel::SharedChunkedTilemap,
+}
+
+impl ChunkedTilemap {
+    pub fn wrap(inner: pyxel::SharedChunkedTilemap) -> Self {
+        Self { inner }
+    }
+}
+
+#[pymethods]
+impl ChunkedTilemap {
+    #[new]
+    #[pyo3(signature = (chunk_size, img, tile_width=None, tile_height=None))]
+    pub fn new(
+        chunk_size: u32,
+        img: Bound<'_, PyAny>,
+        tile_width: Option<u32>,
+        tile_height: Option<u32>,
+    ) -> PyResult<Self> {
+        let imgsrc = cast_pyany! {
+            img,
+            (u32, { pyxel::ImageSource::Index(img) }),
+            (Image, { pyxel::ImageSource::Image(img.inner) })
+        };
+        let tilemap = Self::wrap(pyxel::ChunkedTilemap::new(chunk_size, imgsrc));
+        if let Some(tile_width) = tile_width {
+            tilemap.set_tile_width(tile_width)?;
+        }
+        if let Some(tile_height) = tile_height {
+            tilemap.set_tile_height(tile_height)?;
+        }
+        Ok(tilemap)
+    }
+
+    #[getter]
+    pub fn chunk_size(&self) -> u32 {
+        self.inner.lock().chunk_size()
+    }
+
+    #[getter]
+    pub fn imgsrc(&self, py: Python) -> Py<PyAny> {
+        let tilemap = self.inner.lock();
+        match &tilemap.imgsrc {
+            pyxel::ImageSource::Index(index) => value_to_pyobj!(py, index),
+            pyxel::ImageSource::Image(image) => class_to_pyobj!(py, Image::wrap(image.clone())),
+        }
+    }
+
+    #[setter]
+    pub fn set_imgsrc(&self, img: Bound<'_, PyAny>) -> PyResult<()> {
+        let imgsrc = cast_pyany! {
+            img,
+            (u32, { pyxel::ImageSource::Index(img) }),
+            (Image, { pyxel::ImageSource::Image(img.inner) })
+        };
+        self.inner.lock().imgsrc = imgsrc;
+        Ok(())
+    }
+
+    #[getter]
+    pub fn tile_width(&self) -> u32 {
+        self.inner.lock().tile_width
+    }
+
+    #[setter]
+    pub fn set_tile_width(&self, tile_width: u32) -> PyResult<()> {
+        if tile_width == 0 {
+            return Err(PyException::new_err("Tile width must be positive"));
+        }
+        self.inner.lock().tile_width = tile_width;
+        Ok(())
+    }
+
+    #[getter]
+    pub fn tile_height(&self) -> u32 {
+        self.inner.lock().tile_height
+    }
+
+    #[setter]
+    pub fn set_tile_height(&self, tile_height: u32) -> PyResult<()> {
+        if tile_height == 0 {
+            return Err(PyException::new_err("Tile height must be positive"));
+        }
+        self.inner.lock().tile_height = tile_height;
+        Ok(())
+    }
+
+    #[getter]
+    pub fn tileset(&self) -> Option<Tileset> {
+        self.inner.lock().tileset.clone().map(Tileset::wrap)
+    }
+
+    #[setter]
+    pub fn set_tileset(&self, tileset: Option<Tileset>) {
+        self.inner.lock().tileset = tileset.map(|tileset| tileset.inner);
+    }
+
+    #[getter]
+    pub fn chunk_dir(&self) -> Option<String> {
+        self.inner.lock().chunk_dir.clone()
+    }
+
+    #[setter]
+    pub fn set_chunk_dir(&self, chunk_dir: Option<String>) {
+        let mut inner = self.inner.lock();
+        inner.chunk_dir = chunk_dir;
+        inner.invalidate_chunks();
+    }
+
+    // Callbacks run while the chunked tilemap is locked, so they must not access it
+    pub fn set_loader(&self, loader: Option<Bound<'_, PyAny>>) {
+        let mut inner = self.inner.lock();
+        inner.invalidate_chunks();
+        inner.loader = loader.map(|loader| {
+            let loader = loader.unbind();
+            Box::new(move |(chunk_x, chunk_y)| {
+                Python::attach(|py| {
+                    let tilemap = loader
+                        .call1(py, (chunk_x, chunk_y))
+                        .map_err(|err| err.to_string())?;
+                    tilemap
+                        .extract::<Option<Tilemap>>(py)
+                        .map(|tilemap| tilemap.map(|tilemap| tilemap.inner))
+                        .map_err(|_| "Chunk loader must return a Tilemap or None".to_string())
+                })
+            }) as pyxel::ChunkLoader
+        });
+    }
+
+    pub fn set_saver(&self, saver: Option<Bound<'_, PyAny>>) {
+        self.inner.lock().saver = saver.map(|saver| {
+            let saver = saver.unbind();
+            Box::new(move |(chunk_x, chunk_y), chunk| {
+                Python::attach(|py| {
+                    saver
+                        .call1(py, (chunk_x, chunk_y, Tilemap::wrap(chunk)))
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                })
+            }) as pyxel::ChunkSaver
+        });
+    }
+
+    pub fn chunk(&self, chunk_x: i32, chunk_y: i32) -> PyResult<Tilemap> {
+        self.inner
+            .lock()
+            .chunk((chunk_x, chunk_y))
+            .map(Tilemap::wrap)
+            .map_err(PyException::new_err)
+    }
+
+    pub fn loaded_chunks(&self) -> Vec<pyxel::ChunkCoord> {
+        self.inner.lock().loaded_chunks()
+    }
+
+    pub fn load_area(&self, x: i32, y: i32, w: i32, h: i32) -> PyResult<()> {
+        self.inner
+            .lock()
+            .load_area(x, y, w, h)
+            .map_err(PyException::new_err)
+    }
+
+    pub fn unload_chunk(&self, chunk_x: i32, chunk_y: i32) -> PyResult<()> {
+        self.inner
+            .lock()
+            .unload_chunk((chunk_x, chunk_y))
+            .map_err(PyException::new_err)
+    }
+
+    pub fn unload_outside(&self, x: i32, y: i32, w: i32, h: i32) -> PyResult<()> {
+        self.inner
+            .lock()
+            .unload_outside(x, y, w, h)
+            .map_err(PyException::new_err)
+    }
+
+    #[pyo3(signature = (chunk_x=None, chunk_y=None))]
+    pub fn invalidate_chunks(&self, chunk_x: Option<i32>, chunk_y: Option<i32>) {
+        let mut inner = self.inner.lock();
+        match (chunk_x, chunk_y) {
+            (Some(chunk_x), Some(chunk_y)) => inner.invalidate_chunk((chunk_x, chunk_y)),
+            _ => inner.invalidate_chunks(),
+        }
+    }
+
+    pub fn save_chunks(&self) -> PyResult<()> {
+        self.inner
+            .lock()
+            .save_chunks()
+            .map_err(PyException::new_err)
+    }
+
+    pub fn pget(&self, x: i32, y: i32) -> PyResult<TileTuple> {
+        self.inner
+            .lock()
+            .pget(x, y)
+            .map(TileTuple::from)
+            .map_err(PyException::new_err)
+    }
+
+    pub fn pset(&self, x: i32, y: i32, tile: TileTuple) -> PyResult<()> {
+        self.inner
+            .lock()
+            .pset(x, y, tile.into())
+            .map_err(PyException::new_err)
+    }
+
+    pub fn rect(&self, x: i32, y: i32, w: i32, h: i32, tile: TileTuple) -> PyResult<()> {
+        self.inner
+            .lock()
+            .rect(x, y, w, h, tile.into())
+            .map_err(PyException::new_err)
+    }
+
+    #[pyo3(signature = (x, y, w, h, dx, dy, walls=None))]
+    pub fn collide(
+        &self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        dy: f32,
+        walls: Option<Vec<TileTuple>>,
+    ) -> PyResult<(f32, f32)> {
+        let walls = to_tiles(walls);
+        self.inner
+            .lock()
+            .collide(x, y, w, h, dx, dy, &walls)
+            .map_err(PyException::new_err)
+    }
+
+    #[pyo3(signature = (x, y, w, h, dx, dy, walls=None))]
+    pub fn sweep(
+        &self,
+        py: Python,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        dy: f32,
+        walls: Option<Vec<TileTuple>>,
+    ) -> PyResult<Py<PyDict>> {
+        let walls = to_tiles(walls);
+        let collision = self
+            .inner
+            .lock()
+            .sweep(x, y, w, h, dx, dy, &walls)
+            .map_err(PyException::new_err)?;
+        collision_to_dict(py, &collision)
+    }
+}
+
+pub fn add_chunked_tilemap_class(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<ChunkedTilemap>()?;
+    Ok(())
+}
@@ -44,6 +44,45 @@ pub fn add_module_constants(m: &Bound<'_, PyModule>) -> PyResult<()> {
     add_constant!(COLOR_PEACH)?;
     add_constant!(FONT_WIDTH)?;
     add_constant!(FONT_HEIGHT)?;
+    add_constant!(FILL_RULE_EVEN_ODD)?;
+    add_constant!(FILL_RULE_NONZERO)?;
+    add_constant!(LINE_CAP_BUTT)?;
+    add_constant!(LINE_CAP_ROUND)?;
+    add_constant!(LINE_CAP_SQUARE)?;
+    add_constant!(BLEND_NONE)?;
+    add_constant!(BLEND_ADD)?;
+    add_constant!(BLEND_SUBTRACT)?;
+    add_constant!(BLEND_MULTIPLY)?;
+    add_constant!(BLEND_AVERAGE)?;
+    add_constant!(ANIM_LOOP)?;
+    add_constant!(ANIM_PINGPONG)?;
+    add_constant!(ANIM_ONESHOT)?;
+    add_constant!(TILE_FLIP_X)?;
+    add_constant!(TILE_FLIP_Y)?;
+    add_constant!(TILE_ROTATE)?;
+    add_constant!(TILE_PALETTE_SHIFT)?;
+    add_constant!(DIAGONAL_NONE)?;
+    add_constant!(DIAGONAL_NO_CORNERS)?;
+    add_constant!(DIAGONAL_ALL)?;
+    add_constant!(TERRAIN_EDGES)?;
+    add_constant!(TERRAIN_BLOB)?;
+    add_constant!(TERRAIN_N)?;
+    add_constant!(TERRAIN_NE)?;
+    add_constant!(TERRAIN_E)?;
+    add_constant!(TERRAIN_SE)?;
+    add_constant!(TERRAIN_S)?;
+    add_constant!(TERRAIN_SW)?;
+    add_constant!(TERRAIN_W)?;
+    add_constant!(TERRAIN_NW)?;
+    add_constant!(ALIGN_LEFT)?;
+    add_constant!(ALIGN_CENTER)?;
+    add_constant!(ALIGN_RIGHT)?;
+    add_constant!(ALIGN_JUSTIFY)?;
+    add_constant!(QUANTIZE_MEDIAN_CUT)?;
+    add_constant!(QUANTIZE_KMEANS)?;
+    add_constant!(IMPORT_DITHER_NONE)?;
+    add_constant!(IMPORT_DITHER_FLOYD_STEINBERG)?;
+    add_constant!(IMPORT_DITHER_ORDERED)?;
 
     add_constant!(NUM_CHANNELS)?;
     add_constant!(NUM_TONES)?;
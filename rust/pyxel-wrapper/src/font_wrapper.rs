@@ -1,5 +1,11 @@
 use pyo3::prelude::*;
 
+use crate::image_wrapper::Image;
+use crate::pyxel_singleton::pyxel;
+
+pub type TextBoundsTuple = (i32, i32, i32, i32);
+pub type TextShadowTuple = (pyxel::Color, i32, i32);
+
 #[pyclass(from_py_object)]
 #[derive(Clone)]
 pub struct Font {
@@ -22,11 +28,103 @@ impl Font {
             .map_err(pyo3::exceptions::PyException::new_err)
     }
 
+    #[staticmethod]
+    pub fn builtin() -> Self {
+        Self::wrap(pyxel::Font::builtin())
+    }
+
+    #[staticmethod]
+    pub fn stack(fonts: Vec<Font>) -> Self {
+        Self::wrap(pyxel::Font::stack(&to_fonts(Some(fonts))))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (
+        img,
+        x,
+        y,
+        w,
+        h,
+        cell_w,
+        cell_h,
+        chars,
+        *,
+        variable_width=None,
+        multicolor=None,
+        colkey=None,
+        spacing=None
+    ))]
+    pub fn from_image(
+        img: Bound<'_, PyAny>,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+        cell_w: u32,
+        cell_h: u32,
+        chars: &str,
+        variable_width: Option<bool>,
+        multicolor: Option<bool>,
+        colkey: Option<pyxel::Color>,
+        spacing: Option<i32>,
+    ) -> PyResult<Self> {
+        let image = cast_pyany! {
+            img,
+            (u32, { pyxel().images.lock()[img as usize].clone() }),
+            (Image, { img.inner })
+        };
+        let image = image.lock();
+        pyxel::Font::from_image(
+            &image,
+            x,
+            y,
+            w,
+            h,
+            cell_w,
+            cell_h,
+            chars,
+            variable_width.unwrap_or(false),
+            multicolor.unwrap_or(false),
+            colkey.unwrap_or(0),
+            spacing,
+        )
+        .map(Self::wrap)
+        .map_err(pyo3::exceptions::PyException::new_err)
+    }
+
     pub fn text_width(&self, s: &str) -> i32 {
         self.inner.lock().text_width(s)
     }
 }
 
+pub(crate) fn to_fonts(fonts: Option<Vec<Font>>) -> Vec<pyxel::SharedFont> {
+    fonts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|font| font.inner)
+        .collect()
+}
+
+pub(crate) fn to_text_style(
+    w: Option<u32>,
+    align: Option<u32>,
+    line_spacing: Option<i32>,
+    shadow: Option<TextShadowTuple>,
+    outline: Option<pyxel::Color>,
+) -> pyxel::TextStyle {
+    pyxel::TextStyle {
+        width: w,
+        align: align.map_or(pyxel::TextAlign::Left, pyxel::TextAlign::from),
+        line_spacing: line_spacing.unwrap_or(0),
+        shadow,
+        outline,
+    }
+}
+
+pub(crate) fn bounds_to_tuple(bounds: pyxel::TextBounds) -> TextBoundsTuple {
+    (bounds.x, bounds.y, bounds.width, bounds.height)
+}
+
 pub fn add_font_class(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Font>()?;
     Ok(())
@@ -2,7 +2,10 @@ use std::sync::Once;
 
 use pyo3::prelude::*;
 
-use crate::font_wrapper::Font;
+use crate::chunked_tilemap_wrapper::ChunkedTilemap;
+use crate::font_wrapper::{
+    bounds_to_tuple, to_fonts, to_text_style, Font, TextBoundsTuple, TextShadowTuple,
+};
 use crate::image_wrapper::Image;
 use crate::pyxel_singleton::pyxel;
 use crate::tilemap_wrapper::Tilemap;
@@ -23,6 +26,32 @@ fn clip(x: Option<f32>, y: Option<f32>, w: Option<f32>, h: Option<f32>) -> PyRes
     Ok(())
 }
 
+#[pyfunction]
+#[pyo3(signature = (img=None, col=None, invert=None))]
+fn mask(
+    img: Option<Bound<'_, PyAny>>,
+    col: Option<pyxel::Color>,
+    invert: Option<bool>,
+) -> PyResult<()> {
+    let Some(img) = img else {
+        if (col, invert) != (None, None) {
+            python_type_error!("mask() requires img when col or invert is given");
+        }
+        pyxel().mask0();
+        return Ok(());
+    };
+    let invert = invert.unwrap_or(false);
+    cast_pyany! {
+        img,
+        (u32, {
+            let image = pyxel().images.lock()[img as usize].clone();
+            pyxel().mask(image, col, invert);
+        }),
+        (Image, { pyxel().mask(img.inner, col, invert); })
+    }
+    Ok(())
+}
+
 #[pyfunction]
 #[pyo3(signature = (x=None, y=None))]
 fn camera(x: Option<f32>, y: Option<f32>) -> PyResult<()> {
@@ -54,6 +83,11 @@ fn dither(alpha: f32) {
     pyxel().dither(alpha);
 }
 
+#[pyfunction]
+fn blend(mode: u32) {
+    pyxel().blend(pyxel::BlendMode::from(mode));
+}
+
 #[pyfunction]
 fn cls(col: pyxel::Color) {
     pyxel().cls(col);
@@ -114,6 +148,50 @@ fn trib(x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, col: pyxel::Color)
     pyxel().trib(x1, y1, x2, y2, x3, y3, col);
 }
 
+#[pyfunction]
+#[pyo3(signature = (pts, col, rule=None))]
+fn poly(pts: Vec<(f32, f32)>, col: pyxel::Color, rule: Option<u32>) {
+    pyxel().poly(
+        &pts,
+        col,
+        pyxel::FillRule::from(rule.unwrap_or(pyxel::FILL_RULE_EVEN_ODD)),
+    );
+}
+
+#[pyfunction]
+fn polyb(pts: Vec<(f32, f32)>, col: pyxel::Color) {
+    pyxel().polyb(&pts, col);
+}
+
+#[pyfunction]
+#[pyo3(signature = (x1, y1, x2, y2, w, col, cap=None))]
+fn thick_line(x1: f32, y1: f32, x2: f32, y2: f32, w: f32, col: pyxel::Color, cap: Option<u32>) {
+    pyxel().thick_line(
+        x1,
+        y1,
+        x2,
+        y2,
+        w,
+        col,
+        pyxel::LineCap::from(cap.unwrap_or(pyxel::LINE_CAP_BUTT)),
+    );
+}
+
+#[pyfunction]
+fn arc(x: f32, y: f32, r: f32, a1: f32, a2: f32, col: pyxel::Color) {
+    pyxel().arc(x, y, r, a1, a2, col);
+}
+
+#[pyfunction]
+fn pie(x: f32, y: f32, r: f32, a1: f32, a2: f32, col: pyxel::Color) {
+    pyxel().pie(x, y, r, a1, a2, col);
+}
+
+#[pyfunction]
+fn bezier(pts: Vec<(f32, f32)>, col: pyxel::Color) {
+    pyxel().bezier(&pts, col);
+}
+
 #[pyfunction]
 fn fill(x: f32, y: f32, col: pyxel::Color) {
     pyxel().fill(x, y, col);
@@ -141,6 +219,53 @@ fn blt(
     Ok(())
 }
 
+#[pyfunction]
+#[pyo3(signature = (x, y, img, u, v, w, h, colkey=None, z=None, flip_x=None, flip_y=None, rotate=None, scale=None, pal=None))]
+fn spr(
+    x: f32,
+    y: f32,
+    img: Bound<'_, PyAny>,
+    u: f32,
+    v: f32,
+    w: f32,
+    h: f32,
+    colkey: Option<pyxel::Color>,
+    z: Option<i32>,
+    flip_x: Option<bool>,
+    flip_y: Option<bool>,
+    rotate: Option<f32>,
+    scale: Option<f32>,
+    pal: Option<Vec<pyxel::Color>>,
+) -> PyResult<()> {
+    let image = cast_pyany! {
+        img,
+        (u32, { pyxel().images.lock()[img as usize].clone() }),
+        (Image, { img.inner })
+    };
+
+    let mut sprite = pyxel::Sprite::new(x, y, image, u, v, w, h);
+    sprite.color_key = colkey;
+    sprite.z = z.unwrap_or(0);
+    sprite.flip_x = flip_x.unwrap_or(false);
+    sprite.flip_y = flip_y.unwrap_or(false);
+    sprite.rotate = rotate.unwrap_or(0.0);
+    sprite.scale = scale.unwrap_or(1.0);
+    sprite.palette = pal;
+    pyxel().spr(sprite);
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (limit=None))]
+fn sprite_limit(limit: Option<u32>) {
+    pyxel().sprite_limit(limit);
+}
+
+#[pyfunction]
+fn flush_sprites() {
+    pyxel().flush_sprites();
+}
+
 #[pyfunction]
 #[pyo3(signature = (x, y, tm, u, v, w, h, colkey=None, rotate=None, scale=None))]
 fn bltm(
@@ -158,11 +283,118 @@ fn bltm(
     cast_pyany! {
         tm,
         (u32, { pyxel().bltm(x, y, tm, u, v, w, h, colkey, rotate, scale); }),
-        (Tilemap, { pyxel().screen.lock().bltm(x, y, tm.inner, u, v, w, h, colkey, rotate, scale); })
+        (Tilemap, { pyxel().screen.lock().bltm(x, y, tm.inner, u, v, w, h, colkey, rotate, scale); }),
+        (ChunkedTilemap, {
+            pyxel().screen.lock().bltm_chunked(x, y, tm.inner, u, v, w, h, colkey, rotate, scale);
+        })
+    }
+    Ok(())
+}
+
+pub type ScanlineTuple = Option<(f32, f32, f32, f32)>;
+
+pub fn to_scanline_transforms(lines: Vec<ScanlineTuple>) -> Vec<Option<pyxel::ScanlineTransform>> {
+    lines
+        .into_iter()
+        .map(|line| line.map(|(u, v, du, dv)| pyxel::ScanlineTransform { u, v, du, dv }))
+        .collect()
+}
+
+#[pyfunction]
+#[pyo3(signature = (x1, y1, x2, y2, x3, y3, img, u1, v1, u2, v2, u3, v3, colkey=None))]
+fn blt_tri(
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    x3: f32,
+    y3: f32,
+    img: Bound<'_, PyAny>,
+    u1: f32,
+    v1: f32,
+    u2: f32,
+    v2: f32,
+    u3: f32,
+    v3: f32,
+    colkey: Option<pyxel::Color>,
+) -> PyResult<()> {
+    let points = [(x1, y1), (x2, y2), (x3, y3)];
+    let uvs = [(u1, v1), (u2, v2), (u3, v3)];
+    cast_pyany! {
+        img,
+        (u32, { pyxel().blt_tri(points, img, uvs, colkey); }),
+        (Image, { pyxel().screen.lock().blt_tri(points, img.inner, uvs, colkey); })
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (x, y, w, h, img, lines, colkey=None, wrap=None))]
+fn blt_perspective(
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    img: Bound<'_, PyAny>,
+    lines: Vec<ScanlineTuple>,
+    colkey: Option<pyxel::Color>,
+    wrap: Option<bool>,
+) -> PyResult<()> {
+    let lines = to_scanline_transforms(lines);
+    let wrap = wrap.unwrap_or(false);
+    cast_pyany! {
+        img,
+        (u32, { pyxel().blt_perspective(x, y, w, h, img, &lines, colkey, wrap); }),
+        (Image, { pyxel().screen.lock().blt_perspective(x, y, w, h, img.inner, &lines, colkey, wrap); })
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (x, y, w, h, tm, lines, colkey=None, wrap=None))]
+fn bltm_perspective(
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    tm: Bound<'_, PyAny>,
+    lines: Vec<ScanlineTuple>,
+    colkey: Option<pyxel::Color>,
+    wrap: Option<bool>,
+) -> PyResult<()> {
+    let lines = to_scanline_transforms(lines);
+    let wrap = wrap.unwrap_or(false);
+    cast_pyany! {
+        tm,
+        (u32, { pyxel().bltm_perspective(x, y, w, h, tm, &lines, colkey, wrap); }),
+        (Tilemap, { pyxel().screen.lock().bltm_perspective(x, y, w, h, tm.inner, &lines, colkey, wrap); })
     }
     Ok(())
 }
 
+#[pyfunction]
+#[pyo3(signature = (w, h, x, y, height, pitch, yaw, fov=None))]
+fn mode7_lines(
+    w: u32,
+    h: u32,
+    x: f32,
+    y: f32,
+    height: f32,
+    pitch: f32,
+    yaw: f32,
+    fov: Option<f32>,
+) -> Vec<ScanlineTuple> {
+    let mut camera = pyxel::Mode7Camera::new(x, y, height, pitch, yaw);
+    if let Some(fov) = fov {
+        camera.fov = fov;
+    }
+    camera
+        .scanline_transforms(w, h)
+        .into_iter()
+        .map(|line| line.map(|line| (line.u, line.v, line.du, line.dv)))
+        .collect()
+}
+
 #[pyfunction]
 #[pyo3(signature = (x, y, s, col, font=None))]
 fn text(x: f32, y: f32, s: &str, col: pyxel::Color, font: Option<Font>) {
@@ -174,6 +406,93 @@ fn text(x: f32, y: f32, s: &str, col: pyxel::Color, font: Option<Font>) {
     pyxel().text(x, y, s, col, font);
 }
 
+#[pyfunction]
+#[pyo3(signature = (font=None))]
+fn default_font(font: Option<Font>) {
+    pyxel().default_font(font.map(|font| font.inner));
+}
+
+#[pyfunction]
+#[pyo3(signature = (x, y, s, col, *, font=None, fonts=None, w=None, align=None, line_spacing=None, shadow=None, outline=None))]
+fn text_box(
+    x: f32,
+    y: f32,
+    s: &str,
+    col: pyxel::Color,
+    font: Option<Font>,
+    fonts: Option<Vec<Font>>,
+    w: Option<u32>,
+    align: Option<u32>,
+    line_spacing: Option<i32>,
+    shadow: Option<TextShadowTuple>,
+    outline: Option<pyxel::Color>,
+) -> TextBoundsTuple {
+    let style = to_text_style(w, align, line_spacing, shadow, outline);
+    let bounds = pyxel().text_box(
+        x,
+        y,
+        s,
+        col,
+        font.map(|font| font.inner),
+        &to_fonts(fonts),
+        &style,
+    );
+    bounds_to_tuple(bounds)
+}
+
+#[pyfunction]
+#[pyo3(signature = (s, *, font=None, fonts=None, w=None, align=None, line_spacing=None, shadow=None, outline=None))]
+fn measure_text(
+    s: &str,
+    font: Option<Font>,
+    fonts: Option<Vec<Font>>,
+    w: Option<u32>,
+    align: Option<u32>,
+    line_spacing: Option<i32>,
+    shadow: Option<TextShadowTuple>,
+    outline: Option<pyxel::Color>,
+) -> TextBoundsTuple {
+    let style = to_text_style(w, align, line_spacing, shadow, outline);
+    let bounds = pyxel::measure_text(s, font.map(|font| font.inner), &to_fonts(fonts), &style);
+    bounds_to_tuple(bounds)
+}
+
+#[pyfunction]
+#[pyo3(signature = (col=None))]
+fn screen_transparent(col: Option<pyxel::Color>) {
+    pyxel().screen_transparent(col);
+}
+
+#[pyfunction]
+#[pyo3(signature = (y=None, colors=None, offset=None))]
+fn raster(y: Option<u32>, colors: Option<Vec<pyxel::Rgb24>>, offset: Option<i32>) -> PyResult<()> {
+    if let Some(y) = y {
+        pyxel().raster(y, colors, offset.unwrap_or(0));
+    } else if colors.is_none() && offset.is_none() {
+        pyxel().raster0();
+    } else {
+        python_type_error!("raster() takes 0 or 1 to 3 arguments");
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (start=None, end=None, speed=None))]
+fn pal_cycle(
+    start: Option<pyxel::Color>,
+    end: Option<pyxel::Color>,
+    speed: Option<f32>,
+) -> PyResult<()> {
+    if let (Some(start), Some(end), Some(speed)) = (start, end, speed) {
+        pyxel().pal_cycle(start, end, speed);
+    } else if (start, end, speed) == (None, None, None) {
+        pyxel().pal_cycle0();
+    } else {
+        python_type_error!("pal_cycle() takes 0 or 3 arguments");
+    }
+    Ok(())
+}
+
 #[pyfunction]
 fn image(img: u32) -> Image {
     IMAGE_ONCE.call_once(|| {
@@ -196,9 +515,11 @@ fn tilemap(tm: u32) -> Tilemap {
 
 pub fn add_graphics_functions(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(clip, m)?)?;
+    m.add_function(wrap_pyfunction!(mask, m)?)?;
     m.add_function(wrap_pyfunction!(camera, m)?)?;
     m.add_function(wrap_pyfunction!(pal, m)?)?;
     m.add_function(wrap_pyfunction!(dither, m)?)?;
+    m.add_function(wrap_pyfunction!(blend, m)?)?;
     m.add_function(wrap_pyfunction!(cls, m)?)?;
     m.add_function(wrap_pyfunction!(pget, m)?)?;
     m.add_function(wrap_pyfunction!(pset, m)?)?;
@@ -211,10 +532,29 @@ pub fn add_graphics_functions(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(ellib, m)?)?;
     m.add_function(wrap_pyfunction!(tri, m)?)?;
     m.add_function(wrap_pyfunction!(trib, m)?)?;
+    m.add_function(wrap_pyfunction!(poly, m)?)?;
+    m.add_function(wrap_pyfunction!(polyb, m)?)?;
+    m.add_function(wrap_pyfunction!(thick_line, m)?)?;
+    m.add_function(wrap_pyfunction!(arc, m)?)?;
+    m.add_function(wrap_pyfunction!(pie, m)?)?;
+    m.add_function(wrap_pyfunction!(bezier, m)?)?;
     m.add_function(wrap_pyfunction!(fill, m)?)?;
     m.add_function(wrap_pyfunction!(blt, m)?)?;
+    m.add_function(wrap_pyfunction!(spr, m)?)?;
+    m.add_function(wrap_pyfunction!(sprite_limit, m)?)?;
+    m.add_function(wrap_pyfunction!(flush_sprites, m)?)?;
     m.add_function(wrap_pyfunction!(bltm, m)?)?;
+    m.add_function(wrap_pyfunction!(blt_tri, m)?)?;
+    m.add_function(wrap_pyfunction!(blt_perspective, m)?)?;
+    m.add_function(wrap_pyfunction!(bltm_perspective, m)?)?;
+    m.add_function(wrap_pyfunction!(mode7_lines, m)?)?;
     m.add_function(wrap_pyfunction!(text, m)?)?;
+    m.add_function(wrap_pyfunction!(default_font, m)?)?;
+    m.add_function(wrap_pyfunction!(text_box, m)?)?;
+    m.add_function(wrap_pyfunction!(measure_text, m)?)?;
+    m.add_function(wrap_pyfunction!(screen_transparent, m)?)?;
+    m.add_function(wrap_pyfunction!(raster, m)?)?;
+    m.add_function(wrap_pyfunction!(pal_cycle, m)?)?;
 
     // Deprecated functions
     m.add_function(wrap_pyfunction!(image, m)?)?;
@@ -2,9 +2,13 @@ use std::ffi::CString;
 
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyBytes, PyDict};
 
-use crate::font_wrapper::Font;
+use crate::chunked_tilemap_wrapper::ChunkedTilemap;
+use crate::font_wrapper::{
+    bounds_to_tuple, to_fonts, to_text_style, Font, TextBoundsTuple, TextShadowTuple,
+};
+use crate::graphics_wrapper::{to_scanline_transforms, ScanlineTuple};
 use crate::pyxel_singleton::pyxel;
 use crate::tilemap_wrapper::Tilemap;
 
@@ -20,6 +24,116 @@ impl Image {
     }
 }
 
+impl Image {
+    fn import_image_with_report(
+        filename: &str,
+        include_colors: Option<bool>,
+        max_colors: Option<u32>,
+        quantize: Option<u32>,
+        dither: Option<u32>,
+        transparent: Option<pyxel::Color>,
+        perceptual: Option<bool>,
+    ) -> PyResult<(Self, pyxel::ImportReport)> {
+        let default_options = pyxel::ImportOptions::default();
+        let options = pyxel::ImportOptions {
+            include_colors: include_colors.unwrap_or(default_options.include_colors),
+            max_colors: max_colors.unwrap_or(default_options.max_colors),
+            method: quantize.map_or(default_options.method, pyxel::QuantizeMethod::from),
+            dither: dither.map_or(default_options.dither, pyxel::ImportDither::from),
+            transparent,
+            perceptual: perceptual.unwrap_or(default_options.perceptual),
+        };
+        pyxel::Image::import(filename, &options)
+            .map(|(image, report)| (Self::wrap(image), report))
+            .map_err(PyException::new_err)
+    }
+}
+
+fn aseprite_to_dict(py: Python, aseprite: &pyxel::Aseprite) -> PyResult<Py<PyDict>> {
+    let frames = aseprite
+        .frames
+        .iter()
+        .map(|frame| {
+            let dict = PyDict::new(py);
+            dict.set_item("x", frame.x)?;
+            dict.set_item("y", frame.y)?;
+            dict.set_item("duration", frame.duration)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let tags = aseprite
+        .tags
+        .iter()
+        .map(|tag| {
+            let dict = PyDict::new(py);
+            dict.set_item("name", &tag.name)?;
+            dict.set_item("from", tag.from)?;
+            dict.set_item("to", tag.to)?;
+            dict.set_item(
+                "direction",
+                match tag.direction {
+                    pyxel::TagDirection::Forward => "forward",
+                    pyxel::TagDirection::Reverse => "reverse",
+                    pyxel::TagDirection::PingPong => "pingpong",
+                    pyxel::TagDirection::PingPongReverse => "pingpong_reverse",
+                },
+            )?;
+            dict.set_item("repeat", tag.repeat)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let layers = aseprite
+        .layers
+        .iter()
+        .map(|layer| {
+            let dict = PyDict::new(py);
+            dict.set_item("name", &layer.name)?;
+            dict.set_item("visible", layer.visible)?;
+            dict.set_item("group", layer.is_group)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let slices = aseprite
+        .slices
+        .iter()
+        .map(|slice| {
+            let keys = slice
+                .keys
+                .iter()
+                .map(|key| {
+                    let dict = PyDict::new(py);
+                    dict.set_item("frame", key.frame)?;
+                    dict.set_item("x", key.x)?;
+                    dict.set_item("y", key.y)?;
+                    dict.set_item("w", key.width)?;
+                    dict.set_item("h", key.height)?;
+                    dict.set_item("center", key.center)?;
+                    dict.set_item("pivot", key.pivot)?;
+                    Ok(dict)
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            let dict = PyDict::new(py);
+            dict.set_item("name", &slice.name)?;
+            dict.set_item("keys", keys)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("width", aseprite.width)?;
+    dict.set_item("height", aseprite.height)?;
+    dict.set_item("frames", frames)?;
+    dict.set_item("tags", tags)?;
+    dict.set_item("layers", layers)?;
+    dict.set_item("slices", slices)?;
+    dict.set_item("colors", &aseprite.colors)?;
+    dict.set_item("transparent", aseprite.transparent)?;
+    Ok(dict.unbind())
+}
+
 #[pymethods]
 impl Image {
     #[new]
@@ -28,16 +142,94 @@ impl Image {
     }
 
     #[staticmethod]
-    #[pyo3(signature = (filename, *, include_colors=None, incl_colors=None))]
+    #[pyo3(signature = (
+        filename,
+        *,
+        include_colors=None,
+        incl_colors=None,
+        max_colors=None,
+        quantize=None,
+        dither=None,
+        transparent=None,
+        perceptual=None
+    ))]
     pub fn from_image(
         filename: &str,
         include_colors: Option<bool>,
         incl_colors: Option<bool>,
+        max_colors: Option<u32>,
+        quantize: Option<u32>,
+        dither: Option<u32>,
+        transparent: Option<pyxel::Color>,
+        perceptual: Option<bool>,
     ) -> PyResult<Self> {
+        Self::import_image_with_report(
+            filename,
+            include_colors.or(incl_colors),
+            max_colors,
+            quantize,
+            dither,
+            transparent,
+            perceptual,
+        )
+        .map(|(image, _)| image)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (
+        filename,
+        *,
+        include_colors=None,
+        incl_colors=None,
+        max_colors=None,
+        quantize=None,
+        dither=None,
+        transparent=None,
+        perceptual=None
+    ))]
+    pub fn import_image(
+        py: Python,
+        filename: &str,
+        include_colors: Option<bool>,
+        incl_colors: Option<bool>,
+        max_colors: Option<u32>,
+        quantize: Option<u32>,
+        dither: Option<u32>,
+        transparent: Option<pyxel::Color>,
+        perceptual: Option<bool>,
+    ) -> PyResult<(Self, Py<PyDict>)> {
+        let (image, report) = Self::import_image_with_report(
+            filename,
+            include_colors.or(incl_colors),
+            max_colors,
+            quantize,
+            dither,
+            transparent,
+            perceptual,
+        )?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("source_colors", report.num_source_colors)?;
+        dict.set_item("colors", report.num_colors)?;
+        dict.set_item("merged", report.merged_colors)?;
+        dict.set_item("transparent_pixels", report.num_transparent_pixels)?;
+        dict.set_item("transparent", report.transparent)?;
+        Ok((image, dict.unbind()))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (filename, *, layer=None, include_colors=None, incl_colors=None))]
+    pub fn from_aseprite(
+        py: Python,
+        filename: &str,
+        layer: Option<u32>,
+        include_colors: Option<bool>,
+        incl_colors: Option<bool>,
+    ) -> PyResult<(Self, Py<PyDict>)> {
         let include_colors = include_colors.or(incl_colors);
-        pyxel::Image::from_image(filename, include_colors)
-            .map(Self::wrap)
-            .map_err(PyException::new_err)
+        let (image, aseprite) = pyxel::Image::from_aseprite(filename, layer, include_colors)
+            .map_err(PyException::new_err)?;
+        Ok((Self::wrap(image), aseprite_to_dict(py, &aseprite)?))
     }
 
     #[getter]
@@ -84,6 +276,26 @@ impl Image {
             .map_err(PyException::new_err)
     }
 
+    #[pyo3(signature = (x, y, filename, *, layer=None, include_colors=None, incl_colors=None))]
+    pub fn load_aseprite(
+        &self,
+        py: Python,
+        x: i32,
+        y: i32,
+        filename: &str,
+        layer: Option<u32>,
+        include_colors: Option<bool>,
+        incl_colors: Option<bool>,
+    ) -> PyResult<Py<PyDict>> {
+        let include_colors = include_colors.or(incl_colors);
+        let aseprite = self
+            .inner
+            .lock()
+            .load_aseprite(x, y, filename, layer, include_colors)
+            .map_err(PyException::new_err)?;
+        aseprite_to_dict(py, &aseprite)
+    }
+
     pub fn save(&self, filename: &str, scale: u32) -> PyResult<()> {
         self.inner
             .lock()
@@ -91,6 +303,15 @@ impl Image {
             .map_err(PyException::new_err)
     }
 
+    #[pyo3(signature = (scale=None))]
+    pub fn to_rgba<'py>(&self, py: Python<'py>, scale: Option<u32>) -> Bound<'py, PyBytes> {
+        let rgba = self
+            .inner
+            .lock()
+            .to_rgba8(&pyxel().colors.lock(), scale.unwrap_or(1));
+        PyBytes::new(py, &rgba)
+    }
+
     #[pyo3(signature = (x=None, y=None, w=None, h=None))]
     pub fn clip(
         &self,
@@ -109,6 +330,32 @@ impl Image {
         Ok(())
     }
 
+    #[pyo3(signature = (img=None, col=None, invert=None))]
+    pub fn mask(
+        &self,
+        img: Option<Bound<'_, PyAny>>,
+        col: Option<pyxel::Color>,
+        invert: Option<bool>,
+    ) -> PyResult<()> {
+        let Some(img) = img else {
+            if (col, invert) != (None, None) {
+                python_type_error!("mask() requires img when col or invert is given");
+            }
+            self.inner.lock().mask0();
+            return Ok(());
+        };
+        let invert = invert.unwrap_or(false);
+        cast_pyany! {
+            img,
+            (u32, {
+                let image = pyxel().images.lock()[img as usize].clone();
+                self.inner.lock().mask(image, col, invert);
+            }),
+            (Image, { self.inner.lock().mask(img.inner, col, invert); })
+        }
+        Ok(())
+    }
+
     #[pyo3(signature = (x=None, y=None))]
     pub fn camera(&self, x: Option<f32>, y: Option<f32>) -> PyResult<()> {
         if let (Some(x), Some(y)) = (x, y) {
@@ -137,6 +384,10 @@ impl Image {
         self.inner.lock().dither(alpha);
     }
 
+    fn blend(&self, mode: u32) {
+        self.inner.lock().blend(pyxel::BlendMode::from(mode));
+    }
+
     pub fn cls(&self, col: pyxel::Color) {
         self.inner.lock().cls(col);
     }
@@ -185,6 +436,53 @@ impl Image {
         self.inner.lock().trib(x1, y1, x2, y2, x3, y3, col);
     }
 
+    #[pyo3(signature = (pts, col, rule=None))]
+    pub fn poly(&self, pts: Vec<(f32, f32)>, col: pyxel::Color, rule: Option<u32>) {
+        self.inner.lock().poly(
+            &pts,
+            col,
+            pyxel::FillRule::from(rule.unwrap_or(pyxel::FILL_RULE_EVEN_ODD)),
+        );
+    }
+
+    pub fn polyb(&self, pts: Vec<(f32, f32)>, col: pyxel::Color) {
+        self.inner.lock().polyb(&pts, col);
+    }
+
+    #[pyo3(signature = (x1, y1, x2, y2, w, col, cap=None))]
+    pub fn thick_line(
+        &self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        w: f32,
+        col: pyxel::Color,
+        cap: Option<u32>,
+    ) {
+        self.inner.lock().thick_line(
+            x1,
+            y1,
+            x2,
+            y2,
+            w,
+            col,
+            pyxel::LineCap::from(cap.unwrap_or(pyxel::LINE_CAP_BUTT)),
+        );
+    }
+
+    pub fn arc(&self, x: f32, y: f32, r: f32, a1: f32, a2: f32, col: pyxel::Color) {
+        self.inner.lock().arc(x, y, r, a1, a2, col);
+    }
+
+    pub fn pie(&self, x: f32, y: f32, r: f32, a1: f32, a2: f32, col: pyxel::Color) {
+        self.inner.lock().pie(x, y, r, a1, a2, col);
+    }
+
+    pub fn bezier(&self, pts: Vec<(f32, f32)>, col: pyxel::Color) {
+        self.inner.lock().bezier(&pts, col);
+    }
+
     pub fn fill(&self, x: f32, y: f32, col: pyxel::Color) {
         self.inner.lock().fill(x, y, col);
     }
@@ -234,7 +532,91 @@ impl Image {
                 let tilemap = pyxel().tilemaps.lock()[tm as usize].clone();
                 self.inner.lock().bltm(x, y, tilemap, u, v, w, h, colkey, rotate, scale);
             }),
-            (Tilemap, { self.inner.lock().bltm(x, y, tm.inner, u, v, w, h, colkey, rotate, scale); })
+            (Tilemap, { self.inner.lock().bltm(x, y, tm.inner, u, v, w, h, colkey, rotate, scale); }),
+            (ChunkedTilemap, {
+                self.inner.lock().bltm_chunked(x, y, tm.inner, u, v, w, h, colkey, rotate, scale);
+            })
+        }
+        Ok(())
+    }
+
+    #[pyo3(signature = (x1, y1, x2, y2, x3, y3, img, u1, v1, u2, v2, u3, v3, colkey=None))]
+    pub fn blt_tri(
+        &self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        x3: f32,
+        y3: f32,
+        img: Bound<'_, PyAny>,
+        u1: f32,
+        v1: f32,
+        u2: f32,
+        v2: f32,
+        u3: f32,
+        v3: f32,
+        colkey: Option<pyxel::Color>,
+    ) -> PyResult<()> {
+        let points = [(x1, y1), (x2, y2), (x3, y3)];
+        let uvs = [(u1, v1), (u2, v2), (u3, v3)];
+        cast_pyany! {
+            img,
+            (u32, {
+                let image = pyxel().images.lock()[img as usize].clone();
+                self.inner.lock().blt_tri(points, image, uvs, colkey);
+            }),
+            (Image, { self.inner.lock().blt_tri(points, img.inner, uvs, colkey); })
+        }
+        Ok(())
+    }
+
+    #[pyo3(signature = (x, y, w, h, img, lines, colkey=None, wrap=None))]
+    pub fn blt_perspective(
+        &self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        img: Bound<'_, PyAny>,
+        lines: Vec<ScanlineTuple>,
+        colkey: Option<pyxel::Color>,
+        wrap: Option<bool>,
+    ) -> PyResult<()> {
+        let lines = to_scanline_transforms(lines);
+        let wrap = wrap.unwrap_or(false);
+        cast_pyany! {
+            img,
+            (u32, {
+                let image = pyxel().images.lock()[img as usize].clone();
+                self.inner.lock().blt_perspective(x, y, w, h, image, &lines, colkey, wrap);
+            }),
+            (Image, { self.inner.lock().blt_perspective(x, y, w, h, img.inner, &lines, colkey, wrap); })
+        }
+        Ok(())
+    }
+
+    #[pyo3(signature = (x, y, w, h, tm, lines, colkey=None, wrap=None))]
+    pub fn bltm_perspective(
+        &self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        tm: Bound<'_, PyAny>,
+        lines: Vec<ScanlineTuple>,
+        colkey: Option<pyxel::Color>,
+        wrap: Option<bool>,
+    ) -> PyResult<()> {
+        let lines = to_scanline_transforms(lines);
+        let wrap = wrap.unwrap_or(false);
+        cast_pyany! {
+            tm,
+            (u32, {
+                let tilemap = pyxel().tilemaps.lock()[tm as usize].clone();
+                self.inner.lock().bltm_perspective(x, y, w, h, tilemap, &lines, colkey, wrap);
+            }),
+            (Tilemap, { self.inner.lock().bltm_perspective(x, y, w, h, tm.inner, &lines, colkey, wrap); })
         }
         Ok(())
     }
@@ -248,6 +630,34 @@ impl Image {
         };
         self.inner.lock().text(x, y, s, col, font);
     }
+
+    #[pyo3(signature = (x, y, s, col, *, font=None, fonts=None, w=None, align=None, line_spacing=None, shadow=None, outline=None))]
+    pub fn text_box(
+        &self,
+        x: f32,
+        y: f32,
+        s: &str,
+        col: pyxel::Color,
+        font: Option<Font>,
+        fonts: Option<Vec<Font>>,
+        w: Option<u32>,
+        align: Option<u32>,
+        line_spacing: Option<i32>,
+        shadow: Option<TextShadowTuple>,
+        outline: Option<pyxel::Color>,
+    ) -> TextBoundsTuple {
+        let style = to_text_style(w, align, line_spacing, shadow, outline);
+        let bounds = self.inner.lock().text_box(
+            x,
+            y,
+            s,
+            col,
+            font.map(|font| font.inner),
+            &to_fonts(fonts),
+            &style,
+        );
+        bounds_to_tuple(bounds)
+    }
 }
 
 pub fn add_image_class(m: &Bound<'_, PyModule>) -> PyResult<()> {
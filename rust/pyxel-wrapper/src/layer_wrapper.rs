@@ -0,0 +1,112 @@
+use pyo3::prelude::*;
+
+use crate::image_wrapper::Image;
+
+#[pyclass(from_py_object)]
+#[derive(Clone)]
+pub struct Layer {
+    pub(crate) inner: pyxel::SharedLayer,
+}
+
+impl Layer {
+    pub fn wrap(inner: pyxel::SharedLayer) -> Self {
+        Self { inner }
+    }
+}
+
+#[pymethods]
+impl Layer {
+    #[new]
+    pub fn new(img: Image) -> Self {
+        Self::wrap(pyxel::Layer::new(img.inner))
+    }
+
+    #[getter]
+    pub fn image(&self) -> Image {
+        Image::wrap(self.inner.lock().image.clone())
+    }
+
+    #[setter]
+    pub fn set_image(&self, image: Image) {
+        self.inner.lock().image = image.inner;
+    }
+
+    #[getter]
+    pub fn x(&self) -> i32 {
+        self.inner.lock().x
+    }
+
+    #[setter]
+    pub fn set_x(&self, x: i32) {
+        self.inner.lock().x = x;
+    }
+
+    #[getter]
+    pub fn y(&self) -> i32 {
+        self.inner.lock().y
+    }
+
+    #[setter]
+    pub fn set_y(&self, y: i32) {
+        self.inner.lock().y = y;
+    }
+
+    #[getter]
+    pub fn visible(&self) -> bool {
+        self.inner.lock().visible
+    }
+
+    #[setter]
+    pub fn set_visible(&self, visible: bool) {
+        self.inner.lock().visible = visible;
+    }
+
+    #[getter]
+    pub fn front(&self) -> bool {
+        self.inner.lock().front
+    }
+
+    #[setter]
+    pub fn set_front(&self, front: bool) {
+        self.inner.lock().front = front;
+    }
+
+    #[getter]
+    pub fn wrap_around(&self) -> bool {
+        self.inner.lock().wrap
+    }
+
+    #[setter]
+    pub fn set_wrap_around(&self, wrap: bool) {
+        self.inner.lock().wrap = wrap;
+    }
+
+    #[getter]
+    pub fn transparent(&self) -> Option<pyxel::Color> {
+        self.inner.lock().transparent
+    }
+
+    #[setter]
+    pub fn set_transparent(&self, transparent: Option<pyxel::Color>) {
+        self.inner.lock().transparent = transparent;
+    }
+
+    #[getter]
+    pub fn colors(&self) -> Option<Vec<pyxel::Rgb24>> {
+        self.inner.lock().colors.clone()
+    }
+
+    #[setter]
+    pub fn set_colors(&self, colors: Option<Vec<pyxel::Rgb24>>) {
+        self.inner.lock().colors = colors;
+    }
+
+    pub fn scroll(&self, x: i32, y: i32) {
+        self.inner.lock().scroll(x, y);
+    }
+}
+
+pub fn add_layer_class(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Layer>()?;
+    Ok(())
+}
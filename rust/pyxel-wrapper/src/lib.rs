@@ -14,20 +14,25 @@
 
 #[macro_use]
 mod utils;
+mod animation_wrapper;
 mod audio_wrapper;
 mod channel_wrapper;
+mod chunked_tilemap_wrapper;
 mod constant_wrapper;
 mod font_wrapper;
 mod graphics_wrapper;
 mod image_wrapper;
 mod input_wrapper;
+mod layer_wrapper;
 mod math_wrapper;
 mod music_wrapper;
 mod pyxel_singleton;
 mod resource_wrapper;
 mod sound_wrapper;
 mod system_wrapper;
+mod terrain_wrapper;
 mod tilemap_wrapper;
+mod tileset_wrapper;
 mod tone_wrapper;
 mod variable_wrapper;
 
@@ -38,6 +43,11 @@ fn pyxel_wrapper(_py: Python, m: Bound<'_, PyModule>) -> PyResult<()> {
     crate::font_wrapper::add_font_class(&m)?;
     crate::image_wrapper::add_image_class(&m)?;
     crate::tilemap_wrapper::add_tilemap_class(&m)?;
+    crate::chunked_tilemap_wrapper::add_chunked_tilemap_class(&m)?;
+    crate::tileset_wrapper::add_tileset_class(&m)?;
+    crate::terrain_wrapper::add_terrain_class(&m)?;
+    crate::layer_wrapper::add_layer_class(&m)?;
+    crate::animation_wrapper::add_animation_class(&m)?;
     crate::channel_wrapper::add_channel_class(&m)?;
     crate::tone_wrapper::add_tone_class(&m)?;
     crate::sound_wrapper::add_sound_class(&m)?;
@@ -2,8 +2,10 @@ use std::sync::Once;
 
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
 
 use crate::pyxel_singleton::pyxel;
+use crate::tilemap_wrapper::Tilemap;
 
 static EXCL_OPTION_ONCE: Once = Once::new();
 
@@ -139,6 +141,25 @@ fn screenshot(scale: Option<u32>) {
     pyxel().screenshot(scale);
 }
 
+#[pyfunction]
+#[pyo3(signature = (scale=None))]
+fn screen_rgba(py: Python<'_>, scale: Option<u32>) -> Bound<'_, PyBytes> {
+    PyBytes::new(py, &pyxel().screen_rgba(scale))
+}
+
+#[pyfunction]
+#[pyo3(signature = (filename, scale=None, tolerance=None))]
+fn compare_screen(
+    filename: &str,
+    scale: Option<u32>,
+    tolerance: Option<u8>,
+) -> PyResult<(bool, String)> {
+    pyxel()
+        .compare_screen(filename, scale, tolerance)
+        .map(|image_diff| (image_diff.is_match(), image_diff.to_string()))
+        .map_err(PyException::new_err)
+}
+
 #[pyfunction]
 #[pyo3(signature = (scale=None))]
 fn screencast(scale: Option<u32>) {
@@ -150,6 +171,114 @@ fn reset_screencast() {
     pyxel().reset_screencast();
 }
 
+fn tmx_properties_to_dict(py: Python, properties: &pyxel::TmxProperties) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new(py);
+    for (name, value) in properties {
+        match value {
+            pyxel::TmxProperty::Bool(value) => dict.set_item(name, value)?,
+            pyxel::TmxProperty::Int(value) => dict.set_item(name, value)?,
+            pyxel::TmxProperty::Float(value) => dict.set_item(name, value)?,
+            pyxel::TmxProperty::String(value) => dict.set_item(name, value)?,
+        }
+    }
+    Ok(dict.unbind())
+}
+
+fn tmx_to_dict(py: Python, tmx: &pyxel::TmxMap) -> PyResult<Py<PyDict>> {
+    let tilesets = tmx
+        .tilesets
+        .iter()
+        .map(|tileset| {
+            let tile_properties = PyDict::new(py);
+            for (tile, properties) in &tileset.tile_properties {
+                tile_properties.set_item(tile, tmx_properties_to_dict(py, properties)?)?;
+            }
+            let dict = PyDict::new(py);
+            dict.set_item("first_gid", tileset.first_gid)?;
+            dict.set_item("columns", tileset.columns)?;
+            dict.set_item("tile_properties", tile_properties)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let layers = tmx
+        .layers
+        .iter()
+        .map(|layer| {
+            let dict = PyDict::new(py);
+            dict.set_item("name", &layer.name)?;
+            dict.set_item("visible", layer.visible)?;
+            dict.set_item("grouped", layer.grouped)?;
+            dict.set_item("tilemap", Tilemap::wrap(layer.tilemap.clone()))?;
+            dict.set_item("properties", tmx_properties_to_dict(py, &layer.properties)?)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let object_groups = tmx
+        .object_groups
+        .iter()
+        .map(|object_group| {
+            let objects = object_group
+                .objects
+                .iter()
+                .map(|object| {
+                    let (shape, points) = match &object.shape {
+                        pyxel::TmxShape::Rectangle => ("rectangle", None),
+                        pyxel::TmxShape::Ellipse => ("ellipse", None),
+                        pyxel::TmxShape::Point => ("point", None),
+                        pyxel::TmxShape::Polygon(points) => ("polygon", Some(points)),
+                        pyxel::TmxShape::Polyline(points) => ("polyline", Some(points)),
+                    };
+                    let dict = PyDict::new(py);
+                    dict.set_item("id", object.id)?;
+                    dict.set_item("name", &object.name)?;
+                    dict.set_item("class", &object.class)?;
+                    dict.set_item("shape", shape)?;
+                    dict.set_item("x", object.x)?;
+                    dict.set_item("y", object.y)?;
+                    dict.set_item("w", object.width)?;
+                    dict.set_item("h", object.height)?;
+                    dict.set_item("rotation", object.rotation)?;
+                    dict.set_item("gid", object.gid)?;
+                    dict.set_item("points", points)?;
+                    dict.set_item(
+                        "properties",
+                        tmx_properties_to_dict(py, &object.properties)?,
+                    )?;
+                    Ok(dict)
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            let dict = PyDict::new(py);
+            dict.set_item("name", &object_group.name)?;
+            dict.set_item("visible", object_group.visible)?;
+            dict.set_item("objects", objects)?;
+            dict.set_item(
+                "properties",
+                tmx_properties_to_dict(py, &object_group.properties)?,
+            )?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("width", tmx.width)?;
+    dict.set_item("height", tmx.height)?;
+    dict.set_item("tile_width", tmx.tile_width)?;
+    dict.set_item("tile_height", tmx.tile_height)?;
+    dict.set_item("properties", tmx_properties_to_dict(py, &tmx.properties)?)?;
+    dict.set_item("tilesets", tilesets)?;
+    dict.set_item("layers", layers)?;
+    dict.set_item("object_groups", object_groups)?;
+    Ok(dict.unbind())
+}
+
+#[pyfunction]
+fn load_tmx(py: Python, filename: &str) -> PyResult<Py<PyDict>> {
+    let tmx = pyxel::TmxMap::load(filename).map_err(PyException::new_err)?;
+    tmx_to_dict(py, &tmx)
+}
+
 #[pyfunction]
 fn user_data_dir(vendor_name: &str, app_name: &str) -> String {
     pyxel().user_data_dir(vendor_name, app_name)
@@ -160,7 +289,10 @@ pub fn add_resource_functions(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(save, m)?)?;
     m.add_function(wrap_pyfunction!(load_pal, m)?)?;
     m.add_function(wrap_pyfunction!(save_pal, m)?)?;
+    m.add_function(wrap_pyfunction!(load_tmx, m)?)?;
     m.add_function(wrap_pyfunction!(screenshot, m)?)?;
+    m.add_function(wrap_pyfunction!(screen_rgba, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_screen, m)?)?;
     m.add_function(wrap_pyfunction!(screencast, m)?)?;
     m.add_function(wrap_pyfunction!(reset_screencast, m)?)?;
     m.add_function(wrap_pyfunction!(user_data_dir, m)?)?;
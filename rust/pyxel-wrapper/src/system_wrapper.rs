@@ -1,6 +1,7 @@
 use std::ffi::CString;
 use std::process::exit;
 
+use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 use pyxel::{Pyxel, PyxelCallback};
@@ -113,11 +114,54 @@ fn integer_scale(enabled: bool) {
     pyxel().integer_scale(enabled);
 }
 
+#[pyfunction]
+fn deterministic(enabled: bool) {
+    pyxel().deterministic(enabled);
+}
+
+#[pyfunction]
+#[pyo3(signature = (seed=None))]
+fn start_recording(seed: Option<u32>) {
+    pyxel().start_recording(seed);
+}
+
+#[pyfunction]
+fn stop_recording() {
+    pyxel().stop_recording();
+}
+
+#[pyfunction]
+fn save_replay(filename: &str) -> PyResult<()> {
+    pyxel().save_replay(filename).map_err(PyException::new_err)
+}
+
+#[pyfunction]
+fn play_replay(filename: &str) -> PyResult<()> {
+    pyxel().play_replay(filename).map_err(PyException::new_err)
+}
+
+#[pyfunction]
+fn is_replaying() -> bool {
+    pyxel().is_replaying()
+}
+
 #[pyfunction]
 fn screen_mode(scr: u32) {
     pyxel().screen_mode(scr);
 }
 
+#[pyfunction]
+fn add_screen_shader(code: &str) -> PyResult<u32> {
+    pyxel()
+        .add_screen_shader(code)
+        .map_err(PyException::new_err)
+}
+
+#[pyfunction]
+fn screen_effects(scrs: Vec<u32>) -> PyResult<()> {
+    pyxel().screen_effects(&scrs).map_err(PyException::new_err)
+}
+
 #[pyfunction]
 fn fullscreen(enabled: bool) {
     pyxel().fullscreen(enabled);
@@ -167,7 +211,15 @@ pub fn add_system_functions(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(icon, m)?)?;
     m.add_function(wrap_pyfunction!(perf_monitor, m)?)?;
     m.add_function(wrap_pyfunction!(integer_scale, m)?)?;
+    m.add_function(wrap_pyfunction!(deterministic, m)?)?;
+    m.add_function(wrap_pyfunction!(start_recording, m)?)?;
+    m.add_function(wrap_pyfunction!(stop_recording, m)?)?;
+    m.add_function(wrap_pyfunction!(save_replay, m)?)?;
+    m.add_function(wrap_pyfunction!(play_replay, m)?)?;
+    m.add_function(wrap_pyfunction!(is_replaying, m)?)?;
     m.add_function(wrap_pyfunction!(screen_mode, m)?)?;
+    m.add_function(wrap_pyfunction!(add_screen_shader, m)?)?;
+    m.add_function(wrap_pyfunction!(screen_effects, m)?)?;
     m.add_function(wrap_pyfunction!(fullscreen, m)?)?;
     m.add_function(wrap_pyfunction!(_set_reset_func, m)?)?;
     m.add_function(wrap_pyfunction!(_reset_statics, m)?)?;
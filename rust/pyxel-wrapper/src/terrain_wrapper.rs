@@ -0,0 +1,55 @@
+use pyo3::prelude::*;
+
+#[pyclass(from_py_object)]
+#[derive(Clone)]
+pub struct Terrain {
+    pub(crate) inner: pyxel::SharedTerrain,
+}
+
+impl Terrain {
+    pub fn wrap(inner: pyxel::SharedTerrain) -> Self {
+        Self { inner }
+    }
+}
+
+#[pymethods]
+impl Terrain {
+    #[new]
+    #[pyo3(signature = (mode=None))]
+    pub fn new(mode: Option<u32>) -> Self {
+        Self::wrap(pyxel::Terrain::new(pyxel::TerrainMode::from(
+            mode.unwrap_or(pyxel::TERRAIN_EDGES),
+        )))
+    }
+
+    #[getter]
+    pub fn mode(&self) -> u32 {
+        match self.inner.lock().mode {
+            pyxel::TerrainMode::Edges => pyxel::TERRAIN_EDGES,
+            pyxel::TerrainMode::Blob => pyxel::TERRAIN_BLOB,
+        }
+    }
+
+    pub fn set_rule(&self, mask: pyxel::TerrainMask, tile: pyxel::TileCoord) {
+        self.inner.lock().set_rule(mask, tile);
+    }
+
+    pub fn get_rule(&self, mask: pyxel::TerrainMask) -> Option<pyxel::TileCoord> {
+        self.inner.lock().rule(mask)
+    }
+
+    pub fn remove_rule(&self, mask: pyxel::TerrainMask) {
+        let mut terrain = self.inner.lock();
+        let mask = terrain.normalize_mask(mask);
+        terrain.rules.remove(&mask);
+    }
+
+    pub fn normalize_mask(&self, mask: pyxel::TerrainMask) -> pyxel::TerrainMask {
+        self.inner.lock().normalize_mask(mask)
+    }
+}
+
+pub fn add_terrain_class(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Terrain>()?;
+    Ok(())
+}
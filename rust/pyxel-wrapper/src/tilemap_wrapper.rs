@@ -1,17 +1,94 @@
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::sync::Once;
 
 use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 
 use crate::image_wrapper::Image;
 use crate::pyxel_singleton::pyxel;
+use crate::terrain_wrapper::Terrain;
+use crate::tileset_wrapper::Tileset;
 
 static IMAGE_ONCE: Once = Once::new();
 static SET_IMAGE_ONCE: Once = Once::new();
 static REFIMG_ONCE: Once = Once::new();
 static SET_REFIMG_ONCE: Once = Once::new();
 
+// Tiles are passed as (x, y) or (x, y, attr) so that code without attributes keeps working
+#[derive(FromPyObject, IntoPyObject)]
+pub enum TileTuple {
+    Attr(
+        (
+            pyxel::ImageTileCoord,
+            pyxel::ImageTileCoord,
+            pyxel::TileAttr,
+        ),
+    ),
+    Plain((pyxel::ImageTileCoord, pyxel::ImageTileCoord)),
+}
+
+impl From<TileTuple> for pyxel::Tile {
+    fn from(tile: TileTuple) -> Self {
+        match tile {
+            TileTuple::Attr(tile) => tile,
+            TileTuple::Plain((x, y)) => (x, y, 0),
+        }
+    }
+}
+
+pub(crate) fn to_tiles(tiles: Option<Vec<TileTuple>>) -> Vec<pyxel::Tile> {
+    tiles
+        .unwrap_or_default()
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
+
+// Tiles listed as walls or marked solid in the tileset block movement and sight
+fn is_blocked(tile: pyxel::Tile, walls: &[pyxel::Tile], tileset: Option<&pyxel::Tileset>) -> bool {
+    walls
+        .iter()
+        .any(|wall| (wall.0, wall.1) == (tile.0, tile.1))
+        || tileset.is_some_and(|tileset| tileset.is_solid(tile))
+}
+
+pub(crate) fn collision_to_dict(
+    py: Python,
+    collision: &pyxel::TileCollision,
+) -> PyResult<Py<PyDict>> {
+    let contacts = collision
+        .contacts
+        .iter()
+        .map(|contact| {
+            let dict = PyDict::new(py);
+            dict.set_item("x", contact.x)?;
+            dict.set_item("y", contact.y)?;
+            dict.set_item("tile", TileTuple::from(contact.tile))?;
+            dict.set_item("normal", contact.normal)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("dx", collision.dx)?;
+    dict.set_item("dy", collision.dy)?;
+    dict.set_item("contacts", contacts)?;
+    dict.set_item("grounded", collision.grounded)?;
+    dict.set_item("ceiling", collision.ceiling)?;
+    dict.set_item("wall_left", collision.wall_left)?;
+    dict.set_item("wall_right", collision.wall_right)?;
+    dict.set_item("on_slope", collision.on_slope)?;
+    Ok(dict.unbind())
+}
+
+impl From<pyxel::Tile> for TileTuple {
+    fn from(tile: pyxel::Tile) -> Self {
+        Self::Attr(tile)
+    }
+}
+
 #[pyclass(from_py_object)]
 #[derive(Clone)]
 pub struct Tilemap {
@@ -27,13 +104,27 @@ impl Tilemap {
 #[pymethods]
 impl Tilemap {
     #[new]
-    pub fn new(width: u32, height: u32, img: Bound<'_, PyAny>) -> PyResult<Self> {
+    #[pyo3(signature = (width, height, img, tile_width=None, tile_height=None))]
+    pub fn new(
+        width: u32,
+        height: u32,
+        img: Bound<'_, PyAny>,
+        tile_width: Option<u32>,
+        tile_height: Option<u32>,
+    ) -> PyResult<Self> {
         let imgsrc = cast_pyany! {
             img,
             (u32, { pyxel::ImageSource::Index(img) }),
             (Image, { pyxel::ImageSource::Image(img.inner) })
         };
-        Ok(Tilemap::wrap(pyxel::Tilemap::new(width, height, imgsrc)))
+        let tilemap = Tilemap::wrap(pyxel::Tilemap::new(width, height, imgsrc));
+        if let Some(tile_width) = tile_width {
+            tilemap.set_tile_width(tile_width)?;
+        }
+        if let Some(tile_height) = tile_height {
+            tilemap.set_tile_height(tile_height)?;
+        }
+        Ok(tilemap)
     }
 
     #[staticmethod]
@@ -73,11 +164,49 @@ impl Tilemap {
         Ok(())
     }
 
+    #[getter]
+    pub fn tile_width(&self) -> u32 {
+        self.inner.lock().tile_width
+    }
+
+    #[setter]
+    pub fn set_tile_width(&self, tile_width: u32) -> PyResult<()> {
+        if tile_width == 0 {
+            return Err(PyException::new_err("Tile width must be positive"));
+        }
+        self.inner.lock().tile_width = tile_width;
+        Ok(())
+    }
+
+    #[getter]
+    pub fn tile_height(&self) -> u32 {
+        self.inner.lock().tile_height
+    }
+
+    #[setter]
+    pub fn set_tile_height(&self, tile_height: u32) -> PyResult<()> {
+        if tile_height == 0 {
+            return Err(PyException::new_err("Tile height must be positive"));
+        }
+        self.inner.lock().tile_height = tile_height;
+        Ok(())
+    }
+
+    #[getter]
+    pub fn tileset(&self) -> Option<Tileset> {
+        self.inner.lock().tileset.clone().map(Tileset::wrap)
+    }
+
+    #[setter]
+    pub fn set_tileset(&self, tileset: Option<Tileset>) {
+        self.inner.lock().tileset = tileset.map(|tileset| tileset.inner);
+    }
+
     pub fn data_ptr(&self, py: Python) -> Py<PyAny> {
         let mut inner = self.inner.lock();
         let python_code = CString::new(format!(
             "import ctypes; c_uint8_array = (ctypes.c_uint8 * {}).from_address({:p})",
-            inner.width() * inner.height() * 2,
+            inner.width() as usize * inner.height() as usize * size_of::<pyxel::Tile>(),
             inner.data_ptr()
         ))
         .unwrap();
@@ -86,6 +215,23 @@ impl Tilemap {
         value_to_pyobj!(py, locals.get_item("c_uint8_array").unwrap())
     }
 
+    // Unlike data_ptr, the tile fields are named so that code does not depend on the tile size
+    pub fn tile_data_ptr(&self, py: Python) -> Py<PyAny> {
+        let mut inner = self.inner.lock();
+        let python_code = CString::new(format!(
+            "import ctypes\n\
+             class Tile(ctypes.Structure):\n    \
+             _fields_ = [('x', ctypes.c_uint8), ('y', ctypes.c_uint8), ('attr', ctypes.c_uint8)]\n\
+             tile_array = (Tile * {}).from_address({:p})",
+            inner.width() as usize * inner.height() as usize,
+            inner.data_ptr()
+        ))
+        .unwrap();
+        let locals = pyo3::types::PyDict::new(py);
+        py.run(python_code.as_c_str(), None, Some(&locals)).unwrap();
+        value_to_pyobj!(py, locals.get_item("tile_array").unwrap())
+    }
+
     pub fn set(&mut self, x: i32, y: i32, data: Vec<String>) {
         let data_refs: Vec<_> = data.iter().map(String::as_str).collect();
         self.inner.lock().set(x, y, &data_refs);
@@ -128,58 +274,218 @@ impl Tilemap {
         Ok(())
     }
 
-    pub fn cls(&self, tile: pyxel::Tile) {
-        self.inner.lock().cls(tile);
+    pub fn cls(&self, tile: TileTuple) {
+        self.inner.lock().cls(tile.into());
+    }
+
+    pub fn pget(&self, x: f32, y: f32) -> TileTuple {
+        self.inner.lock().pget(x, y).into()
     }
 
-    pub fn pget(&self, x: f32, y: f32) -> pyxel::Tile {
-        self.inner.lock().pget(x, y)
+    pub fn pset(&self, x: f32, y: f32, tile: TileTuple) {
+        self.inner.lock().pset(x, y, tile.into());
     }
 
-    pub fn pset(&self, x: f32, y: f32, tile: pyxel::Tile) {
-        self.inner.lock().pset(x, y, tile);
+    pub fn line(&self, x1: f32, y1: f32, x2: f32, y2: f32, tile: TileTuple) {
+        self.inner.lock().line(x1, y1, x2, y2, tile.into());
     }
 
-    pub fn line(&self, x1: f32, y1: f32, x2: f32, y2: f32, tile: pyxel::Tile) {
-        self.inner.lock().line(x1, y1, x2, y2, tile);
+    pub fn rect(&self, x: f32, y: f32, w: f32, h: f32, tile: TileTuple) {
+        self.inner.lock().rect(x, y, w, h, tile.into());
     }
 
-    pub fn rect(&self, x: f32, y: f32, w: f32, h: f32, tile: pyxel::Tile) {
-        self.inner.lock().rect(x, y, w, h, tile);
+    pub fn rectb(&self, x: f32, y: f32, w: f32, h: f32, tile: TileTuple) {
+        self.inner.lock().rectb(x, y, w, h, tile.into());
     }
 
-    pub fn rectb(&self, x: f32, y: f32, w: f32, h: f32, tile: pyxel::Tile) {
-        self.inner.lock().rectb(x, y, w, h, tile);
+    pub fn circ(&self, x: f32, y: f32, r: f32, tile: TileTuple) {
+        self.inner.lock().circ(x, y, r, tile.into());
     }
 
-    pub fn circ(&self, x: f32, y: f32, r: f32, tile: pyxel::Tile) {
-        self.inner.lock().circ(x, y, r, tile);
+    pub fn circb(&self, x: f32, y: f32, r: f32, tile: TileTuple) {
+        self.inner.lock().circb(x, y, r, tile.into());
     }
 
-    pub fn circb(&self, x: f32, y: f32, r: f32, tile: pyxel::Tile) {
-        self.inner.lock().circb(x, y, r, tile);
+    pub fn elli(&self, x: f32, y: f32, w: f32, h: f32, tile: TileTuple) {
+        self.inner.lock().elli(x, y, w, h, tile.into());
     }
 
-    pub fn elli(&self, x: f32, y: f32, w: f32, h: f32, tile: pyxel::Tile) {
-        self.inner.lock().elli(x, y, w, h, tile);
+    pub fn ellib(&self, x: f32, y: f32, w: f32, h: f32, tile: TileTuple) {
+        self.inner.lock().ellib(x, y, w, h, tile.into());
     }
 
-    pub fn ellib(&self, x: f32, y: f32, w: f32, h: f32, tile: pyxel::Tile) {
-        self.inner.lock().ellib(x, y, w, h, tile);
+    pub fn tri(&self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, tile: TileTuple) {
+        self.inner.lock().tri(x1, y1, x2, y2, x3, y3, tile.into());
     }
 
-    pub fn tri(&self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, tile: pyxel::Tile) {
-        self.inner.lock().tri(x1, y1, x2, y2, x3, y3, tile);
+    pub fn trib(&self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, tile: TileTuple) {
+        self.inner.lock().trib(x1, y1, x2, y2, x3, y3, tile.into());
     }
 
-    pub fn trib(&self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, tile: pyxel::Tile) {
-        self.inner.lock().trib(x1, y1, x2, y2, x3, y3, tile);
+    #[pyo3(signature = (pts, tile, rule=None))]
+    pub fn poly(&self, pts: Vec<(f32, f32)>, tile: TileTuple, rule: Option<u32>) {
+        self.inner.lock().poly(
+            &pts,
+            tile.into(),
+            pyxel::FillRule::from(rule.unwrap_or(pyxel::FILL_RULE_EVEN_ODD)),
+        );
     }
 
-    pub fn fill(&self, x: f32, y: f32, tile: pyxel::Tile) {
-        self.inner.lock().fill(x, y, tile);
+    pub fn polyb(&self, pts: Vec<(f32, f32)>, tile: TileTuple) {
+        self.inner.lock().polyb(&pts, tile.into());
     }
 
+    #[pyo3(signature = (x1, y1, x2, y2, w, tile, cap=None))]
+    pub fn thick_line(
+        &self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        w: f32,
+        tile: TileTuple,
+        cap: Option<u32>,
+    ) {
+        self.inner.lock().thick_line(
+            x1,
+            y1,
+            x2,
+            y2,
+            w,
+            tile.into(),
+            pyxel::LineCap::from(cap.unwrap_or(pyxel::LINE_CAP_BUTT)),
+        );
+    }
+
+    pub fn arc(&self, x: f32, y: f32, r: f32, a1: f32, a2: f32, tile: TileTuple) {
+        self.inner.lock().arc(x, y, r, a1, a2, tile.into());
+    }
+
+    pub fn pie(&self, x: f32, y: f32, r: f32, a1: f32, a2: f32, tile: TileTuple) {
+        self.inner.lock().pie(x, y, r, a1, a2, tile.into());
+    }
+
+    pub fn bezier(&self, pts: Vec<(f32, f32)>, tile: TileTuple) {
+        self.inner.lock().bezier(&pts, tile.into());
+    }
+
+    pub fn fill(&self, x: f32, y: f32, tile: TileTuple) {
+        self.inner.lock().fill(x, y, tile.into());
+    }
+
+    pub fn paint_terrain(&self, x: i32, y: i32, terrain: Terrain) {
+        let terrain = terrain.inner.lock();
+        self.inner.lock().paint_terrain(x, y, &terrain);
+    }
+
+    #[pyo3(signature = (terrain, x=None, y=None, w=None, h=None))]
+    pub fn resolve_terrain(
+        &self,
+        terrain: Terrain,
+        x: Option<i32>,
+        y: Option<i32>,
+        w: Option<i32>,
+        h: Option<i32>,
+    ) {
+        let terrain = terrain.inner.lock();
+        let mut tilemap = self.inner.lock();
+        let w = w.unwrap_or(tilemap.width() as i32);
+        let h = h.unwrap_or(tilemap.height() as i32);
+        tilemap.resolve_terrain(x.unwrap_or(0), y.unwrap_or(0), w, h, &terrain);
+    }
+
+    pub fn terrain_mask(&self, x: i32, y: i32, terrain: Terrain) -> pyxel::TerrainMask {
+        let terrain = terrain.inner.lock();
+        self.inner.lock().terrain_mask(x, y, &terrain)
+    }
+
+    #[pyo3(signature = (x1, y1, x2, y2, walls=None, diagonal=None, costs=None))]
+    pub fn find_path(
+        &self,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        walls: Option<Vec<TileTuple>>,
+        diagonal: Option<u32>,
+        costs: Option<HashMap<pyxel::TileCoord, f32>>,
+    ) -> Option<Vec<pyxel::TilePos>> {
+        let walls = to_tiles(walls);
+        let costs = costs.unwrap_or_default();
+        let diagonal = pyxel::DiagonalRule::from(diagonal.unwrap_or(pyxel::DIAGONAL_NONE));
+        let tilemap = self.inner.lock();
+        let tileset = tilemap.tileset.as_ref().map(|tileset| tileset.lock());
+
+        tilemap.find_path((x1, y1), (x2, y2), diagonal, |tile| {
+            if is_blocked(tile, &walls, tileset.as_deref()) {
+                None
+            } else {
+                Some(costs.get(&(tile.0, tile.1)).copied().unwrap_or(1.0))
+            }
+        })
+    }
+
+    #[pyo3(signature = (x, y, walls=None, diagonal=None, costs=None, max_cost=None))]
+    pub fn reachable(
+        &self,
+        x: i32,
+        y: i32,
+        walls: Option<Vec<TileTuple>>,
+        diagonal: Option<u32>,
+        costs: Option<HashMap<pyxel::TileCoord, f32>>,
+        max_cost: Option<f32>,
+    ) -> Vec<pyxel::TilePos> {
+        let walls = to_tiles(walls);
+        let costs = costs.unwrap_or_default();
+        let diagonal = pyxel::DiagonalRule::from(diagonal.unwrap_or(pyxel::DIAGONAL_NONE));
+        let tilemap = self.inner.lock();
+        let tileset = tilemap.tileset.as_ref().map(|tileset| tileset.lock());
+
+        tilemap.reachable((x, y), diagonal, max_cost, |tile| {
+            if is_blocked(tile, &walls, tileset.as_deref()) {
+                None
+            } else {
+                Some(costs.get(&(tile.0, tile.1)).copied().unwrap_or(1.0))
+            }
+        })
+    }
+
+    #[pyo3(signature = (x1, y1, x2, y2, walls=None))]
+    pub fn line_of_sight(
+        &self,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        walls: Option<Vec<TileTuple>>,
+    ) -> bool {
+        let walls = to_tiles(walls);
+        let tilemap = self.inner.lock();
+        let tileset = tilemap.tileset.as_ref().map(|tileset| tileset.lock());
+
+        tilemap.line_of_sight((x1, y1), (x2, y2), |tile| {
+            is_blocked(tile, &walls, tileset.as_deref())
+        })
+    }
+
+    #[pyo3(signature = (x, y, radius, walls=None))]
+    pub fn field_of_view(
+        &self,
+        x: i32,
+        y: i32,
+        radius: u32,
+        walls: Option<Vec<TileTuple>>,
+    ) -> Vec<pyxel::TilePos> {
+        let walls = to_tiles(walls);
+        let tilemap = self.inner.lock();
+        let tileset = tilemap.tileset.as_ref().map(|tileset| tileset.lock());
+
+        tilemap.field_of_view((x, y), radius, |tile| {
+            is_blocked(tile, &walls, tileset.as_deref())
+        })
+    }
+
+    #[pyo3(signature = (x, y, w, h, dx, dy, walls=None))]
     pub fn collide(
         &self,
         x: f32,
@@ -188,11 +494,29 @@ impl Tilemap {
         h: f32,
         dx: f32,
         dy: f32,
-        walls: Vec<pyxel::Tile>,
+        walls: Option<Vec<TileTuple>>,
     ) -> (f32, f32) {
+        let walls = to_tiles(walls);
         self.inner.lock().collide(x, y, w, h, dx, dy, &walls)
     }
 
+    #[pyo3(signature = (x, y, w, h, dx, dy, walls=None))]
+    pub fn sweep(
+        &self,
+        py: Python,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        dx: f32,
+        dy: f32,
+        walls: Option<Vec<TileTuple>>,
+    ) -> PyResult<Py<PyDict>> {
+        let walls = to_tiles(walls);
+        let collision = self.inner.lock().sweep(x, y, w, h, dx, dy, &walls);
+        collision_to_dict(py, &collision)
+    }
+
     #[pyo3(signature = (x, y, tm, u, v, w, h, tilekey=None, rotate=None, scale=None))]
     pub fn blt(
         &self,
@@ -203,10 +527,11 @@ impl Tilemap {
         v: f32,
         w: f32,
         h: f32,
-        tilekey: Option<pyxel::Tile>,
+        tilekey: Option<TileTuple>,
         rotate: Option<f32>,
         scale: Option<f32>,
     ) -> PyResult<()> {
+        let tilekey = tilekey.map(pyxel::Tile::from);
         cast_pyany! {
             tm,
             (u32, {
@@ -0,0 +1,95 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::tilemap_wrapper::TileTuple;
+
+#[pyclass(from_py_object)]
+#[derive(Clone)]
+pub struct Tileset {
+    pub(crate) inner: pyxel::SharedTileset,
+}
+
+impl Tileset {
+    pub fn wrap(inner: pyxel::SharedTileset) -> Self {
+        Self { inner }
+    }
+}
+
+#[pymethods]
+impl Tileset {
+    #[new]
+    pub fn new() -> Self {
+        Self::wrap(pyxel::Tileset::new())
+    }
+
+    #[pyo3(signature = (tile, *, frames=None, solid=None, one_way=None, slope=None, damage=None, friction=None))]
+    pub fn set_tile(
+        &self,
+        tile: TileTuple,
+        frames: Option<Vec<(pyxel::ImageTileCoord, pyxel::ImageTileCoord, u32)>>,
+        solid: Option<bool>,
+        one_way: Option<bool>,
+        slope: Option<(f32, f32)>,
+        damage: Option<i32>,
+        friction: Option<f32>,
+    ) {
+        let mut tileset = self.inner.lock();
+        let info = tileset.tile_mut(tile.into());
+
+        if let Some(frames) = frames {
+            info.frames = frames
+                .into_iter()
+                .map(|(x, y, duration)| pyxel::TileFrame {
+                    tile: (x, y),
+                    duration,
+                })
+                .collect();
+        }
+        if let Some(solid) = solid {
+            info.solid = solid;
+        }
+        if let Some(one_way) = one_way {
+            info.one_way = one_way;
+        }
+        if slope.is_some() {
+            info.slope = slope;
+        }
+        if let Some(damage) = damage {
+            info.damage = damage;
+        }
+        if let Some(friction) = friction {
+            info.friction = friction;
+        }
+    }
+
+    pub fn get_tile(&self, py: Python, tile: TileTuple) -> PyResult<Option<Py<PyDict>>> {
+        let tileset = self.inner.lock();
+        let Some(info) = tileset.tile(tile.into()) else {
+            return Ok(None);
+        };
+
+        let frames: Vec<_> = info
+            .frames
+            .iter()
+            .map(|frame| (frame.tile.0, frame.tile.1, frame.duration))
+            .collect();
+        let dict = PyDict::new(py);
+        dict.set_item("frames", frames)?;
+        dict.set_item("solid", info.solid)?;
+        dict.set_item("one_way", info.one_way)?;
+        dict.set_item("slope", info.slope)?;
+        dict.set_item("damage", info.damage)?;
+        dict.set_item("friction", info.friction)?;
+        Ok(Some(dict.unbind()))
+    }
+
+    pub fn remove_tile(&self, tile: TileTuple) {
+        let tile: pyxel::Tile = tile.into();
+        self.inner.lock().tiles.remove(&(tile.0, tile.1));
+    }
+}
+
+pub fn add_tileset_class(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Tileset>()?;
+    Ok(())
+}
@@ -1,8 +1,10 @@
 use pyo3::exceptions::PyAttributeError;
 use pyo3::prelude::*;
 
+use crate::animation_wrapper::Animation;
 use crate::channel_wrapper::Channel;
 use crate::image_wrapper::Image;
+use crate::layer_wrapper::Layer;
 use crate::music_wrapper::Music;
 use crate::pyxel_singleton::pyxel;
 use crate::sound_wrapper::Sound;
@@ -47,6 +49,8 @@ macro_rules! wrap_shared_vec_as_python_list {
 
 wrap_shared_vec_as_python_list!(Images, Image, images);
 wrap_shared_vec_as_python_list!(Tilemaps, Tilemap, tilemaps);
+wrap_shared_vec_as_python_list!(Layers, Layer, layers);
+wrap_shared_vec_as_python_list!(Animations, Animation, animations);
 wrap_shared_vec_as_python_list!(Channels, Channel, channels);
 wrap_shared_vec_as_python_list!(Tones, Tone, tones);
 wrap_shared_vec_as_python_list!(Sounds, Sound, sounds);
@@ -73,6 +77,8 @@ fn __getattr__(py: Python, name: &str) -> PyResult<Py<PyAny>> {
         "images" => class_to_pyobj!(py, Images::wrap(0)),
         "tilemaps" => class_to_pyobj!(py, Tilemaps::wrap(0)),
         "screen" => class_to_pyobj!(py, Image::wrap(pyxel().screen.clone())),
+        "layers" => class_to_pyobj!(py, Layers::wrap(0)),
+        "animations" => class_to_pyobj!(py, Animations::wrap(0)),
         "cursor" => class_to_pyobj!(py, Image::wrap(pyxel().cursor.clone())),
         "font" => class_to_pyobj!(py, Image::wrap(pyxel().font.clone())),
 